@@ -0,0 +1,186 @@
+//! 多核心相关操作
+use core::arch::asm;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use riscv::register::sip;
+
+use crate::memory::addr::PAGE_SIZE;
+use crate::sync::mutex::Mutex;
+use crate::sbi::{hart_start, hart_get_status, HART_STATE_STOPPED};
+use crate::sbi::{send_ipi, remote_sfence_vma};
+
+// 支持的最大核心数量 需要与 entry.asm 中的 MAX_HARTS 一致
+pub const MAX_HARTS: usize = 4;
+
+// 主核心id 初始值不为0 保证位于.data段 不会被清空bss段影响
+static BOOT_HART: AtomicUsize = AtomicUsize::new(usize::MAX);
+// 主核心是否已经完成初始化
+static BOOT_DONE: AtomicBool = AtomicBool::new(false);
+// 参与任务调度的核心掩码 只有这些核心可能缓存用户页表的TLB
+static ONLINE_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+// 超过这个大小的TLB刷新直接刷新全部TLB
+const FLUSH_ALL_THRESHOLD: usize = 64 * PAGE_SIZE;
+
+// 核间调用请求 由发起核心填写 目标核心执行后清除pending
+struct CrossCall {
+    func: AtomicUsize,      // fn(usize) 的地址
+    arg: AtomicUsize,       // 调用参数
+    pending: AtomicBool     // 是否有未执行的请求
+}
+
+impl CrossCall {
+    const fn new() -> Self {
+        Self {
+            func: AtomicUsize::new(0),
+            arg: AtomicUsize::new(0),
+            pending: AtomicBool::new(false)
+        }
+    }
+}
+
+// 每个核心的核间调用请求
+static CROSS_CALLS: [CrossCall; MAX_HARTS] = [CrossCall::new(), CrossCall::new(), CrossCall::new(), CrossCall::new()];
+// 同一时间只有一个核心发起核间调用 等待锁时执行发给当前核心的请求
+static CROSS_CALL_LOCK: Mutex<()> = Mutex::new(());
+
+// 获取当前核心id 内核态下tp寄存器保存核心id
+#[inline]
+pub fn hart_id() -> usize {
+    let hart_id;
+    unsafe {
+        asm!("mv {}, tp", out(reg) hart_id);
+    }
+    hart_id
+}
+
+// 竞争成为主核心 返回是否成功
+pub fn claim_boot_hart(hart_id: usize) -> bool {
+    BOOT_HART.compare_exchange(usize::MAX, hart_id, Ordering::AcqRel, Ordering::Acquire).is_ok()
+}
+
+// 判断当前核心是否为主核心
+pub fn is_boot_hart() -> bool {
+    BOOT_HART.load(Ordering::Acquire) == hart_id()
+}
+
+// 等待主核心完成初始化
+pub fn wait_boot_done() {
+    while !BOOT_DONE.load(Ordering::Acquire) {
+        core::hint::spin_loop();
+    }
+}
+
+// 主核心初始化完成 启动处于停止状态的辅助核心
+pub fn start_secondary_harts() {
+    extern "C" {
+        fn _secondary_start();
+    }
+    BOOT_DONE.store(true, Ordering::Release);
+    let boot_hart = hart_id();
+    for i in 0..MAX_HARTS {
+        if i == boot_hart {
+            continue;
+        }
+        // 已经由sbi启动的核心在 rust_main 中等待初始化完成
+        let status = hart_get_status(i);
+        if status.error == 0 && status.value == HART_STATE_STOPPED {
+            let ret = hart_start(i, _secondary_start as usize, 0);
+            info!("启动核心 {} 结果: {}", i, ret.error as isize);
+        }
+    }
+}
+
+// 当前核心开始参与任务调度
+pub fn set_online() {
+    ONLINE_HARTS.fetch_or(1 << hart_id(), Ordering::AcqRel);
+}
+
+// 获取除当前核心以外参与调度的核心掩码
+fn other_online_harts() -> usize {
+    ONLINE_HARTS.load(Ordering::Acquire) & !(1 << hart_id())
+}
+
+// 在hart_mask中的核心上执行func(arg) 等待所有核心执行完成后返回
+// 其他核心在用户态时由核间中断进入内核 在内核中自旋等待锁时执行请求
+pub fn cross_call(hart_mask: usize, func: fn(usize), arg: usize) {
+    let _guard = CROSS_CALL_LOCK.lock();
+    let hart_id = hart_id();
+    let remote_mask = hart_mask & !(1 << hart_id) & ((1 << MAX_HARTS) - 1);
+    for i in 0..MAX_HARTS {
+        if remote_mask & (1 << i) == 0 {
+            continue;
+        }
+        let call = &CROSS_CALLS[i];
+        call.func.store(func as usize, Ordering::Relaxed);
+        call.arg.store(arg, Ordering::Relaxed);
+        call.pending.store(true, Ordering::Release);
+    }
+    if remote_mask != 0 {
+        send_ipi(remote_mask, 0);
+    }
+    // 当前核心直接执行
+    if hart_mask & (1 << hart_id) != 0 {
+        func(arg);
+    }
+    // 等待其他核心完成
+    for i in 0..MAX_HARTS {
+        if remote_mask & (1 << i) == 0 {
+            continue;
+        }
+        while CROSS_CALLS[i].pending.load(Ordering::Acquire) {
+            handle_cross_call();
+            core::hint::spin_loop();
+        }
+    }
+}
+
+// 执行发给当前核心的核间调用请求
+pub fn handle_cross_call() {
+    // 先清除软件中断再检查请求 避免返回用户态后再次触发或空闲时无法等待
+    unsafe {
+        sip::clear_ssoft();
+    }
+    let call = &CROSS_CALLS[hart_id()];
+    if !call.pending.load(Ordering::Acquire) {
+        return;
+    }
+    let func: fn(usize) = unsafe { core::mem::transmute(call.func.load(Ordering::Relaxed)) };
+    func(call.arg.load(Ordering::Relaxed));
+    call.pending.store(false, Ordering::Release);
+}
+
+// 刷新当前核心 [start, start + size) 范围内的TLB
+fn local_flush_tlb(start: usize, size: usize) {
+    if size > FLUSH_ALL_THRESHOLD {
+        local_flush_tlb_all(0);
+        return;
+    }
+    let mut addr = start / PAGE_SIZE * PAGE_SIZE;
+    while addr < start + size {
+        unsafe {
+            asm!("sfence.vma {}, zero", in(reg) addr);
+        }
+        addr += PAGE_SIZE;
+    }
+}
+
+// 刷新当前核心的全部TLB 作为核间调用的回调
+fn local_flush_tlb_all(_arg: usize) {
+    unsafe {
+        asm!("sfence.vma");
+    }
+}
+
+// 修改页表后刷新所有核心上 [start, start + size) 范围内的TLB
+// 优先使用SBI的远程刷新 不支持时通过核间调用刷新
+pub fn tlb_shootdown(start: usize, size: usize) {
+    local_flush_tlb(start, size);
+    let remote_mask = other_online_harts();
+    if remote_mask == 0 {
+        return;
+    }
+    if remote_sfence_vma(remote_mask, 0, start, size).error != 0 {
+        cross_call(remote_mask, local_flush_tlb_all, 0);
+    }
+}
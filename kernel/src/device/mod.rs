@@ -1,9 +1,9 @@
 pub mod block;
 pub mod sdcard;
 
-use alloc::borrow::ToOwned;
 use alloc::boxed::Box;
 use alloc::rc::Rc;
+use core::ops::Deref;
 use alloc::vec::Vec;
 use fatfs::{Dir as OtherDir, File as OtherFile, FileSystem as OtherFileSystem};
 use fatfs::LossyOemCpConverter;
@@ -24,22 +24,34 @@ pub type FileSystem = OtherFileSystem<DiskCursor, NullTimeProvider, LossyOemCpCo
 #[cfg(not(feature = "board_k210"))]
 pub const VIRTIO0: usize = 0x10001000;
 
-// 存储设备控制器 用来存储读取设备
-pub static mut BLK_CONTROL: Vec<Box<dyn BlockDevice>> = vec![];
-
 lazy_static! {
-    pub static ref GLOBAL_FS: Mutex<Rc<FileSystem>> = {
+    // 存储设备控制器 用来存储读取设备
+    pub static ref BLK_CONTROL: Mutex<Vec<Box<dyn BlockDevice>>> = Mutex::new(Vec::new());
+    pub static ref GLOBAL_FS: Mutex<FsHandle> = {
         let c = DiskCursor {
             sector: 0,
             offset: 0,
             disk_index: 0
         };
-        Mutex::new(Rc::new(fatfs::FileSystem::new(c, fatfs::FsOptions::new()).expect("文件系统初始化失败")))
+        Mutex::new(FsHandle(Rc::new(fatfs::FileSystem::new(c, fatfs::FsOptions::new()).expect("文件系统初始化失败"))))
     };
 }
 
+// fatfs内部使用Rc和RefCell 文件系统和其中的文件、目录只在持有GLOBAL_FS时访问
+pub struct FsHandle(Rc<FileSystem>);
+
+unsafe impl Send for FsHandle {}
+
+impl Deref for FsHandle {
+    type Target = Rc<FileSystem>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
 /// 定义trait
-pub trait BlockDevice {
+pub trait BlockDevice: Send {
     // 读取扇区
     fn read_block(&mut self, sector_offset: usize, buf: &mut [u8]);
     // 写入扇区
@@ -54,9 +66,7 @@ pub fn add_virt_io(virtio: usize) {
         VirtIOBlk::new(unsafe {&mut *(virtio as *mut VirtIOHeader)}).expect("failed to create blk driver")
     ));
     // 加入设备表
-    unsafe {
-        BLK_CONTROL.push(device)
-    };
+    BLK_CONTROL.lock().push(device);
 }
 
 #[allow(unused)]
@@ -65,9 +75,7 @@ pub fn add_sdcard() {
     let block_device = Box::new(SDCardWrapper::new());
 
     // 加入存储设备表
-    unsafe {
-        BLK_CONTROL.push(block_device);
-    }
+    BLK_CONTROL.lock().push(block_device);
 }
 
 // 初始化函数
@@ -86,7 +94,7 @@ pub fn init() {
 }
 
 pub fn root_dir() -> Dir {
-    GLOBAL_FS.lock().to_owned().root_dir()
+    GLOBAL_FS.lock().clone().root_dir()
 }
 
 /// 硬盘数据读取器
@@ -132,7 +140,8 @@ impl fatfs::IoBase for DiskCursor {
 impl fatfs::Read for DiskCursor {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, RuntimeError> {
         // 获取硬盘设备写入器（驱动？）
-        let block_device = unsafe { &mut BLK_CONTROL[self.disk_index] };
+        let mut blk_control = BLK_CONTROL.lock();
+        let block_device = &mut blk_control[self.disk_index];
 
         let mut i = 0;
         let mut data = [0u8; 512];
@@ -159,7 +168,8 @@ impl fatfs::Read for DiskCursor {
 impl fatfs::Write for DiskCursor {
     fn write(&mut self, buf: &[u8]) -> Result<usize, RuntimeError> {
         // 获取硬盘设备写入器（驱动？）
-        let block_device = unsafe { &mut BLK_CONTROL[self.disk_index] };
+        let mut blk_control = BLK_CONTROL.lock();
+        let block_device = &mut blk_control[self.disk_index];
 
         let mut data = [0u8; 512];
 
@@ -5,7 +5,7 @@
 use crate::sync::mutex::Mutex;
 
 use super::BlockDevice;
-use core::{convert::TryInto, cell::RefCell};
+use core::{convert::TryInto, cell::RwLock};
 use k210_hal::prelude::*;
 use k210_pac::{Peripherals, SPI0};
 use k210_soc::{
@@ -740,11 +740,11 @@ fn init_sdcard() -> SDCard<SPIImpl<SPI0>> {
     sd
 }
 
-pub struct SDCardWrapper(RefCell<SDCard<SPIImpl<SPI0>>>);
+pub struct SDCardWrapper(RwLock<SDCard<SPIImpl<SPI0>>>);
 
 impl SDCardWrapper {
     pub fn new() -> Self {
-        unsafe { Self(RefCell::new(init_sdcard())) }
+        unsafe { Self(RwLock::new(init_sdcard())) }
     }
 
     pub fn wait_for_one_sec() {
@@ -756,10 +756,10 @@ impl SDCardWrapper {
 
 impl BlockDevice for SDCardWrapper {
     fn read_block(&mut self, block_id: usize, buf: &mut [u8]) {
-        // self.0.borrow()
+        // self.0.read()
         //     .read_sector(buf, block_id as u32)
         //     .unwrap();
-        let sd_card = self.0.borrow();
+        let sd_card = self.0.read();
         let mut result = sd_card.read_sector(buf, block_id as u32);
         let mut cont_cnt = 0;
         while result.is_err() {
@@ -787,7 +787,7 @@ impl BlockDevice for SDCardWrapper {
     }
     fn write_block(&mut self, block_id: usize, buf: &mut [u8]) {
         self.0
-            .borrow()
+            .read()
             .write_sector(buf, block_id as u32)
             .unwrap();
     }
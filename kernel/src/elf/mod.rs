@@ -30,6 +30,85 @@ pub const AT_EXECFN: usize = 31;
 pub const AT_SYSINFO: usize = 32;
 pub const AT_SYSINFO_EHDR: usize = 33;
 
+// 支持的扩展 IMAFDC 扩展字母相对'a'的偏移作为位
+pub const HWCAP_ISA: usize = (1 << (b'i' - b'a')) | (1 << (b'm' - b'a')) | (1 << (b'a' - b'a'))
+    | (1 << (b'f' - b'a')) | (1 << (b'd' - b'a')) | (1 << (b'c' - b'a'));
+
+// times等接口使用的每秒时钟滴答数
+pub const CLOCK_TICKS: usize = 100;
+
+// vDSO中各部分的偏移 ELF头之后依次为程序头 哈希表 符号表 字符串表和动态段
+const VDSO_PHDR: usize = 0x40;
+const VDSO_HASH: usize = 0xb0;
+const VDSO_DYNSYM: usize = 0xc0;
+const VDSO_DYNSTR: usize = 0xd8;
+const VDSO_DYNAMIC: usize = 0xf0;
+pub const VDSO_SIZE: usize = 0x160;
+
+// vDSO的字符串表 只包含soname
+const VDSO_STRTAB: &[u8] = b"\0linux-vdso.so.1\0";
+
+// 最小的vDSO镜像 包含PT_LOAD和PT_DYNAMIC 符号表中只有空符号
+// libc可以正常解析 找不到符号时使用系统调用
+pub fn vdso_image() -> [u8; VDSO_SIZE] {
+    let mut image = [0u8; VDSO_SIZE];
+    let mut put = |offset: usize, bytes: &[u8]| {
+        image[offset..offset + bytes.len()].copy_from_slice(bytes);
+    };
+    // e_ident: 魔数 64位 小端 版本1
+    put(0, &[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+    // e_type ET_DYN e_machine EM_RISCV e_version
+    put(16, &3u16.to_le_bytes());
+    put(18, &243u16.to_le_bytes());
+    put(20, &1u32.to_le_bytes());
+    // e_phoff 紧跟在ELF头之后 没有节头
+    put(32, &(VDSO_PHDR as u64).to_le_bytes());
+    // e_flags RVC 双精度浮点ABI
+    put(48, &5u32.to_le_bytes());
+    // e_ehsize e_phentsize e_phnum
+    put(52, &64u16.to_le_bytes());
+    put(54, &56u16.to_le_bytes());
+    put(56, &2u16.to_le_bytes());
+
+    // 程序头 (类型, 权限, 偏移, 大小, 对齐) 虚拟地址与偏移相同
+    let dynamic_size = VDSO_SIZE - VDSO_DYNAMIC;
+    let phdrs = [
+        (1u32, 5u32, 0, VDSO_SIZE, PAGE_SIZE),          // PT_LOAD R|X
+        (2u32, 4u32, VDSO_DYNAMIC, dynamic_size, 8)     // PT_DYNAMIC R
+    ];
+    for (i, (p_type, p_flags, offset, size, align)) in phdrs.into_iter().enumerate() {
+        let ph = VDSO_PHDR + i * 56;
+        put(ph, &p_type.to_le_bytes());
+        put(ph + 4, &p_flags.to_le_bytes());
+        put(ph + 8, &(offset as u64).to_le_bytes());
+        put(ph + 16, &(offset as u64).to_le_bytes());
+        put(ph + 24, &(offset as u64).to_le_bytes());
+        put(ph + 32, &(size as u64).to_le_bytes());
+        put(ph + 40, &(size as u64).to_le_bytes());
+        put(ph + 48, &(align as u64).to_le_bytes());
+    }
+
+    // 哈希表 一个桶 一个链 符号表中只有空符号
+    put(VDSO_HASH, &1u32.to_le_bytes());
+    put(VDSO_HASH + 4, &1u32.to_le_bytes());
+    put(VDSO_DYNSTR, VDSO_STRTAB);
+
+    // 动态段 DT_SONAME DT_HASH DT_STRTAB DT_SYMTAB DT_STRSZ DT_SYMENT 最后为DT_NULL
+    let dynamic = [
+        (14u64, 1),
+        (4, VDSO_HASH),
+        (5, VDSO_DYNSTR),
+        (6, VDSO_DYNSYM),
+        (10, VDSO_STRTAB.len()),
+        (11, 24)
+    ];
+    for (i, (tag, value)) in dynamic.into_iter().enumerate() {
+        put(VDSO_DYNAMIC + i * 16, &tag.to_le_bytes());
+        put(VDSO_DYNAMIC + i * 16 + 8, &(value as u64).to_le_bytes());
+    }
+    image
+}
+
 use alloc::vec::Vec;
 use xmas_elf::{ElfFile, program::Type, sections::SectionData, symbol_table::{DynEntry64, Entry}};
 
@@ -1,20 +1,47 @@
+# 支持的最大核心数量 需要与 cpu.rs 中的 MAX_HARTS 一致
+    .equ MAX_HARTS, 4
+# 每个核心的启动栈大小 64K
+    .equ BOOT_STACK_SHIFT, 16
+
 # 代码空间
     .section .text.entry
     .globl _start
 # 操作系统入口函数
 _start:
-    # 堆栈初始化
+    # 超出支持数量的核心不参与运行
+    li t0, MAX_HARTS
+    bgeu a0, t0, _park
+    # tp 保存核心id
+    mv tp, a0
+    # 堆栈初始化 每个核心使用独立的栈
     la sp, boot_stack_top
+    slli t0, a0, BOOT_STACK_SHIFT
+    sub sp, sp, t0
     # 进入rust主函数
     call rust_main
 
+    .globl _secondary_start
+# 辅助核心入口函数 由主核心通过 sbi hart_start 启动
+_secondary_start:
+    li t0, MAX_HARTS
+    bgeu a0, t0, _park
+    mv tp, a0
+    la sp, boot_stack_top
+    slli t0, a0, BOOT_STACK_SHIFT
+    sub sp, sp, t0
+    call rust_secondary_main
+
+_park:
+    wfi
+    j _park
+
     # 回忆：bss 段是 ELF 文件中只记录长度，而全部初始化为 0 的一段内存空间
     # 这里声明字段 .bss.stack 作为操作系统启动时的栈
     .section .bss.stack
     .global boot_stack
 boot_stack:
-    # 16K 启动栈大小
-    .space 4096 * 16
+    # 每个核心 64K 启动栈大小
+    .space 4096 * 16 * MAX_HARTS
     .global boot_stack_top
 boot_stack_top:
     # 栈结尾
\ No newline at end of file
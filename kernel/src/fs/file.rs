@@ -1,7 +1,7 @@
 use core::any::{Any, TypeId};
 
-use alloc::rc::Rc;
-use core::cell::RefCell;
+use alloc::sync::Arc;
+use crate::sync::rwlock::RwLock;
 
 
 use crate::memory::mem_map::MemMap;
@@ -10,6 +10,10 @@ use crate::memory::addr::{get_buf_from_phys_page, get_pages_num, PAGE_SIZE, Virt
 use crate::memory::page::alloc_more;
 use crate::memory::page_table::{PageMappingManager, PTEFlags};
 
+use crate::task::task::Task;
+use crate::interrupt::timer::TimeSpec;
+use crate::sys_call::consts::{EISDIR, ENOSPC};
+
 use super::filetree::INode;
 
 #[allow(unused)]
@@ -43,10 +47,46 @@ pub enum FileType {
     Directory,      // 文件夹
     Device,         // 设备
     Pipeline,       // 管道
+    Link,           // 符号链接
     None            // 空
 }
 
+// 文件模式中的文件类型
+pub const S_IFMT: u32   = 0o170000;
+pub const S_IFSOCK: u32 = 0o140000;
+pub const S_IFLNK: u32  = 0o120000;
+pub const S_IFREG: u32  = 0o100000;
+pub const S_IFDIR: u32  = 0o040000;
+pub const S_IFCHR: u32  = 0o020000;
+pub const S_IFIFO: u32  = 0o010000;
+
+// 块大小 st_blocks 以512字节为单位
+pub const STAT_BLOCK_SIZE: usize = 512;
+
+// 生成设备号 与glibc的makedev相同
+pub const fn makedev(major: u64, minor: u64) -> u64 {
+    ((major & 0xfffff000) << 32) | ((major & 0xfff) << 8)
+        | ((minor & 0xffffff00) << 12) | (minor & 0xff)
+}
+
+// 设备号的主设备号和次设备号
+pub const fn major(dev: u64) -> u32 {
+    (((dev >> 32) & 0xfffff000) | ((dev >> 8) & 0xfff)) as u32
+}
+
+pub const fn minor(dev: u64) -> u32 {
+    (((dev >> 12) & 0xffffff00) | (dev & 0xff)) as u32
+}
+
+// 文件所在的设备
+pub const DEV_ROOT: u64 = makedev(254, 0);      // 根文件系统所在的块设备
+pub const DEV_PIPE: u64 = makedev(0, 12);       // 管道
+pub const DEV_SOCK: u64 = makedev(0, 8);        // 套接字
+pub const DEV_DEVTMPFS: u64 = makedev(0, 5);    // /dev 下的设备文件
+pub const DEV_PROC: u64 = makedev(0, 22);       // /proc 下的文件
+
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub struct Kstat {
 	pub st_dev: u64,			// 设备号
 	pub st_ino: u64,			// inode
@@ -66,34 +106,191 @@ pub struct Kstat {
 	pub st_mtime_nsec: u64,		// 最后修改微秒
 	pub st_ctime_sec: u64,		// 最后创建秒
 	pub st_ctime_nsec: u64,		// 最后创建微秒
+	pub __unused: [u32; 2],		// 保留
+}
+
+impl Kstat {
+    // 创建文件信息 时间为当前时间
+    pub fn new(dev: u64, ino: u64, mode: u32, size: usize) -> Self {
+        let now = TimeSpec::now();
+        Self {
+            st_dev: dev,
+            st_ino: ino,
+            st_mode: mode,
+            st_nlink: 1,
+            st_uid: 0,
+            st_gid: 0,
+            st_rdev: 0,
+            __pad: 0,
+            st_size: size as u64,
+            st_blksize: STAT_BLOCK_SIZE as u32,
+            __pad2: 0,
+            st_blocks: ((size + STAT_BLOCK_SIZE - 1) / STAT_BLOCK_SIZE) as u64,
+            st_atime_sec: now.tv_sec as u64,
+            st_atime_nsec: now.tv_nsec as u64,
+            st_mtime_sec: now.tv_sec as u64,
+            st_mtime_nsec: now.tv_nsec as u64,
+            st_ctime_sec: now.tv_sec as u64,
+            st_ctime_nsec: now.tv_nsec as u64,
+            __unused: [0; 2]
+        }
+    }
+
+    // 字符设备 使用设备号作为inode
+    pub fn char_device(rdev: u64) -> Self {
+        Self {
+            st_rdev: rdev,
+            ..Self::new(DEV_DEVTMPFS, rdev, S_IFCHR | 0o666, 0)
+        }
+    }
+
+    // 设置访问 修改 状态改变时间
+    pub fn set_times(&mut self, atime: TimeSpec, mtime: TimeSpec, ctime: TimeSpec) {
+        self.st_atime_sec = atime.tv_sec as u64;
+        self.st_atime_nsec = atime.tv_nsec as u64;
+        self.st_mtime_sec = mtime.tv_sec as u64;
+        self.st_mtime_nsec = mtime.tv_nsec as u64;
+        self.st_ctime_sec = ctime.tv_sec as u64;
+        self.st_ctime_nsec = ctime.tv_nsec as u64;
+    }
+}
+
+// statx 已经填写的字段
+pub const STATX_BASIC_STATS: u32 = 0x7ff;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct StatxTimestamp {
+	pub tv_sec: i64,			// 秒
+	pub tv_nsec: u32,			// 纳秒
+	pub __reserved: i32			// 保留
+}
+
+#[repr(C)]
+pub struct Statx {
+	pub stx_mask: u32,			// 已经填写的字段
+	pub stx_blksize: u32,		// 占用块大小
+	pub stx_attributes: u64,	// 文件属性
+	pub stx_nlink: u32,			// 文件links
+	pub stx_uid: u32,			// 文件uid
+	pub stx_gid: u32,			// 文件gid
+	pub stx_mode: u16,			// 文件mode
+	pub __spare0: u16,			// 保留
+	pub stx_ino: u64,			// inode
+	pub stx_size: u64,			// 文件大小
+	pub stx_blocks: u64,		// 占用块数量
+	pub stx_attributes_mask: u64,	// 支持的文件属性
+	pub stx_atime: StatxTimestamp,	// 最后访问时间
+	pub stx_btime: StatxTimestamp,	// 创建时间
+	pub stx_ctime: StatxTimestamp,	// 最后状态改变时间
+	pub stx_mtime: StatxTimestamp,	// 最后修改时间
+	pub stx_rdev_major: u32,	// rdev主设备号
+	pub stx_rdev_minor: u32,	// rdev次设备号
+	pub stx_dev_major: u32,		// 设备主设备号
+	pub stx_dev_minor: u32,		// 设备次设备号
+	pub stx_mnt_id: u64,		// 挂载点
+	pub stx_dio_mem_align: u32,	// 直接读写内存对齐
+	pub stx_dio_offset_align: u32,	// 直接读写偏移对齐
+	pub __spare3: [u64; 12]		// 保留
 }
 
-pub trait FileOP: Any {
+impl Statx {
+    // 根据stat信息生成 没有创建时间
+    pub fn from_kstat(kstat: &Kstat) -> Self {
+        let timestamp = |sec: u64, nsec: u64| StatxTimestamp {
+            tv_sec: sec as i64,
+            tv_nsec: nsec as u32,
+            __reserved: 0
+        };
+        Self {
+            stx_mask: STATX_BASIC_STATS,
+            stx_blksize: kstat.st_blksize,
+            stx_attributes: 0,
+            stx_nlink: kstat.st_nlink,
+            stx_uid: kstat.st_uid,
+            stx_gid: kstat.st_gid,
+            stx_mode: kstat.st_mode as u16,
+            __spare0: 0,
+            stx_ino: kstat.st_ino,
+            stx_size: kstat.st_size,
+            stx_blocks: kstat.st_blocks,
+            stx_attributes_mask: 0,
+            stx_atime: timestamp(kstat.st_atime_sec, kstat.st_atime_nsec),
+            stx_btime: timestamp(0, 0),
+            stx_ctime: timestamp(kstat.st_ctime_sec, kstat.st_ctime_nsec),
+            stx_mtime: timestamp(kstat.st_mtime_sec, kstat.st_mtime_nsec),
+            stx_rdev_major: major(kstat.st_rdev),
+            stx_rdev_minor: minor(kstat.st_rdev),
+            stx_dev_major: major(kstat.st_dev),
+            stx_dev_minor: minor(kstat.st_dev),
+            stx_mnt_id: 0,
+            stx_dio_mem_align: 0,
+            stx_dio_offset_align: 0,
+            __spare3: [0; 12]
+        }
+    }
+}
+
+pub trait FileOP: Any + Send + Sync {
 	fn readable(&self) -> bool;
 	fn writeable(&self) -> bool;
 	fn read_at(&self, pos: usize, data: &mut [u8]) -> usize;
 	fn write_at(&self, pos: usize, data: &[u8], count: usize) -> usize;
 	fn get_size(&self) -> usize;
+	// 读取时是否不需要等待
+	fn read_ready(&self) -> bool { true }
+	// 写入时是否不需要等待
+	fn write_ready(&self) -> bool { true }
+	// 进入读取等待队列
+	fn wait_read(&self, _task: Arc<Task>) {}
+	// 进入写入等待队列
+	fn wait_write(&self, _task: Arc<Task>) {}
+	// 是否可以移动读写位置 管道 套接字和设备不能移动
+	fn seekable(&self) -> bool { false }
+	// 获取文件信息 默认为字符设备
+	fn stat(&self) -> Kstat {
+		Kstat::char_device(0)
+	}
 }
 
-pub struct File(pub RefCell<FileInner>);
+pub struct File(pub RwLock<FileInner>);
 
 pub struct FileInner {
-    pub file: Rc<INode>,
-    pub offset: usize,
+    pub file: Arc<INode>,
     pub file_size: usize,
     pub mem_size: usize,
     pub buf: &'static mut [u8],
-    pub mem_map: Option<Rc<MemMap>>,
+    pub mem_map: Option<Arc<MemMap>>,
     pub file_type: FileType
 }
 
+impl FileInner {
+    // 保证缓冲区可以容纳size字节 不足时重新申请并复制原来的数据
+    fn reserve(&mut self, size: usize) -> Result<(), RuntimeError> {
+        if size <= self.mem_size {
+            return Ok(());
+        }
+        // 每次至少扩大一倍 减少复制的次数
+        let old_pages = self.mem_size / PAGE_SIZE;
+        let pages = get_pages_num(size).max(old_pages * 2);
+        let phys_start = alloc_more(pages)?;
+        let mem_map = MemMap::exists_page(phys_start, phys_start.0.into(),
+            pages, PTEFlags::VRWX);
+        let buf = get_buf_from_phys_page(phys_start, pages);
+        buf[..self.file_size].copy_from_slice(&self.buf[..self.file_size]);
+        self.buf = buf;
+        self.mem_size = pages * PAGE_SIZE;
+        // 原来的内存在MemMap释放时回收
+        self.mem_map = Some(mem_map);
+        Ok(())
+    }
+}
+
 impl File {
-    pub fn new(inode: Rc<INode>) -> Result<Rc<Self>, RuntimeError>{
+    pub fn new(inode: Arc<INode>) -> Result<Arc<Self>, RuntimeError>{
         if inode.is_dir() {
-            Ok(Rc::new(Self(RefCell::new(FileInner {
+            Ok(Arc::new(Self(RwLock::new(FileInner {
                 file: inode,
-                offset: 0,
                 file_size: 0,
                 buf: get_buf_from_phys_page(PhysPageNum::from(0x80020usize), 0),
                 mem_size: 0,
@@ -101,9 +298,8 @@ impl File {
                 file_type: FileType::Directory
             }))))
         } else if inode.is_virt_file() {
-            Ok(Rc::new(Self(RefCell::new(FileInner {
+            Ok(Arc::new(Self(RwLock::new(FileInner {
                 file: inode,
-                offset: 0,
                 file_size: 0,
                 buf: get_buf_from_phys_page(PhysPageNum::from(0x80020usize), 0),
                 mem_size: 0,
@@ -121,9 +317,8 @@ impl File {
             let buf = get_buf_from_phys_page(elf_phy_start, elf_pages);
             inode.read_to(buf)?;
             let file_size = inode.get_file_size();
-            Ok(Rc::new(Self(RefCell::new(FileInner {
+            Ok(Arc::new(Self(RwLock::new(FileInner {
                 file: inode,
-                offset: 0,
                 file_size,
                 buf,
                 mem_size: elf_pages * PAGE_SIZE,
@@ -134,7 +329,7 @@ impl File {
         }
     }
 
-    pub fn cache(inode: Rc<INode>) -> Result<Rc<Self>, RuntimeError>{
+    pub fn cache(inode: Arc<INode>) -> Result<Arc<Self>, RuntimeError>{
         // 申请页表存储程序
         let elf_pages = get_pages_num(inode.get_file_size());
         // 申请页表
@@ -146,9 +341,8 @@ impl File {
         let buf = get_buf_from_phys_page(elf_phy_start, elf_pages);
         inode.read_to(buf)?;
         let file_size = inode.get_file_size();
-        Ok(Rc::new(Self(RefCell::new(FileInner {
+        Ok(Arc::new(Self(RwLock::new(FileInner {
             file: inode,
-            offset: 0,
             file_size,
             buf,
             mem_size: elf_pages * PAGE_SIZE,
@@ -158,40 +352,39 @@ impl File {
         
     }
 
-    pub fn get_inode(&self) -> Rc<INode> {
-        let inner = self.0.borrow_mut();
+    pub fn get_inode(&self) -> Arc<INode> {
+        let inner = self.0.write();
         inner.file.clone()
     }
 
     pub fn copy_to(&self, offset: usize, buf: &mut [u8]) {
-        let inner = self.0.borrow_mut();
+        let inner = self.0.write();
         let mut len = inner.buf.len() - offset;
         if len > buf.len() { len = buf.len(); }
         buf[..len].copy_from_slice(&inner.buf[offset..offset + len]);
     }
 
-    pub fn mmap(&self, pmm: Rc<PageMappingManager>, virt_addr: VirtAddr) -> Result<(), RuntimeError>{
-        let inner = self.0.borrow_mut();
+    pub fn mmap(&self, pmm: Arc<PageMappingManager>, virt_addr: VirtAddr) -> Result<(), RuntimeError>{
+        let inner = self.0.write();
         let mem_map = inner.mem_map.clone().ok_or(RuntimeError::NoMatchedFile)?;
         pmm.add_mapping_range(mem_map.ppn.into(), virt_addr, mem_map.page_num * PAGE_SIZE, PTEFlags::UVRWX)
     }
 
-    pub fn entry_next(&self) -> Option<(usize, Rc<INode>)> {
-        let mut inner = self.0.borrow_mut();
-        let offset = inner.offset;
-        let child = {
-            let children = &mut inner.file.0.borrow_mut().children;
-            if offset >= children.len() {
-                return None;
-            }
-            children[offset].clone()
-        };
-        inner.offset += 1;
-        Some((offset, child))
+    pub fn get_file_type(&self) -> FileType {
+        self.0.write().file_type
     }
 
-    pub fn get_file_type(&self) -> FileType {
-        self.0.borrow_mut().file_type
+    // 修改文件大小 扩大时填充0
+    pub fn truncate(&self, size: usize) -> Result<(), RuntimeError> {
+        let mut inner = self.0.write();
+        inner.reserve(size)?;
+        let file_size = inner.file_size;
+        if size > file_size {
+            inner.buf[file_size..size].fill(0);
+        }
+        inner.file_size = size;
+        inner.file.touch();
+        Ok(())
     }
 }
 
@@ -205,40 +398,57 @@ impl FileOP for File {
     }
 
     fn read_at(&self, pos: usize, data: &mut [u8]) -> usize {
-        let inner = self.0.borrow_mut();
+        let inner = self.0.write();
+        // 超出文件末尾时没有数据
+        if pos >= inner.file_size {
+            return 0;
+        }
         let remain = inner.file_size - pos;
         let len = if remain < data.len() { remain } else { data.len() };
         data[..len].copy_from_slice(&inner.buf[pos..pos + len]);
         len
     }
 
+    // 写入超出文件末尾时扩展文件 中间的空洞填充0
     fn write_at(&self, pos: usize, data: &[u8], count: usize) -> usize {
-        let mut inner = self.0.borrow_mut();
-        if inner.file_type == FileType::File {
-            let end = pos + count;
-            if end >= inner.mem_size {
-                panic!("无法写入超出部分");
-            }
-
-            let start = pos;
-            inner.buf[start..end].copy_from_slice(&data);
-
-            // inner.offset += count;
-            // // 需要更新文件数据
-            // if inner.offset >= inner.file_size {
-            //     inner.file_size = inner.offset;
-            //     let _file_size = inner.file_size;
-            //     let _inode = inner.file.0.borrow_mut();
-            // }
-            count
-        } else {
-            // 写入虚拟文件
-            count
+        let mut inner = self.0.write();
+        if inner.file_type == FileType::Directory {
+            return EISDIR;
+        }
+        let count = if count < data.len() { count } else { data.len() };
+        let end = pos + count;
+        if inner.reserve(end).is_err() {
+            return ENOSPC;
+        }
+        let file_size = inner.file_size;
+        if pos > file_size {
+            inner.buf[file_size..pos].fill(0);
         }
+        inner.buf[pos..end].copy_from_slice(&data[..count]);
+        if end > file_size {
+            inner.file_size = end;
+        }
+        inner.file.touch();
+        count
+    }
+
+    fn seekable(&self) -> bool {
+        self.0.read().file_type != FileType::Directory
     }
 
     fn get_size(&self) -> usize {
-        self.0.borrow_mut().file_size
+        self.0.read().file_size
+    }
+
+    fn stat(&self) -> Kstat {
+        let inner = self.0.read();
+        let mut kstat = inner.file.stat();
+        // 文件内容在内存中 使用内存中的文件大小
+        if inner.file_type != FileType::Directory {
+            kstat.st_size = inner.file_size as u64;
+            kstat.st_blocks = ((inner.file_size + STAT_BLOCK_SIZE - 1) / STAT_BLOCK_SIZE) as u64;
+        }
+        kstat
     }
 }
 
@@ -246,12 +456,12 @@ impl dyn FileOP {
     pub fn is<T: 'static>(&self) -> bool {
         TypeId::of::<T>() == self.type_id()
     }
-    pub fn downcast<T: 'static>(self: Rc<Self>) -> Result<Rc<T>,Rc<Self>> {
+    pub fn downcast<T: 'static>(self: Arc<Self>) -> Result<Arc<T>,Arc<Self>> {
         debug!("type_id: {:?}   self type_id: {:?}   file: {:?}", 
             TypeId::of::<T>(), self.type_id(), TypeId::of::<File>());
         if self.is::<T>() {
             unsafe {
-                Ok(Rc::from_raw(Rc::into_raw(self) as _))
+                Ok(Arc::from_raw(Arc::into_raw(self) as _))
             }
         } else {
             Err(self)
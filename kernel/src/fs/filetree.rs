@@ -1,15 +1,51 @@
 
-use core::cell::RefCell;
+use crate::sync::rwlock::RwLock;
 
-use alloc::{string::{String, ToString}, vec::Vec, rc::{Rc, Weak}};
-use fatfs::{Read, Write};
+use alloc::{string::{String, ToString}, vec::Vec, sync::{Arc, Weak}};
+use core::sync::atomic::{AtomicU64, Ordering};
+use fatfs::{Read, Write, DateTime};
 
-use crate::{device::{DiskFile, Dir}, runtime_err::RuntimeError};
+use crate::{device::{DiskFile, Dir, GLOBAL_FS}, runtime_err::RuntimeError, interrupt::timer::TimeSpec};
 
-use super::{file::{FileType, File}, cache::get_cache_file, virt_file::VirtFile};
+use super::{file::{FileType, File, FileOP, Kstat, DEV_ROOT, S_IFDIR, S_IFREG, S_IFCHR, S_IFIFO, S_IFLNK}, cache::get_cache_file, virt_file::VirtFile};
 
 
-pub static mut FILE_TREE: Option<Rc<INode>> = None;
+pub static mut FILE_TREE: Option<Arc<INode>> = None;
+
+// 根目录的inode 与Linux的vfat相同
+pub const ROOT_INO: u64 = 1;
+
+// 虚拟节点的inode从此开始分配 不会与硬盘目录项的位置重复
+const VIRT_INO_START: u64 = 1 << 32;
+
+// 下一个虚拟inode
+static NEXT_INO: AtomicU64 = AtomicU64::new(VIRT_INO_START);
+
+// 分配inode 用于虚拟文件 管道 套接字等
+pub fn alloc_ino() -> u64 {
+    NEXT_INO.fetch_add(1, Ordering::Relaxed)
+}
+
+// 将FAT中的本地时间转换为时间戳
+fn fat_time_to_timespec(date_time: DateTime) -> TimeSpec {
+    let (date, time) = (date_time.date, date_time.time);
+    // 计算从1970-01-01开始的天数
+    let (year, month) = if date.month <= 2 {
+        (date.year as i64 - 1, date.month as i64 + 9)
+    } else {
+        (date.year as i64, date.month as i64 - 3)
+    };
+    let era = year / 400;
+    let yoe = year - era * 400;
+    let doy = (153 * month + 2) / 5 + date.day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    let secs = days * 86400 + time.hour as i64 * 3600 + time.min as i64 * 60 + time.sec as i64;
+    TimeSpec {
+        tv_sec: secs as usize,
+        tv_nsec: time.millis as usize * 1_000_000
+    }
+}
 
 #[derive(Clone)]
 pub enum DiskFileEnum {
@@ -20,32 +56,118 @@ pub enum DiskFileEnum {
     None
 }
 
+// 硬盘文件和目录只在持有GLOBAL_FS时读写和释放
+unsafe impl Send for DiskFileEnum {}
+unsafe impl Sync for DiskFileEnum {}
+
 // 文件树原始树
 pub struct INodeInner {
     pub filename: String,               // 文件名
     pub file_type: FileType,            // 文件数类型
     pub parent: Option<Weak<INode>>,    // 父节点
-    pub children: Vec<Rc<INode>>,       // 子节点
-    pub file: DiskFileEnum              // 硬盘文件
+    pub children: Vec<Arc<INode>>,       // 子节点
+    pub file: DiskFileEnum,             // 硬盘文件
+    pub ino: u64,                       // inode 硬链接共享
+    pub mode: u32,                      // 权限位
+    pub uid: u32,                       // 所有者
+    pub gid: u32,                       // 所属组
+    pub atime: TimeSpec,                // 最后访问时间
+    pub mtime: TimeSpec,                // 最后修改时间
+    pub ctime: TimeSpec,                // 最后状态改变时间
+    pub seq: usize,                     // 在父目录中的序号 用于目录的读取位置
+    pub next_seq: usize,                // 下一个子节点的序号
+    pub cache: Option<Arc<File>>         // 文件内容 所有打开共享
+}
+
+impl Drop for INodeInner {
+    fn drop(&mut self) {
+        // 释放硬盘文件时会写回文件系统
+        if let DiskFileEnum::DiskFile(_) | DiskFileEnum::DiskDir(_) = self.file {
+            let _fs = GLOBAL_FS.lock();
+            self.file = DiskFileEnum::None;
+        }
+    }
 }
 
-pub struct INode(pub RefCell<INodeInner>);
+pub struct INode(pub RwLock<INodeInner>);
 
 impl INode {
     // 创建文件 创建文件时需要使用文件名
     pub fn new(filename: String, file: DiskFileEnum, 
-            file_type: FileType, parent: Option<Weak<INode>>) -> Rc<Self> {
-        Rc::new(Self(RefCell::new(INodeInner {
+            file_type: FileType, parent: Option<Weak<INode>>) -> Arc<Self> {
+        let now = TimeSpec::now();
+        let mode = if file_type == FileType::Directory { 0o755 } else { 0o644 };
+        Arc::new(Self(RwLock::new(INodeInner {
             filename, 
             file_type, 
             parent, 
             children: vec![],
-            file
+            file,
+            ino: alloc_ino(),
+            mode,
+            uid: 0,
+            gid: 0,
+            atime: now,
+            mtime: now,
+            ctime: now,
+            seq: 0,
+            next_seq: 0,
+            cache: None
         })))
     }
 
+    // 获取文件信息
+    pub fn stat(&self) -> Kstat {
+        let size = self.get_file_size();
+        let inner = self.0.read();
+        let mut kstat = match inner.file_type {
+            FileType::Directory => {
+                let mut kstat = Kstat::new(DEV_ROOT, inner.ino, S_IFDIR | inner.mode, 4096);
+                // 目录的链接数为 2 + 子目录数量
+                kstat.st_nlink = 2 + inner.children.iter().filter(|x| x.is_dir()).count() as u32;
+                kstat
+            }
+            FileType::Device => Kstat::new(DEV_ROOT, inner.ino, S_IFCHR | inner.mode, 0),
+            FileType::Pipeline => Kstat::new(DEV_ROOT, inner.ino, S_IFIFO | inner.mode, 0),
+            FileType::Link => Kstat::new(DEV_ROOT, inner.ino, S_IFLNK | 0o777, size),
+            _ => Kstat::new(DEV_ROOT, inner.ino, S_IFREG | inner.mode, size)
+        };
+        kstat.st_uid = inner.uid;
+        kstat.st_gid = inner.gid;
+        kstat.set_times(inner.atime, inner.mtime, inner.ctime);
+        kstat
+    }
+
+    // 获取inode
+    pub fn get_ino(&self) -> u64 {
+        self.0.read().ino
+    }
+
+    // 修改权限位
+    pub fn set_mode(&self, mode: u32) {
+        let mut inner = self.0.write();
+        inner.mode = mode & 0o7777;
+        inner.ctime = TimeSpec::now();
+    }
+
+    // 文件内容被修改 更新修改时间
+    pub fn touch(&self) {
+        let mut inner = self.0.write();
+        let now = TimeSpec::now();
+        inner.mtime = now;
+        inner.ctime = now;
+    }
+
+    // 修改所有者和所属组
+    pub fn set_owner(&self, uid: u32, gid: u32) {
+        let mut inner = self.0.write();
+        inner.uid = uid;
+        inner.gid = gid;
+        inner.ctime = TimeSpec::now();
+    }
+
     // 根目录节点
-    pub fn root() -> Rc<INode> {
+    pub fn root() -> Arc<INode> {
         unsafe {
             if let Some(data) = &FILE_TREE {
                 return data.clone();
@@ -54,20 +176,45 @@ impl INode {
         }
     }
 
-    // 添加节点到父节点
-    pub fn add(self: Rc<Self>, child: Rc<INode>) {
-        let mut inner = self.0.borrow_mut();
-        let mut cinner = child.0.borrow_mut();
-        cinner.parent = Some(Rc::downgrade(&self));
+    // 添加节点到父节点 子节点按照序号从小到大排列
+    pub fn add(self: Arc<Self>, child: Arc<INode>) {
+        let mut inner = self.0.write();
+        let mut cinner = child.0.write();
+        cinner.parent = Some(Arc::downgrade(&self));
+        cinner.seq = inner.next_seq;
+        inner.next_seq += 1;
         drop(cinner);
         inner.children.push(child);
     }
 
-    pub fn get_children(self: Rc<Self>, filename: &str) -> Result<Rc<INode>, RuntimeError> {
+    // 获取目录中位置不小于pos的第一个目录项 返回 (位置, 文件名, 节点)
+    // 位置0和1为 . 和 .. 子节点的位置为序号加2 删除其他子节点时位置不变
+    pub fn entry_at(self: &Arc<Self>, pos: usize) -> Option<(usize, String, Arc<INode>)> {
+        match pos {
+            0 => Some((0, String::from("."), self.clone())),
+            1 => {
+                let parent = self.0.read().parent.as_ref().and_then(|x| x.upgrade());
+                Some((1, String::from(".."), parent.unwrap_or(self.clone())))
+            }
+            _ => {
+                let inner = self.0.read();
+                inner.children.iter().find_map(|child| {
+                    let cinner = child.0.read();
+                    if cinner.seq + 2 >= pos {
+                        Some((cinner.seq + 2, cinner.filename.clone(), child.clone()))
+                    } else {
+                        None
+                    }
+                })
+            }
+        }
+    }
+
+    pub fn get_children(self: Arc<Self>, filename: &str) -> Result<Arc<INode>, RuntimeError> {
         match filename {
             "."     => Ok(self.clone()),
             ".."    => {
-                let inner = self.0.borrow_mut();
+                let inner = self.0.write();
                 match inner.parent.clone() {
                     Some(parent) => {
                         match parent.upgrade() {
@@ -91,7 +238,7 @@ impl INode {
         }
     }
 
-    pub fn find(self: Rc<Self>, path: &str) -> Result<Rc<INode>, RuntimeError> {
+    pub fn find(self: Arc<Self>, path: &str) -> Result<Arc<INode>, RuntimeError> {
         // traverse path
         let (name, rest_opt) = get_curr_dir(path);
         if let Some(rest) = rest_opt {
@@ -103,74 +250,101 @@ impl INode {
     }
 
     // 根据路径 获取文件节点
-    pub fn get(current: Option<Rc<INode>>, path: &str) -> Result<Rc<INode>, RuntimeError> {
+    pub fn get(current: Option<Arc<INode>>, path: &str) -> Result<Arc<INode>, RuntimeError> {
         if let Some(node) = current {
             node.find(path)
         } else {
             Self::root().find(path)
         }
     }
+    // 获取路径所在的目录 没有目录部分时为根目录
+    pub fn get_parent_dir(current: Option<Arc<INode>>, path: &str) -> Result<Arc<INode>, RuntimeError> {
+        let (dir_path, _) = split_path(path);
+        dir_path.map_or(Ok(INode::root()), |x| INode::get(current, x))
+    }
     // 根据路径 获取文件节点
-    pub fn open(current: Option<Rc<INode>>, path: &str) -> Result<Rc<File>, RuntimeError> {
-        let inode = Self::get(current, path)?;
+    pub fn open(current: Option<Arc<INode>>, path: &str) -> Result<Arc<File>, RuntimeError> {
+        Self::open_node(Self::get(current, path)?)
+    }
+    // 打开文件节点 优先使用缓存的文件
+    pub fn open_node(inode: Arc<INode>) -> Result<Arc<File>, RuntimeError> {
         if let Some(file) = get_cache_file(&inode.get_filename()) {
             return Ok(file.clone());
         }
-        File::new(inode)
+        // 同一个节点的所有打开共享文件内容
+        if let Some(file) = inode.0.read().cache.clone() {
+            return Ok(file);
+        }
+        let file = File::new(inode.clone())?;
+        if !inode.is_dir() {
+            inode.0.write().cache = Some(file.clone());
+        }
+        Ok(file)
+    }
+    // 在目录中创建不在文件树中的匿名文件 用于O_TMPFILE
+    pub fn create_anonymous(dir: &Arc<INode>) -> Result<Arc<File>, RuntimeError> {
+        let file = VirtFile::new(String::new());
+        let file_node = INode::new(String::new(), DiskFileEnum::VirtFile(file),
+            FileType::VirtFile, Some(Arc::downgrade(dir)));
+        File::new(file_node)
     }
     // 根据路径 获取文件节点
-    pub fn open_or_create(current: Option<Rc<INode>>, path: &str) -> Result<Rc<File>, RuntimeError> {
+    pub fn open_or_create(current: Option<Arc<INode>>, path: &str) -> Result<Arc<File>, RuntimeError> {
         if let Ok(inode) = Self::get(current.clone(), path) {
-            if let Some(file) = get_cache_file(&inode.get_filename()) {
-                return Ok(file.clone());
-            }
-            File::new(inode)
+            Self::open_node(inode)
         } else {
-            let (dir_path, filename) = split_path(path);
+            let (_, filename) = split_path(path);
             
-            debug!("split path: {:?}  filename: {}", dir_path, filename);
+            debug!("create path: {}  filename: {}", path, filename);
 
-            let dir_inode = 
-                dir_path.map_or(Ok(INode::root()), |x| INode::get(current, x))?;
+            let dir_inode = Self::get_parent_dir(current, path)?;
 
             let file = VirtFile::new(filename.to_string());
 
-            let parent_node = Some(Rc::downgrade(&dir_inode));
+            let parent_node = Some(Arc::downgrade(&dir_inode));
             let file_node = INode::new(filename.to_string(), 
             DiskFileEnum::VirtFile(file), FileType::VirtFile, parent_node);
             dir_inode.clone().add(file_node.clone());
-            File::new(file_node)
+            Self::open_node(file_node)
             // Err(RuntimeError::FileNotFound)
         }
     }
 
     // 获取当前路径
     pub fn get_pwd(&self) -> String {
-        let tree_node = self.clone();
-        let mut path = String::new();
-        loop {
-            path = path + "/" + &tree_node.get_filename();
-            if self.is_root() { break; }
+        if self.is_root() { return String::from("/"); }
+        // 从当前节点向上拼接路径
+        let mut path = String::from("/") + &self.get_filename();
+        let mut parent = self.get_parent();
+        while let Some(node) = parent {
+            if node.is_root() { break; }
+            path = String::from("/") + &node.get_filename() + &path;
+            parent = node.get_parent();
         }
         path
     }
 
+    // 获取父目录
+    pub fn get_parent(&self) -> Option<Arc<INode>> {
+        self.0.read().parent.clone().and_then(|x| x.upgrade())
+    }
+
     // 判断当前是否为根目录
     pub fn is_root(&self) -> bool {
         // 根目录文件名为空
-        self.0.borrow().parent.is_none()
+        self.0.read().parent.is_none()
     }
 
     // 判断是否为目录
     pub fn is_dir(&self) -> bool {
-        match self.0.borrow().file_type {
+        match self.0.read().file_type {
             FileType::Directory => true,
             _ => false
         }
     }
 
     pub fn is_virt_file(&self) -> bool {
-        match self.0.borrow().file_type {
+        match self.0.read().file_type {
             FileType::VirtFile => true,
             _ => false
         }
@@ -178,35 +352,43 @@ impl INode {
 
     // 获取文件名
     pub fn get_filename(&self) -> String{
-        self.0.borrow_mut().filename.clone()
+        self.0.write().filename.clone()
     }
 
     // 获取子元素
-    pub fn clone_children(&self) -> Vec<Rc<INode>> {
-        self.0.borrow().children.clone()
+    pub fn clone_children(&self) -> Vec<Arc<INode>> {
+        self.0.read().children.clone()
     }
 
     // 判断是否为空
     pub fn is_empty(&self) -> bool {
-        self.0.borrow_mut().children.is_empty()
+        self.0.write().children.is_empty()
     }
 
     // 删除子节点
     pub fn delete(&self, filename: &str) {
-        self.0.borrow_mut().children.retain(|c| c.get_filename() != filename);
+        self.0.write().children.retain(|c| c.get_filename() != filename);
     }
 
     // 获取文件大小
     pub fn get_file_size(&self) -> usize {
-        match &self.0.borrow_mut().file {
-            DiskFileEnum::DiskFile(f) => f.size().unwrap() as usize,
+        // 已经打开的文件使用内存中的大小
+        let cache = self.0.read().cache.clone();
+        if let Some(file) = cache {
+            return file.get_size();
+        }
+        match &self.0.read().file {
+            DiskFileEnum::DiskFile(f) => {
+                let _fs = GLOBAL_FS.lock();
+                f.size().unwrap() as usize
+            }
             _ => 0
         }
     }
 
     // 获取文件类型
     pub fn get_file_type(&self) -> FileType {
-        self.0.borrow_mut().file_type
+        self.0.write().file_type
     }
 
     // 读取文件内容
@@ -218,7 +400,7 @@ impl INode {
     }
 
     pub fn to_file(&self) -> Result<DiskFile, RuntimeError>{
-        if let DiskFileEnum::DiskFile(f) = &self.0.borrow().file {
+        if let DiskFileEnum::DiskFile(f) = &self.0.read().file {
             Ok(f.clone())
         } else {
             Err(RuntimeError::NotRWFile)
@@ -228,9 +410,10 @@ impl INode {
     // 读取文件内容
     pub fn read_to(&self, buf: &mut [u8]) -> Result<usize, RuntimeError>  {
         // 不再处理虚拟文件
-        // self.0.borrow_mut().file.read_exact(buf);
+        // self.0.write().file.read_exact(buf);
         // 读取错误 但是会抛出异常 UnexpectedEOF
         // self.to_file()?.read_exact(buf).expect("读取错误");
+        let _fs = GLOBAL_FS.lock();
         let mut file = self.to_file()?;
         file.read_exact(buf);
         Ok(buf.len())
@@ -238,24 +421,22 @@ impl INode {
 
     // 写入设备
     pub fn write(&self, buf: &mut [u8]) -> Result<usize, RuntimeError> {
-        // self.0.borrow_mut().file.write(buf).unwrap()
+        // self.0.write().file.write(buf).unwrap()
+        let _fs = GLOBAL_FS.lock();
         self.to_file()?.write(buf).map_err(|_| RuntimeError::NotRWFile)
     }
 
     // 创建文件夹
     // TODO: 创建文件夹
-    pub fn mkdir(current: Option<Rc<INode>>, path: &str, _flags: u16) -> Result<Rc<INode>, RuntimeError>{
+    pub fn mkdir(current: Option<Arc<INode>>, path: &str, _flags: u16) -> Result<Arc<INode>, RuntimeError>{
         match Self::get(current.clone(), path) {
             Ok(inode) => Ok(inode),
             Err(_) => {
                 // 创建文件夹
-                let (dir, filename) = split_path(path);
-                let pnode = match dir {
-                    Some(path) => INode::get(current, path)?,
-                    None => INode::root()
-                };
+                let (_, filename) = split_path(path);
+                let pnode = Self::get_parent_dir(current, path)?;
 
-                let parent_node = Some(Rc::downgrade(&pnode));
+                let parent_node = Some(Arc::downgrade(&pnode));
                 let file_node = INode::new(filename.to_string(), 
                 DiskFileEnum::VirtDir, FileType::Directory, parent_node);
                 pnode.clone().add(file_node.clone());
@@ -266,7 +447,9 @@ impl INode {
 
     // 删除自身
     pub fn del_self(&self) {
-        let inner = self.0.borrow_mut();
+        let mut inner = self.0.write();
+        // 已经打开的文件描述符仍然持有文件内容
+        inner.cache = None;
         let parent = inner.parent.clone();
         if let Some(parent) = parent {
             let parent = parent.upgrade().unwrap();
@@ -278,7 +461,7 @@ impl INode {
 
     // 删除自身
     pub fn is_valid(&self) -> bool {
-        let inner = self.0.borrow_mut();
+        let inner = self.0.write();
         let parent = inner.parent.clone();
         if let Some(parent) = parent {
             parent.upgrade().is_some()
@@ -294,9 +477,25 @@ impl INode {
             Some(path) => INode::get(None, path).expect("don't hava this folder"),
             None => INode::root()
         };
-        let inner = self.0.borrow_mut();
-        let new_node = Self::new(filename.to_string(), inner.file.clone(),
+        let inner = self.0.write();
+        let file = {
+            let _fs = GLOBAL_FS.lock();
+            inner.file.clone()
+        };
+        let new_node = Self::new(filename.to_string(), file,
             inner.file_type, inner.parent.clone());
+        // 硬链接与原文件共享inode和属性
+        {
+            let mut new_inner = new_node.0.write();
+            new_inner.ino = inner.ino;
+            new_inner.mode = inner.mode;
+            new_inner.uid = inner.uid;
+            new_inner.gid = inner.gid;
+            new_inner.atime = inner.atime;
+            new_inner.mtime = inner.mtime;
+            new_inner.ctime = inner.ctime;
+            new_inner.cache = inner.cache.clone();
+        }
 
         pnode.add(new_node);
 
@@ -336,28 +535,40 @@ fn split_path(path: &str) -> (Option<&str>, &str) {
 //     }
 // }
 
-pub fn add_files_to_dir(dir: Dir, node: Rc<INode>) {
+pub fn add_files_to_dir(dir: Dir, node: Arc<INode>) {
     for file_entry in dir.iter() {
         let file_entry = file_entry.expect("文件节点异常");
         let filename = file_entry.file_name();
         if filename == "." || filename == ".." {
             continue;
         }
-        if file_entry.is_dir() {
+        let child_node = if file_entry.is_dir() {
             let child_dir = file_entry.to_dir();
-            let parent_node = Some(Rc::downgrade(&node));
+            let parent_node = Some(Arc::downgrade(&node));
             let dir_node = INode::new(filename, 
                 DiskFileEnum::DiskDir(child_dir.clone()), FileType::Directory, parent_node);
             node.clone().add(dir_node.clone());
-            add_files_to_dir(child_dir, dir_node);
+            add_files_to_dir(child_dir, dir_node.clone());
+            dir_node
         } else if file_entry.is_file() {
-            let parent_node = Some(Rc::downgrade(&node));
+            let parent_node = Some(Arc::downgrade(&node));
             let dir_node = INode::new(filename, 
         DiskFileEnum::DiskFile(file_entry.to_file()), FileType::File, parent_node);
             node.clone().add(dir_node.clone());
+            dir_node
         } else {
             error!("不支持的文件类型");
-        }
+            continue;
+        };
+        // 使用短文件名目录项在分区中的序号作为inode 与起始簇不同 空文件也有唯一且不变的位置
+        // 加上ROOT_INO + 1 保证不为0也不与根目录重复
+        let mut inner = child_node.0.write();
+        inner.ino = file_entry.entry_pos / 32 + ROOT_INO + 1;
+        // FAT没有执行权限 与vfat的默认挂载选项相同 只读文件去掉写权限
+        inner.mode = if file_entry.attributes().contains(fatfs::FileAttributes::READ_ONLY) { 0o555 } else { 0o755 };
+        inner.ctime = fat_time_to_timespec(file_entry.created());
+        inner.mtime = fat_time_to_timespec(file_entry.modified());
+        inner.atime = fat_time_to_timespec(DateTime::new(file_entry.accessed(), fatfs::Time::new(0, 0, 0, 0)));
     }
 }
 
@@ -365,8 +576,10 @@ pub fn init(path: &str, root_dir: Dir) {
     if path == "/" {
         let inode = INode::new(String::from(""), DiskFileEnum::DiskDir(root_dir.clone()), 
             FileType::Directory, None);
+        inode.0.write().ino = ROOT_INO;
         // 添加到文件树子节点
         unsafe { FILE_TREE = Some(inode.clone()); }
+        let _fs = GLOBAL_FS.lock();
         add_files_to_dir(root_dir, inode);
     }
 }
\ No newline at end of file
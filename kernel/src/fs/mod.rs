@@ -8,7 +8,34 @@ pub mod virt_file;
 
 pub use partition::Partition;
 
-use crate::device::root_dir;
+use crate::device::{root_dir, GLOBAL_FS};
+use crate::memory::addr::PAGE_SIZE;
+use crate::memory::page::{PAGE_ALLOCATOR, get_free_page_num};
+
+use self::file::{DEV_ROOT, DEV_PROC, DEV_DEVTMPFS, DEV_PIPE, DEV_SOCK};
+
+// 文件系统类型
+pub const MSDOS_SUPER_MAGIC: u64 = 0x4d44;
+pub const TMPFS_MAGIC: u64 = 0x01021994;
+pub const PROC_SUPER_MAGIC: u64 = 0x9fa0;
+pub const PIPEFS_MAGIC: u64 = 0x50495045;
+pub const SOCKFS_MAGIC: u64 = 0x534f434b;
+
+// 使用长文件名时文件名的最大长度
+pub const NAME_MAX: u64 = 255;
+
+// 挂载点 不在文件树中的目录根据路径确定所在的文件系统
+const MOUNTS: [(&str, u64); 2] = [
+    ("/proc", DEV_PROC),
+    ("/dev", DEV_DEVTMPFS)
+];
+
+// 根据路径获取挂载的文件系统 不是挂载点下的路径时返回None
+pub fn mount_dev(path: &str) -> Option<u64> {
+    MOUNTS.iter().find(|(mount, _)| {
+        path == *mount || (path.starts_with(mount) && path[mount.len()..].starts_with('/'))
+    }).map(|(_, dev)| *dev)
+}
 
 #[repr(C)]
 pub struct StatFS{
@@ -21,6 +48,66 @@ pub struct StatFS{
     pub f_ffree: u64,       //可用文件结点数
     pub f_fsid: u64,        //文件系统标识
     pub f_namelen: u64,     //文件名的最大长度
+    pub f_frsize: u64,      //片段大小
+    pub f_flags: u64,       //挂载选项
+    pub f_spare: [u64; 4]   //保留
+}
+
+impl StatFS {
+    // 没有存储空间的文件系统
+    fn new(f_type: u64) -> Self {
+        Self {
+            f_type,
+            f_bsize: PAGE_SIZE as u64,
+            f_blocks: 0,
+            f_bfree: 0,
+            f_bavail: 0,
+            f_files: 0,
+            f_ffree: 0,
+            f_fsid: 0,
+            f_namelen: NAME_MAX,
+            f_frsize: PAGE_SIZE as u64,
+            f_flags: 0,
+            f_spare: [0; 4]
+        }
+    }
+
+    // 根据文件所在的设备获取文件系统信息
+    pub fn from_dev(dev: u64) -> Self {
+        match dev {
+            DEV_ROOT => {
+                let fs = GLOBAL_FS.lock();
+                let volume_id = fs.volume_id();
+                let mut statfs = Self::new(MSDOS_SUPER_MAGIC);
+                match fs.clone().stats() {
+                    Ok(stats) => {
+                        statfs.f_bsize = stats.cluster_size() as u64;
+                        statfs.f_frsize = stats.cluster_size() as u64;
+                        statfs.f_blocks = stats.total_clusters() as u64;
+                        statfs.f_bfree = stats.free_clusters() as u64;
+                        statfs.f_bavail = stats.free_clusters() as u64;
+                    }
+                    Err(_) => {
+                        warn!("读取文件系统信息失败");
+                    }
+                }
+                statfs.f_fsid = volume_id as u64;
+                statfs
+            }
+            // 内存文件系统使用物理页
+            DEV_DEVTMPFS => {
+                let mut statfs = Self::new(TMPFS_MAGIC);
+                let free_pages = get_free_page_num() as u64;
+                statfs.f_blocks = PAGE_ALLOCATOR.lock().pages.len() as u64;
+                statfs.f_bfree = free_pages;
+                statfs.f_bavail = free_pages;
+                statfs
+            }
+            DEV_PIPE => Self::new(PIPEFS_MAGIC),
+            DEV_SOCK => Self::new(SOCKFS_MAGIC),
+            _ => Self::new(PROC_SUPER_MAGIC)
+        }
+    }
 }
 
 // 初始化文件系统
@@ -1,12 +1,12 @@
-use core::cell::RefCell;
+use crate::sync::rwlock::RwLock;
 
-use crate::fs::file::FileOP;
+use crate::fs::file::{FileOP, Kstat, makedev};
 
-pub struct DevRtc(RefCell<bool>);
+pub struct DevRtc(RwLock<bool>);
 
 impl DevRtc {
     pub fn new() -> Self {
-        Self(RefCell::new(true))
+        Self(RwLock::new(true))
     }
 }
 
@@ -16,15 +16,15 @@ impl FileOP for DevRtc {
     }
 
     fn writeable(&self) -> bool {
-        todo!()
+        false
     }
 
     fn read_at(&self, _pos: usize, data: &mut [u8]) -> usize {
-        let readable = *self.0.borrow_mut();
+        let readable = *self.0.write();
         if readable {
             let bytes = b"MemTotal:       8024 kB";
             data[..bytes.len()].copy_from_slice(bytes);
-            *self.0.borrow_mut() = false;
+            *self.0.write() = false;
             bytes.len()
         } else {
             0
@@ -38,4 +38,8 @@ impl FileOP for DevRtc {
     fn get_size(&self) -> usize {
        0
     }
+
+    fn stat(&self) -> Kstat {
+        Kstat::char_device(makedev(10, 135))
+    }
 }
\ No newline at end of file
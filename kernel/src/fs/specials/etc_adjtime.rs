@@ -1,12 +1,14 @@
-use core::cell::RefCell;
+use crate::sync::rwlock::RwLock;
 
-use crate::fs::file::FileOP;
+use crate::fs::file::{FileOP, Kstat, S_IFREG, DEV_ROOT};
 
-pub struct EtcAdjtime(RefCell<bool>);
+use super::ETC_ADJTIME_INO;
+
+pub struct EtcAdjtime(RwLock<bool>);
 
 impl EtcAdjtime {
     pub fn new() -> Self {
-        Self(RefCell::new(true))
+        Self(RwLock::new(true))
     }
 }
 
@@ -16,15 +18,15 @@ impl FileOP for EtcAdjtime {
     }
 
     fn writeable(&self) -> bool {
-        todo!()
+        false
     }
 
     fn read_at(&self, _pos: usize, data: &mut [u8]) -> usize {
-        let readable = *self.0.borrow_mut();
+        let readable = *self.0.write();
         if readable {
             let bytes = b"0.000000 1643115317 0.000000\n1643115317\nUTC";
             data[..bytes.len()].copy_from_slice(bytes);
-            *self.0.borrow_mut() = false;
+            *self.0.write() = false;
             bytes.len()
         } else {
             0
@@ -38,4 +40,8 @@ impl FileOP for EtcAdjtime {
     fn get_size(&self) -> usize {
         0
     }
+
+    fn stat(&self) -> Kstat {
+        Kstat::new(DEV_ROOT, ETC_ADJTIME_INO, S_IFREG | 0o644, 0)
+    }
 }
\ No newline at end of file
@@ -1,4 +1,74 @@
+use alloc::sync::Arc;
+use alloc::string::ToString;
+
+use super::{file::{FileOP, FileType, S_IFMT, S_IFCHR, S_IFIFO}, filetree::{INode, DiskFileEnum}, stdio::{StdZero, StdNull}};
+
+use self::{proc_mounts::ProcMounts, proc_meminfo::ProcMeminfo, etc_adjtime::EtcAdjtime, dev_rtc::DevRtc, proc_timeslice::ProcTimeslice};
+
 pub mod proc_mounts;
 pub mod proc_meminfo;
 pub mod etc_adjtime;
-pub mod dev_rtc;
\ No newline at end of file
+pub mod dev_rtc;
+pub mod proc_timeslice;
+
+// 特殊文件的inode 每次打开都创建新的对象 需要固定的inode
+pub const PROC_MOUNTS_INO: u64 = 0x1000;
+pub const PROC_MEMINFO_INO: u64 = 0x1001;
+pub const ETC_ADJTIME_INO: u64 = 0x1002;
+pub const PROC_TIMESLICE_INO: u64 = 0x1003;
+
+// 特殊文件的路径 在文件树中创建对应的节点 读取目录时可以列出
+const SPECIAL_FILES: [&str; 7] = [
+    "/dev/zero",
+    "/dev/null",
+    "/dev/rtc",
+    "/proc/mounts",
+    "/proc/meminfo",
+    "/proc/sys/kernel/sched_rr_timeslice_ms",
+    "/etc/adjtime"
+];
+
+// 符号链接 由readlinkat读取
+const SPECIAL_LINKS: [&str; 1] = ["/proc/self/exe"];
+
+// 在文件树中创建特殊文件的节点 节点类型与特殊文件相同 打开时仍然使用open_special
+pub fn init() {
+    for path in SPECIAL_FILES {
+        let file_type = match open_special(path).unwrap().stat().st_mode & S_IFMT {
+            S_IFCHR => FileType::Device,
+            S_IFIFO => FileType::Pipeline,
+            _ => FileType::File
+        };
+        add_node(path, file_type);
+    }
+    for path in SPECIAL_LINKS {
+        add_node(path, FileType::Link);
+    }
+}
+
+// 创建节点 上级目录不存在时创建目录
+fn add_node(path: &str, file_type: FileType) {
+    let (dir, filename) = path.rsplit_once('/').unwrap();
+    for (i, _) in dir.match_indices('/').skip(1) {
+        INode::mkdir(None, &dir[..i], 0).expect("can't create special directory");
+    }
+    let parent = INode::mkdir(None, dir, 0).expect("can't create special directory");
+    if parent.clone().get_children(filename).is_err() {
+        parent.add(INode::new(filename.to_string(), DiskFileEnum::None, file_type, None));
+    }
+}
+
+// 根据路径获取特殊文件 不是特殊文件时返回None
+pub fn open_special(path: &str) -> Option<Arc<dyn FileOP>> {
+    let file: Arc<dyn FileOP> = match path {
+        "/dev/zero"     => Arc::new(StdZero),
+        "/dev/null"     => Arc::new(StdNull),
+        "/proc/mounts"  => Arc::new(ProcMounts::new()),
+        "/proc/meminfo" => Arc::new(ProcMeminfo::new()),
+        "/etc/adjtime"  => Arc::new(EtcAdjtime::new()),
+        "/dev/rtc"      => Arc::new(DevRtc::new()),
+        "/proc/sys/kernel/sched_rr_timeslice_ms" => Arc::new(ProcTimeslice::new()),
+        _ => return None
+    };
+    Some(file)
+}
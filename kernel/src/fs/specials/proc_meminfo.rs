@@ -1,12 +1,14 @@
-use core::cell::RefCell;
+use crate::sync::rwlock::RwLock;
 
-use crate::fs::file::FileOP;
+use crate::fs::file::{FileOP, Kstat, S_IFREG, DEV_PROC};
 
-pub struct ProcMeminfo(RefCell<bool>);
+use super::PROC_MEMINFO_INO;
+
+pub struct ProcMeminfo(RwLock<bool>);
 
 impl ProcMeminfo {
     pub fn new() -> Self {
-        Self(RefCell::new(true))
+        Self(RwLock::new(true))
     }
 }
 
@@ -16,15 +18,15 @@ impl FileOP for ProcMeminfo {
     }
 
     fn writeable(&self) -> bool {
-        todo!()
+        false
     }
 
     fn read_at(&self, _pos: usize, data: &mut [u8]) -> usize {
-        let readable = *self.0.borrow_mut();
+        let readable = *self.0.write();
         if readable {
             let bytes = b"MemTotal:       8024 kB";
             data[..bytes.len()].copy_from_slice(bytes);
-            *self.0.borrow_mut() = false;
+            *self.0.write() = false;
             bytes.len()
         } else {
             0
@@ -38,4 +40,8 @@ impl FileOP for ProcMeminfo {
     fn get_size(&self) -> usize {
         0
     }
+
+    fn stat(&self) -> Kstat {
+        Kstat::new(DEV_PROC, PROC_MEMINFO_INO, S_IFREG | 0o444, 0)
+    }
 }
\ No newline at end of file
@@ -1,12 +1,12 @@
-use core::cell::RefCell;
+use crate::fs::file::{FileOP, Kstat, S_IFREG, DEV_PROC};
 
-use crate::fs::file::FileOP;
+use super::PROC_MOUNTS_INO;
 
-pub struct ProcMounts(RefCell<bool>);
+pub struct ProcMounts;
 
 impl ProcMounts {
     pub fn new() -> Self {
-        Self(RefCell::new(true))
+        Self
     }
 }
 
@@ -16,19 +16,18 @@ impl FileOP for ProcMounts {
     }
 
     fn writeable(&self) -> bool {
-        todo!()
-    }
-
-    fn read_at(&self, _pos: usize, data: &mut [u8]) -> usize {
-        let readable = *self.0.borrow_mut();
-        if readable {
-            let bytes = b"fs / fs rw,nosuid,nodev,noexec,relatime 0 0";
-            data[..bytes.len()].copy_from_slice(bytes);
-            *self.0.borrow_mut() = false;
-            bytes.len()
-        } else {
-            0
+        false
+    }
+
+    // 从pos开始读取 每次最多读取缓冲区的大小
+    fn read_at(&self, pos: usize, data: &mut [u8]) -> usize {
+        let bytes = b"/dev/vda / vfat rw,relatime 0 0\nproc /proc proc rw,nosuid,nodev,noexec,relatime 0 0\ndevtmpfs /dev devtmpfs rw,relatime 0 0\n";
+        if pos >= bytes.len() {
+            return 0;
         }
+        let len = data.len().min(bytes.len() - pos);
+        data[..len].copy_from_slice(&bytes[pos..pos + len]);
+        len
     }
 
     fn write_at(&self, _pos: usize, _data: &[u8], _count: usize) -> usize {
@@ -38,4 +37,12 @@ impl FileOP for ProcMounts {
     fn get_size(&self) -> usize {
         0
     }
+
+    fn seekable(&self) -> bool {
+        true
+    }
+
+    fn stat(&self) -> Kstat {
+        Kstat::new(DEV_PROC, PROC_MOUNTS_INO, S_IFREG | 0o444, 0)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,62 @@
+use alloc::format;
+
+use crate::fs::file::{FileOP, Kstat, S_IFREG, DEV_PROC};
+use crate::interrupt::timer::{get_time_slice, set_time_slice};
+use crate::sys_call::consts::EINVAL;
+
+use super::PROC_TIMESLICE_INO;
+
+// 时间片长度 单位为毫秒 写入后对所有核心生效
+pub struct ProcTimeslice;
+
+impl ProcTimeslice {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl FileOP for ProcTimeslice {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writeable(&self) -> bool {
+        true
+    }
+
+    fn read_at(&self, pos: usize, data: &mut [u8]) -> usize {
+        let content = format!("{}\n", get_time_slice());
+        let bytes = content.as_bytes();
+        if pos >= bytes.len() {
+            return 0;
+        }
+        let len = data.len().min(bytes.len() - pos);
+        data[..len].copy_from_slice(&bytes[pos..pos + len]);
+        len
+    }
+
+    fn write_at(&self, _pos: usize, data: &[u8], count: usize) -> usize {
+        let count = count.min(data.len());
+        let value = core::str::from_utf8(&data[..count]).ok()
+            .and_then(|x| x.trim().parse::<usize>().ok());
+        match value {
+            Some(ms) if ms > 0 => {
+                set_time_slice(ms);
+                count
+            }
+            _ => EINVAL
+        }
+    }
+
+    fn get_size(&self) -> usize {
+        0
+    }
+
+    fn seekable(&self) -> bool {
+        true
+    }
+
+    fn stat(&self) -> Kstat {
+        Kstat::new(DEV_PROC, PROC_TIMESLICE_INO, S_IFREG | 0o644, 0)
+    }
+}
@@ -1,5 +1,10 @@
 use crate::console::puts;
-use super::file::FileOP;
+use super::file::{FileOP, Kstat, makedev};
+
+// 设备号
+const DEV_CONSOLE: u64 = makedev(5, 1);
+const DEV_NULL: u64 = makedev(1, 3);
+const DEV_ZERO: u64 = makedev(1, 5);
 
 pub struct StdIn;
 pub struct StdOut;
@@ -27,6 +32,10 @@ impl FileOP for StdIn {
     fn get_size(&self) -> usize {
         0
     }
+
+    fn stat(&self) -> Kstat {
+        Kstat::char_device(DEV_CONSOLE)
+    }
 }
 
 impl FileOP for StdOut {
@@ -50,11 +59,15 @@ impl FileOP for StdOut {
     fn get_size(&self) -> usize {
         0
     }
+
+    fn stat(&self) -> Kstat {
+        Kstat::char_device(DEV_CONSOLE)
+    }
 }
 
 impl FileOP for StdErr {
     fn readable(&self) -> bool {
-        todo!()
+        false
     }
 
     fn writeable(&self) -> bool {
@@ -75,6 +88,10 @@ impl FileOP for StdErr {
     fn get_size(&self) -> usize {
         0
     }
+
+    fn stat(&self) -> Kstat {
+        Kstat::char_device(DEV_CONSOLE)
+    }
 }
 
 impl FileOP for StdZero {
@@ -83,7 +100,7 @@ impl FileOP for StdZero {
     }
 
     fn writeable(&self) -> bool {
-        todo!()
+        true
     }
 
     fn read_at(&self, _pos: usize, data: &mut [u8]) -> usize {
@@ -91,13 +108,18 @@ impl FileOP for StdZero {
         data.len()
     }
 
-    fn write_at(&self, _pos: usize, _data: &[u8], _count: usize) -> usize {
-        todo!("not implemente write stzero")
+    // 写入的数据被丢弃
+    fn write_at(&self, _pos: usize, _data: &[u8], count: usize) -> usize {
+        count
     }
 
     fn get_size(&self) -> usize {
         0
     }
+
+    fn stat(&self) -> Kstat {
+        Kstat::char_device(DEV_ZERO)
+    }
 }
 
 impl FileOP for StdNull {
@@ -120,4 +142,8 @@ impl FileOP for StdNull {
     fn get_size(&self) -> usize {
         0
     }
+
+    fn stat(&self) -> Kstat {
+        Kstat::char_device(DEV_NULL)
+    }
 }
@@ -6,6 +6,9 @@ use riscv::register::scause::Trap;
 use riscv::register::scause::Exception;
 use riscv::register::scause::Interrupt;
 use riscv::register::scause::Scause;
+use riscv::register::sie;
+
+use crate::cpu::handle_cross_call;
 
 pub use timer::TICKS;
 
@@ -39,6 +42,44 @@ impl Context {
     }
 }
 
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+// 浮点寄存器上下文
+pub struct FpContext {
+    pub f: [usize; 32],     // 32 个浮点寄存器
+    pub fcsr: usize
+}
+
+impl FpContext {
+    // 创建浮点寄存器上下文
+    pub fn new() -> Self {
+        FpContext {
+            f: [0usize; 32],
+            fcsr: 0
+        }
+    }
+
+    // 保存当前核心的浮点寄存器
+    pub fn save(&mut self) {
+        extern "C" {
+            fn save_fp(ptr: usize);
+        }
+        unsafe {
+            save_fp(self as *mut Self as usize)
+        }
+    }
+
+    // 恢复到当前核心的浮点寄存器
+    pub fn restore(&self) {
+        extern "C" {
+            fn restore_fp(ptr: usize);
+        }
+        unsafe {
+            restore_fp(self as *const Self as usize)
+        }
+    }
+}
+
 // break中断
 fn breakpoint(context: &mut Context) {
     warn!("break中断产生 中断地址 {:#x}", context.sepc);
@@ -75,6 +116,8 @@ fn kernel_callback(context: &mut Context, scause: Scause, stval: usize) -> usize
         Trap::Exception(Exception::Breakpoint) => breakpoint(context),
         // 时钟中断 eg: 不再内核处理时间中断 just in user
         Trap::Interrupt(Interrupt::SupervisorTimer) => {},
+        // 核间中断
+        Trap::Interrupt(Interrupt::SupervisorSoft) => handle_cross_call(),
         // 缺页异常
         Trap::Exception(Exception::StorePageFault) => handle_page_fault(context, stval),
         // 加载页面错误
@@ -109,6 +152,11 @@ pub fn init() {
         asm!("csrw stvec, a0", in("a0") kernel_callback_entry as usize);
     }
 
+    // 开启软件中断 用于接收核间中断
+    unsafe {
+        sie::set_ssoft();
+    }
+
     // 初始化定时器
     timer::init();
 }
@@ -1,5 +1,9 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use crate::sync::mutex::Mutex;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use crate::sbi::set_timer;
+use crate::cpu::{MAX_HARTS, hart_id, is_boot_hart};
 use riscv::register::{sie, time};
 
 #[cfg(not(feature = "board_k210"))]
@@ -9,15 +13,19 @@ const CLOCK_FREQ: usize = 12500000;
 // const CLOCK_FREQ: usize = 4030000000 / 62;
 const CLOCK_FREQ: usize = 403000000 / 62;
 
+// 默认时间片 单位为时钟滴答
 const CHANGE_TASK_TICKS: usize = 10;
 
-// const INTERVAL: usize = CLOCK_FREQ / 100;
+// 时钟滴答间隔 10ms
+const INTERVAL: usize = CLOCK_FREQ / 100;
 // const INTERVAL: usize = CLOCK_FREQ / 25;
-const INTERVAL: usize = CLOCK_FREQ / 1;
+// const INTERVAL: usize = CLOCK_FREQ / 1;
 
 const MSEC_PER_SEC: usize = 1000;
+const USEC_PER_SEC: usize = 1_000_000;
 const NSEC_PER_SEC: usize = 1_000_000_000;
 
+// 内核中以微秒记录 写入用户空间时转换为时钟滴答
 // tms_utime记录的是进程执行用户代码的时间.
 // tms_stime记录的是进程执行内核代码的时间.
 // tms_cutime记录的是子进程执行用户代码的时间.
@@ -64,6 +72,75 @@ impl TimeSpec {
             tv_nsec: (tick % CLOCK_FREQ) * NSEC_PER_SEC / CLOCK_FREQ,
         }
     }
+
+    // 从微秒创建
+    pub fn from_us(us: usize) -> Self {
+        Self {
+            tv_sec: us / USEC_PER_SEC,
+            tv_nsec: us % USEC_PER_SEC * 1000
+        }
+    }
+
+    // 转换为微秒
+    pub fn as_us(&self) -> usize {
+        self.tv_sec * USEC_PER_SEC + self.tv_nsec / 1000
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct TimeVal {
+    pub tv_sec: usize,      /* 秒 */
+    pub tv_usec: usize      /* 微秒, 范围在0~999999 */
+}
+
+impl TimeVal {
+    // 从微秒创建
+    pub fn from_us(us: usize) -> Self {
+        Self {
+            tv_sec: us / USEC_PER_SEC,
+            tv_usec: us % USEC_PER_SEC
+        }
+    }
+
+    // 转换为微秒
+    pub fn as_us(&self) -> usize {
+        self.tv_sec * USEC_PER_SEC + self.tv_usec
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ITimerVal {
+    pub it_interval: TimeVal,   /* 定时器间隔 */
+    pub it_value: TimeVal       /* 距离下一次到期的时间 */
+}
+
+// 间隔定时器 时间单位为微秒
+pub struct IntervalTimer {
+    pub interval: usize,            // 到期后重新计时的间隔 为0时只触发一次
+    pub deadline: usize,            // 下一次到期的时间 为0时未启用
+    pub timer: Option<TimerId>      // 定时器队列中的定时器
+}
+
+impl IntervalTimer {
+    pub fn new() -> Self {
+        Self {
+            interval: 0,
+            deadline: 0,
+            timer: None
+        }
+    }
+
+    // 获取定时器的间隔和剩余时间
+    pub fn get_value(&self) -> ITimerVal {
+        let now = get_time_us();
+        let remain = if self.deadline > now { self.deadline - now } else { 0 };
+        ITimerVal {
+            it_interval: TimeVal::from_us(self.interval),
+            it_value: TimeVal::from_us(remain)
+        }
+    }
 }
 
 // 获取毫秒结构
@@ -76,53 +153,161 @@ pub fn get_time_ms() -> usize {
 }
 
 pub fn get_time_us() -> usize {
-    time::read() / (CLOCK_FREQ / MSEC_PER_SEC / MSEC_PER_SEC)
+    let tick = time::read();
+    tick / CLOCK_FREQ * USEC_PER_SEC + tick % CLOCK_FREQ * USEC_PER_SEC / CLOCK_FREQ
 }
 
 // 下一个任务ticks
-pub struct NextTaskTicks(usize);
+#[derive(Clone, Copy)]
+pub struct NextTaskTicks {
+    next: usize,        // 当前任务时间片结束的ticks
+    quantum: usize      // 时间片长度
+}
 
 impl NextTaskTicks {
     // 创建任务TICKS结构
     pub fn new() -> Self {
-        NextTaskTicks(CHANGE_TASK_TICKS)
+        NextTaskTicks {
+            next: CHANGE_TASK_TICKS,
+            quantum: CHANGE_TASK_TICKS
+        }
     }
 
-    // 刷新TICKS
+    // 刷新TICKS 从当前时间开始新的时间片
     pub fn refresh(&mut self) {
-        self.0 = self.0 + CHANGE_TASK_TICKS;
+        self.next = get_ticks() + self.quantum;
     }
 
     // 判断是否需要更换任务
     pub fn need_change(&self, ticks: usize) -> bool {
-        ticks > self.0
+        ticks >= self.next
+    }
+
+    // 设置时间片长度
+    pub fn set_quantum(&mut self, quantum: usize) {
+        self.quantum = if quantum == 0 { 1 } else { quantum };
+    }
+}
+
+// 定时器编号 用于取消定时器
+#[derive(Clone, Copy, PartialEq)]
+pub struct TimerId(usize, usize);
+
+// 定时器队列 按照到期时间排序
+pub struct TimerQueue {
+    timers: BTreeMap<(usize, usize), Box<dyn FnOnce() + Send>>,    // (到期时间, 编号) -> 回调
+    next_id: usize,                                         // 下一个定时器编号
+    tick_deadline: [usize; MAX_HARTS]                       // 每个核心下一次时钟滴答的时间
+}
+
+impl TimerQueue {
+    // 创建定时器队列
+    pub fn new() -> Self {
+        Self {
+            timers: BTreeMap::new(),
+            next_id: 0,
+            tick_deadline: [0; MAX_HARTS]
+        }
+    }
+
+    // 添加定时器 deadline为到期的时钟周期数
+    pub fn add(&mut self, deadline: usize, callback: Box<dyn FnOnce() + Send>) -> TimerId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.timers.insert((deadline, id), callback);
+        TimerId(deadline, id)
+    }
+
+    // 取消定时器 返回定时器是否还未到期
+    pub fn cancel(&mut self, timer: TimerId) -> bool {
+        self.timers.remove(&(timer.0, timer.1)).is_some()
+    }
+
+    // 取出一个已经到期的定时器
+    pub fn pop_expired(&mut self, now: usize) -> Option<Box<dyn FnOnce() + Send>> {
+        let key = match self.timers.keys().next() {
+            Some(&key) if key.0 <= now => key,
+            _ => return None
+        };
+        self.timers.remove(&key)
+    }
+
+    // 核心下一次需要触发中断的时间 取时钟滴答和最近的定时器中较早的一个
+    pub fn next_deadline(&self, hart_id: usize) -> usize {
+        match self.timers.keys().next() {
+            Some(&(deadline, _)) if deadline < self.tick_deadline[hart_id] => deadline,
+            _ => self.tick_deadline[hart_id]
+        }
     }
 }
 
 lazy_static! {
-    pub static ref NEXT_TICKS: Mutex<NextTaskTicks> = Mutex::new(NextTaskTicks::new());
+    // 每个核心当前任务的时间片
+    pub static ref NEXT_TICKS: Mutex<[NextTaskTicks; MAX_HARTS]> = Mutex::new([NextTaskTicks::new(); MAX_HARTS]);
+    pub static ref TIMER_QUEUE: Mutex<TimerQueue> = Mutex::new(TimerQueue::new());
 }
 
 // 时间信息
-pub static mut TICKS: usize = 0;
-pub static mut LAST_TICKS: usize = 0;
+pub static TICKS: AtomicUsize = AtomicUsize::new(0);
+pub static LAST_TICKS: AtomicUsize = AtomicUsize::new(0);
 
-/// 时钟中断处理器
-pub fn timer_handler() {
+/// 时钟中断处理器 返回当前任务的时间片是否用完
+pub fn timer_handler() -> bool {
+    // 定时器到期引起的中断不计入时钟滴答
+    if time::read() < TIMER_QUEUE.lock().tick_deadline[hart_id()] {
+        program_timer();
+        return false;
+    }
     set_next_timeout();
-    unsafe {
-        TICKS=TICKS+1;
+    // 每个核心都有时钟滴答 只由主核心计数
+    if is_boot_hart() {
+        TICKS.fetch_add(1, Ordering::Relaxed);
     }
     // 判断是否需要更换任务
-    if NEXT_TICKS.force_get().need_change(unsafe { TICKS }) {
-        // suspend_and_run_next();
-    }
+    NEXT_TICKS.lock()[hart_id()].need_change(get_ticks())
 }
 
 // 设置下一次时钟中断触发时间
 fn set_next_timeout() {
-    // 调用sbi设置定时器
-    set_timer(time::read() + INTERVAL);
+    TIMER_QUEUE.lock().tick_deadline[hart_id()] = time::read() + INTERVAL;
+    program_timer();
+}
+
+// 调用sbi设置定时器 在最近的到期时间触发中断
+fn program_timer() {
+    let deadline = TIMER_QUEUE.lock().next_deadline(hart_id());
+    set_timer(deadline);
+}
+
+// 添加定时器 在deadline(微秒)时执行回调
+pub fn add_timer(deadline: usize, callback: Box<dyn FnOnce() + Send>) -> TimerId {
+    let timer = TIMER_QUEUE.lock().add(us_to_ticks(deadline), callback);
+    program_timer();
+    timer
+}
+
+// 取消定时器
+pub fn cancel_timer(timer: TimerId) -> bool {
+    TIMER_QUEUE.lock().cancel(timer)
+}
+
+// 执行所有到期的定时器回调 回调中可以添加新的定时器
+pub fn check_timers() {
+    let now = time::read();
+    loop {
+        let callback = TIMER_QUEUE.lock().pop_expired(now);
+        match callback {
+            Some(callback) => callback(),
+            None => break
+        }
+    }
+    program_timer();
+}
+
+// 微秒转换为时钟周期数
+// 向上取整 保证到期时get_time_us()不小于us
+pub fn us_to_ticks(us: usize) -> usize {
+    us / USEC_PER_SEC * CLOCK_FREQ + (us % USEC_PER_SEC * CLOCK_FREQ + USEC_PER_SEC - 1) / USEC_PER_SEC
 }
 
 // 初始化定时器
@@ -138,17 +323,35 @@ pub fn init() {
     set_next_timeout();
 }
 
+// 任务切换时开始新的时间片
 pub fn task_time_refresh() {
-    NEXT_TICKS.force_get().refresh();
-    set_next_timeout();
+    NEXT_TICKS.lock()[hart_id()].refresh();
+}
+
+// 设置时间片长度 单位为毫秒 不足一个时钟滴答时按一个滴答计算
+// 通过/proc/sys/kernel/sched_rr_timeslice_ms设置
+pub fn set_time_slice(ms: usize) {
+    for next_ticks in NEXT_TICKS.lock().iter_mut() {
+        next_ticks.set_quantum(ms / (MSEC_PER_SEC / 100));
+    }
+}
+
+// 获取时间片长度 单位为毫秒
+pub fn get_time_slice() -> usize {
+    NEXT_TICKS.lock()[hart_id()].quantum * (MSEC_PER_SEC / 100)
+}
+
+// 微秒转换为时钟滴答
+pub fn us_to_clock_ticks(us: usize) -> usize {
+    us / (USEC_PER_SEC / 100)
 }
 
 #[inline]
 pub fn get_ticks() -> usize {
-    unsafe {TICKS}
+    TICKS.load(Ordering::Relaxed)
 }
 
 #[inline]
 pub fn set_last_ticks() {
-    unsafe {LAST_TICKS = TICKS};
+    LAST_TICKS.store(get_ticks(), Ordering::Relaxed);
 }
\ No newline at end of file
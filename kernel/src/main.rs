@@ -16,12 +16,14 @@ pub mod interrupt;
 mod memory;
 mod fs;
 mod sbi;
+mod cpu;
 mod panic;
 mod sync;
 pub mod task;
 pub mod runtime_err;
 pub mod elf;
 pub mod sys_call;
+mod random;
 
 #[macro_use]
 extern crate bitflags;
@@ -32,7 +34,7 @@ extern crate alloc;
 use core::arch::global_asm;
 
 
-use alloc::{rc::Rc, string::ToString};
+use alloc::{sync::Arc, string::ToString};
 use riscv::register::sstatus;
 use crate::fs::filetree::INode;
 use crate::fs::filetree::DiskFileEnum;
@@ -62,10 +64,9 @@ fn clear_bss() {
 
 #[no_mangle]
 pub extern "C" fn rust_main(hart_id: usize, device_tree_p_addr: usize) -> ! {
-    // // 保证仅有一个核心工作
-    #[cfg(not(debug_assertions))]
-    if hart_id != 0 {
-        sbi::hart_suspend(0x00000000, support_hart_resume as usize, 0);
+    // 第一个到达的核心作为主核心进行初始化 其他核心等待初始化完成
+    if !cpu::claim_boot_hart(hart_id) {
+        support_hart_resume(hart_id, 0);
     }
 
     unsafe {
@@ -99,6 +100,9 @@ pub extern "C" fn rust_main(hart_id: usize, device_tree_p_addr: usize) -> ! {
     // 初始化设备
     device::init();
 
+    // 初始化随机数
+    random::init();
+
     // 初始化文件系统
     fs::init();
 
@@ -125,6 +129,9 @@ pub extern "C" fn rust_main(hart_id: usize, device_tree_p_addr: usize) -> ! {
     INode::root().add(INode::new("proc".to_string(), 
         DiskFileEnum::None, FileType::Directory, None));
 
+    // 在文件树中创建特殊文件的节点
+    fs::specials::init();
+
     #[cfg(not(feature = "board_k210"))]
     {
         // 非k210缓冲文件
@@ -133,6 +140,9 @@ pub extern "C" fn rust_main(hart_id: usize, device_tree_p_addr: usize) -> ! {
         cache_file("lmbench_all");
     }
 
+    // 启动辅助核心
+    cpu::start_secondary_harts();
+
     // 初始化多任务
     task::init();
 
@@ -143,22 +153,48 @@ pub extern "C" fn rust_main(hart_id: usize, device_tree_p_addr: usize) -> ! {
     panic!("正常关机")
 }
 
-#[allow(unused)]
-/// 暂时不使用  目前只使用单核
-extern "C" fn support_hart_resume(hart_id: usize, _param: usize) {
-    info!("核心 {} 作为辅助核心进行等待", hart_id);
-    loop {} // 进入循环
+// 辅助核心入口 由 entry.asm 中的 _secondary_start 调用
+#[no_mangle]
+pub extern "C" fn rust_secondary_main(hart_id: usize) -> ! {
+    support_hart_resume(hart_id, 0)
+}
+
+// 辅助核心等待主核心初始化完成后 参与任务调度
+fn support_hart_resume(hart_id: usize, _param: usize) -> ! {
+    cpu::wait_boot_done();
+    info!("核心 {} 作为辅助核心开始运行", hart_id);
+
+    unsafe {
+        sstatus::set_fs(sstatus::FS::Dirty);
+    }
+
+    #[cfg(not(feature = "board_k210"))]
+    unsafe {
+        sstatus::set_sum();
+    }
+
+    // 使用内核页表
+    memory::page_table::switch_to_kernel_page();
+
+    // 初始化当前核心的中断和定时器
+    interrupt::init();
+
+    // 参与任务调度
+    task::init();
+
+    // 辅助核心不会结束调度
+    loop {}
 }
 
 
 // 打印目录树
-pub fn print_file_tree(node: Rc<INode>) {
+pub fn print_file_tree(node: Arc<INode>) {
     info!("{}", node.get_pwd());
     print_file_tree_back(node, 0);
 }
 
 // 打印目录树 - 递归
-pub fn print_file_tree_back(node: Rc<INode>, space: usize) {
+pub fn print_file_tree_back(node: Arc<INode>, space: usize) {
     let iter = node.clone_children();
     let mut iter = iter.iter().peekable();
     while let Some(sub_node) = iter.next() {
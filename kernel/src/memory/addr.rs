@@ -300,6 +300,10 @@ pub fn get_buf_from_phys_page<'a>(phys_page: PhysPageNum, pages: usize) -> &'a m
 #[derive(Clone, Copy)]
 pub struct UserAddr<T>(pub *mut T);
 
+// 用户地址只是一个数值 访问时使用当前核心的页表
+unsafe impl<T> Send for UserAddr<T> {}
+unsafe impl<T> Sync for UserAddr<T> {}
+
 impl<T> UserAddr<T> {
     pub fn is_valid(&self) -> bool {
         self.0 as usize != 0
@@ -1,4 +1,6 @@
-use alloc::rc::Rc;
+use core::mem::ManuallyDrop;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 
 use crate::memory::page::alloc;
 use crate::runtime_err::RuntimeError;
@@ -36,6 +38,33 @@ bitflags! {
     }
 }
 
+bitflags! {
+    // PROT Flags
+    pub struct ProtFlags: u32 {
+        const PROT_NONE  = 0x0;
+        const PROT_READ  = 0x1;
+        const PROT_WRITE = 0x2;
+        const PROT_EXEC  = 0x4;
+    }
+}
+
+impl ProtFlags {
+    // 转换为用户页表项的标志 只写的页面也需要可读
+    pub fn to_pte_flags(&self) -> PTEFlags {
+        let mut flags = PTEFlags::V | PTEFlags::U;
+        if self.intersects(ProtFlags::PROT_READ | ProtFlags::PROT_WRITE) {
+            flags |= PTEFlags::R;
+        }
+        if self.contains(ProtFlags::PROT_WRITE) {
+            flags |= PTEFlags::W;
+        }
+        if self.contains(ProtFlags::PROT_EXEC) {
+            flags |= PTEFlags::X;
+        }
+        flags
+    }
+}
+
 #[derive(Clone)]
 pub struct MemMap {
     pub ppn: PhysPageNum,
@@ -46,9 +75,9 @@ pub struct MemMap {
 
 impl MemMap {
     // 申请开始页表和页表数量 申请内存
-    pub fn new(vpn: VirtPageNum, page_num: usize, flags: PTEFlags) -> Result<Rc<Self>, RuntimeError> {
+    pub fn new(vpn: VirtPageNum, page_num: usize, flags: PTEFlags) -> Result<Arc<Self>, RuntimeError> {
         let phys_num_start = alloc_more(page_num)?;
-        Ok(Rc::new(Self {
+        Ok(Arc::new(Self {
             ppn: phys_num_start,
             vpn,
             page_num,
@@ -57,9 +86,9 @@ impl MemMap {
     }
 
     // 申请开始页表和页表数量 申请内存
-    pub fn new_kernel_buf(page_num: usize) -> Result<Rc<Self>, RuntimeError> {
+    pub fn new_kernel_buf(page_num: usize) -> Result<Arc<Self>, RuntimeError> {
         let phys_num_start = alloc_more(page_num)?;
-        Ok(Rc::new(Self {
+        Ok(Arc::new(Self {
             ppn: phys_num_start,
             vpn: 0usize.into(),
             page_num,
@@ -68,9 +97,9 @@ impl MemMap {
     }
 
     // 申请开始页表和页表数量 申请内存
-    pub fn new_virt_file_page() -> Result<Rc<Self>, RuntimeError> {
+    pub fn new_virt_file_page() -> Result<Arc<Self>, RuntimeError> {
         let phys_num_start = alloc()?;
-        Ok(Rc::new(Self {
+        Ok(Arc::new(Self {
             ppn: phys_num_start,
             vpn: 0usize.into(),
             page_num: 1,
@@ -79,8 +108,8 @@ impl MemMap {
     }
 
     // 获取pte容器地址
-    pub fn pte_container(ppn: PhysPageNum) -> Rc<Self> {
-        Rc::new(Self {
+    pub fn pte_container(ppn: PhysPageNum) -> Arc<Self> {
+        Arc::new(Self {
             ppn,
             vpn: VirtPageNum::default(),
             page_num: 1,
@@ -89,12 +118,12 @@ impl MemMap {
     }
 
     // 通过虚拟地址申请内存map
-    pub fn alloc_range(start_va: VirtAddr, end_va: VirtAddr, flags: PTEFlags) -> Result<Rc<Self>, RuntimeError> {
+    pub fn alloc_range(start_va: VirtAddr, end_va: VirtAddr, flags: PTEFlags) -> Result<Arc<Self>, RuntimeError> {
         let start_page: usize = start_va.0 / PAGE_SIZE * PAGE_SIZE;   // floor get start_page
         let end_page: usize = (end_va.0 + PAGE_SIZE - 1) / PAGE_SIZE;  
         let page_num = end_page - start_page;
         let phys_num_start = alloc_more(page_num)?;
-        Ok(Rc::new(Self {
+        Ok(Arc::new(Self {
             ppn: phys_num_start,
             vpn: VirtPageNum::from(start_va),
             page_num,
@@ -103,8 +132,8 @@ impl MemMap {
     }
 
     // 添加已经映射的页
-    pub fn exists_page(ppn: PhysPageNum, vpn: VirtPageNum, page_num: usize, flags: PTEFlags) -> Rc<Self> {
-        Rc::new(Self { 
+    pub fn exists_page(ppn: PhysPageNum, vpn: VirtPageNum, page_num: usize, flags: PTEFlags) -> Arc<Self> {
+        Arc::new(Self { 
             ppn, 
             vpn, 
             page_num, 
@@ -112,8 +141,8 @@ impl MemMap {
         })
     }
 
-    pub fn pte_page(ppn: PhysPageNum) -> Rc<Self> {
-        Rc::new(Self {
+    pub fn pte_page(ppn: PhysPageNum) -> Arc<Self> {
+        Arc::new(Self {
             ppn,
             vpn: 0usize.into(),
             page_num: 1,
@@ -121,7 +150,33 @@ impl MemMap {
         })
     }
 
-    pub fn clone_with_data(&self) -> Result<Rc<Self>, RuntimeError> {
+    // 删除[start, end)页范围内的页面并释放物理页 返回范围外剩余的部分
+    // 映射在其他地方仍被使用时不拆分 完全在范围内时只删除引用
+    pub fn unmap_range(this: Arc<Self>, start: usize, end: usize) -> Vec<Arc<Self>> {
+        let map_start = this.vpn.0;
+        let map_end = map_start + this.page_num;
+        if end <= map_start || start >= map_end {
+            return vec![this];
+        }
+        let map = match Arc::try_unwrap(this) {
+            Ok(map) => ManuallyDrop::new(map),
+            Err(this) => return if start <= map_start && end >= map_end { vec![] } else { vec![this] }
+        };
+        let cut_start = start.max(map_start);
+        let cut_end = end.min(map_end);
+        dealloc_more(map.ppn + (cut_start - map_start).into(), cut_end - cut_start);
+        let mut rest = vec![];
+        if cut_start > map_start {
+            rest.push(Self::exists_page(map.ppn, map.vpn, cut_start - map_start, map.flags));
+        }
+        if cut_end < map_end {
+            rest.push(Self::exists_page(map.ppn + (cut_end - map_start).into(), cut_end.into(),
+                map_end - cut_end, map.flags));
+        }
+        rest
+    }
+
+    pub fn clone_with_data(&self) -> Result<Arc<Self>, RuntimeError> {
         let page_num = self.page_num;
         let phys_num_start = alloc_more(page_num)?;
 
@@ -131,7 +186,7 @@ impl MemMap {
         new_data.copy_from_slice(old_data);
 
 
-        Ok(Rc::new(Self {
+        Ok(Arc::new(Self {
             ppn: phys_num_start,
             vpn: self.vpn,
             page_num,
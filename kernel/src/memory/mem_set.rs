@@ -1,18 +1,18 @@
-use alloc::{vec::Vec, rc::Rc};
+use alloc::{vec::Vec, sync::Arc};
 
 use crate::runtime_err::RuntimeError;
 
 use super::mem_map::MemMap;
 
 #[derive(Clone)]
-pub struct MemSet(pub Vec<Rc<MemMap>>);
+pub struct MemSet(pub Vec<Arc<MemMap>>);
 
 impl MemSet {
     pub fn new() -> Self {
         MemSet(vec![])
     }
 
-    pub fn inner(&mut self) -> &mut Vec<Rc<MemMap>> {
+    pub fn inner(&mut self) -> &mut Vec<Arc<MemMap>> {
         &mut self.0
     }
 
@@ -41,6 +41,17 @@ impl MemSet {
         (end + 1) << 12
     }
 
+    // 获取占用的页数
+    pub fn get_page_num(&self) -> usize {
+        self.0.iter().map(|x| x.page_num).sum()
+    }
+
+    // 删除与[start, end)页范围重叠的映射 部分重叠时保留范围外的部分
+    pub fn remove_range(&mut self, start: usize, end: usize) {
+        let maps = core::mem::take(&mut self.0);
+        self.0 = maps.into_iter().flat_map(|x| MemMap::unmap_range(x, start, end)).collect();
+    }
+
     // 释放占用的资源
     pub fn release(&mut self) {
         self.0.clear();
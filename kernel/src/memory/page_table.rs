@@ -1,11 +1,12 @@
 use core::arch::asm;
 use core::slice::from_raw_parts_mut;
 use core::slice;
-use _core::cell::RefCell;
+use crate::sync::rwlock::RwLock;
 use bitflags::*;
 
 use crate::memory::addr::PhysAddr;
 use crate::sync::mutex::Mutex;
+use crate::cpu::tlb_shootdown;
 use crate::runtime_err::RuntimeError;
 
 use super::addr::PhysPageNum;
@@ -95,10 +96,9 @@ pub enum PagingMode {
     Sv48 = 9
 }
 
-#[derive(Clone)]
 pub struct PageMappingManager {
     pub paging_mode: PagingMode,
-    pub mem_set: RefCell<MemSet>,
+    pub mem_set: RwLock<MemSet>,
     pub pte: PageMapping
 }
 
@@ -187,6 +187,56 @@ impl PageMapping {
         }
     }
 
+    // 修改已有用户映射的权限 保留物理页号和访问标志
+    // 新的权限不包含RWX时清除V位 保留物理页号以便之后恢复权限
+    pub fn protect_mapping(&self, virt_addr: VirtAddr, flags: PTEFlags) -> Result<(), RuntimeError> {
+        if usize::from(self.0) == 0 {
+            return Err(RuntimeError::NoMatchedAddr);
+        }
+
+        let l2_pte = PageTableEntry::get_vec_from_phys(PhysAddr::from(self.0))[virt_addr.l2()];
+        if !l2_pte.is_valid_pd() {
+            return Err(RuntimeError::NoMatchedAddr);
+        }
+
+        let l1_pte = PageTableEntry::get_vec_from_phys(l2_pte.ppn().into())[virt_addr.l1()];
+        if !l1_pte.is_valid_pd() {
+            return Err(RuntimeError::NoMatchedAddr);
+        }
+
+        let pte_vec = PageTableEntry::get_vec_from_phys(l1_pte.ppn().into());
+        // 只修改用户页面的映射 未映射的项指向内核地址且不含U位
+        let l0_pte = pte_vec[virt_addr.l0()];
+        if !l0_pte.flags().contains(PTEFlags::U) {
+            return Err(RuntimeError::NoMatchedAddr);
+        }
+        let mut new_flags = (l0_pte.flags() & (PTEFlags::A | PTEFlags::D | PTEFlags::G)) | flags;
+        if !new_flags.intersects(PTEFlags::R | PTEFlags::W | PTEFlags::X) {
+            new_flags.remove(PTEFlags::V);
+        }
+        pte_vec[virt_addr.l0()] = PageTableEntry::new(l0_pte.ppn(), new_flags);
+        Ok(())
+    }
+
+    // 判断是否存在用户页面的映射 未映射的项指向内核地址且不含U位
+    pub fn is_user_mapped(&self, virt_addr: VirtAddr) -> bool {
+        if usize::from(self.0) == 0 {
+            return false;
+        }
+
+        let l2_pte = PageTableEntry::get_vec_from_phys(PhysAddr::from(self.0))[virt_addr.l2()];
+        if !l2_pte.is_valid_pd() {
+            return false;
+        }
+
+        let l1_pte = PageTableEntry::get_vec_from_phys(l2_pte.ppn().into())[virt_addr.l1()];
+        if !l1_pte.is_valid_pd() {
+            return false;
+        }
+
+        PageTableEntry::get_vec_from_phys(l1_pte.ppn().into())[virt_addr.l0()].flags().contains(PTEFlags::U)
+    }
+
     // 获取物理地址
     pub fn get_phys_addr(&self, virt_addr: VirtAddr) -> Result<PhysAddr, RuntimeError> {
         // 如果没有pte则申请pte
@@ -323,7 +373,7 @@ impl PageMappingManager {
         Ok(PageMappingManager { 
             paging_mode: PagingMode::Sv39, 
             pte: PhysAddr::from(ppn).into(),
-            mem_set: RefCell::new(mem_set)
+            mem_set: RwLock::new(mem_set)
         })
     }
 
@@ -366,8 +416,32 @@ impl PageMappingManager {
         Ok(())
     }
 
+    // 删除mapping 并刷新所有核心的TLB
     pub fn remove_mapping(&self, virt_addr: VirtAddr) {
-        self.pte.remove_mapping(virt_addr)
+        self.pte.remove_mapping(virt_addr);
+        tlb_shootdown(virt_addr.0, PAGE_SIZE);
+    }
+
+    // 删除一个范围内的mapping
+    pub fn remove_mapping_range(&self, virt_addr: VirtAddr, size: usize) {
+        let start = virt_addr.0 / PAGE_SIZE * PAGE_SIZE;
+        for addr in (start..virt_addr.0 + size).step_by(PAGE_SIZE) {
+            self.pte.remove_mapping(addr.into());
+        }
+        tlb_shootdown(start, virt_addr.0 + size - start);
+    }
+
+    // 修改一个范围内mapping的权限 存在没有映射的页时不做修改
+    pub fn protect_mapping_range(&self, virt_addr: VirtAddr, size: usize, flags: PTEFlags) -> Result<(), RuntimeError> {
+        let start = virt_addr.0 / PAGE_SIZE * PAGE_SIZE;
+        if !(start..virt_addr.0 + size).step_by(PAGE_SIZE).all(|addr| self.pte.is_user_mapped(addr.into())) {
+            return Err(RuntimeError::NoMatchedAddr);
+        }
+        for addr in (start..virt_addr.0 + size).step_by(PAGE_SIZE) {
+            self.pte.protect_mapping(addr.into(), flags)?;
+        }
+        tlb_shootdown(start, virt_addr.0 + size - start);
+        Ok(())
     }
 
     // 获取物理地址
@@ -392,13 +466,13 @@ impl PageMappingManager {
 
     // 添加内存set
     pub fn add_mem_set(&self, target_mem_set: &mut MemSet) {
-        let mut mem_set = self.mem_set.borrow_mut();
+        let mut mem_set = self.mem_set.write();
         mem_set.append(target_mem_set);
     }
 
     // 释放内存资源
     pub fn release(&self) {
-        let mut mem_set = self.mem_set.borrow_mut();
+        let mut mem_set = self.mem_set.write();
         mem_set.release();
     }
 }
@@ -410,7 +484,7 @@ lazy_static! {
 // 初始化页面映射
 pub fn init() {
     {
-        let kernel_page = KERNEL_PAGE_MAPPING.force_get();
+        let kernel_page = KERNEL_PAGE_MAPPING.lock();
         
         let mem_map = MemMap::exists_page(0x80000usize.into(), 0x80000usize.into(), 
                 (ADDR_END - 0x8000_0000) / PAGE_SIZE, PTEFlags::VRWX | PTEFlags::G | PTEFlags::D | PTEFlags::A);
@@ -420,6 +494,6 @@ pub fn init() {
 }
 
 pub fn switch_to_kernel_page() {
-    let mapping_manager = KERNEL_PAGE_MAPPING.force_get();
+    let mapping_manager = KERNEL_PAGE_MAPPING.lock();
     mapping_manager.change_satp();
 }
\ No newline at end of file
@@ -0,0 +1,59 @@
+use riscv::register::{time, cycle};
+use crate::sync::mutex::Mutex;
+
+// qemu virt平台的goldfish RTC 读取低32位时锁存高32位 单位为纳秒
+#[cfg(not(feature = "board_k210"))]
+const GOLDFISH_RTC: usize = 0x101000;
+
+lazy_static! {
+    // 随机数状态 启动时由平台的熵源初始化 每次获取时混入当前的计数器
+    static ref RANDOM_STATE: Mutex<u64> = Mutex::new(0x9e37_79b9_7f4a_7c15);
+}
+
+// splitmix64 将状态扩散到64位
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// 读取平台的熵源 qemu使用RTC的当前时间 每次启动都不同
+#[cfg(not(feature = "board_k210"))]
+fn platform_entropy() -> u64 {
+    unsafe {
+        let low = (GOLDFISH_RTC as *const u32).read_volatile() as u64;
+        let high = ((GOLDFISH_RTC + 4) as *const u32).read_volatile() as u64;
+        (high << 32) | low
+    }
+}
+
+// k210没有RTC和随机数发生器 使用启动过程中SD卡等设备造成的周期数抖动
+#[cfg(feature = "board_k210")]
+fn platform_entropy() -> u64 {
+    cycle::read() as u64
+}
+
+// 使用平台的熵源初始化随机数状态 需要在设备初始化之后调用
+pub fn init() {
+    let mut state = RANDOM_STATE.lock();
+    *state ^= platform_entropy();
+    *state ^= (cycle::read() as u64).rotate_left(32);
+    splitmix64(&mut state);
+}
+
+// 获取随机数 混入时钟和周期计数的低位 不同的调用之间变化
+pub fn get_random() -> u64 {
+    let mut state = RANDOM_STATE.lock();
+    *state ^= (time::read() as u64).rotate_left(17) ^ (cycle::read() as u64).rotate_left(41);
+    splitmix64(&mut state)
+}
+
+// 使用随机数填充缓冲区
+pub fn fill_random(buf: &mut [u8]) {
+    for chunk in buf.chunks_mut(8) {
+        let value = get_random().to_le_bytes();
+        chunk.copy_from_slice(&value[..chunk.len()]);
+    }
+}
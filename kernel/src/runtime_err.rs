@@ -15,8 +15,10 @@ pub enum RuntimeError {
     KillCurrentProc,
     // 触发EBADF
     EBADF,
-    // 信号返回程序
-    SigReturn,
+    // 文件描述符超出RLIMIT_NOFILE
+    EMFILE,
+    // 不是可以执行的elf
+    ENOEXEC,
     //
     WriteZero,
     UnexpectedEof,
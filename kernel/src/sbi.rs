@@ -91,4 +91,66 @@ pub fn hart_suspend(suspend_type: u32, resume_addr: usize, opaque: usize) -> Sbi
         resume_addr,
         opaque,
     )
+}
+
+// HSM 核心状态
+pub const HART_STATE_STARTED: usize = 0;
+pub const HART_STATE_STOPPED: usize = 1;
+pub const HART_STATE_START_PENDING: usize = 2;
+pub const HART_STATE_STOP_PENDING: usize = 3;
+pub const HART_STATE_SUSPENDED: usize = 4;
+
+/// 启动核心 核心从start_addr开始执行 a0为hartid a1为opaque
+pub fn hart_start(hart_id: usize, start_addr: usize, opaque: usize) -> SbiRet {
+    sbi_call_3(EXTENSION_HSM, FUNCTION_HSM_HART_START, hart_id, start_addr, opaque)
+}
+
+/// 停止当前核心
+pub fn hart_stop() -> SbiRet {
+    sbi_call_3(EXTENSION_HSM, FUNCTION_HSM_HART_STOP, 0, 0, 0)
+}
+
+/// 获取核心状态
+pub fn hart_get_status(hart_id: usize) -> SbiRet {
+    sbi_call_3(EXTENSION_HSM, FUNCTION_HSM_HART_GET_STATUS, hart_id, 0, 0)
+}
+
+const FUNCTION_IPI_SEND_IPI: usize = 0x0;
+
+const FUNCTION_RFENCE_REMOTE_FENCE_I: usize = 0x0;
+const FUNCTION_RFENCE_REMOTE_SFENCE_VMA: usize = 0x1;
+const FUNCTION_RFENCE_REMOTE_SFENCE_VMA_ASID: usize = 0x2;
+
+#[inline(always)]
+fn sbi_call_5(extension: usize, function: usize, arg0: usize, arg1: usize, arg2: usize, arg3: usize, arg4: usize) -> SbiRet {
+    let (error, value);
+    unsafe {
+        asm!(
+            "ecall",
+            in("a0") arg0, in("a1") arg1, in("a2") arg2, in("a3") arg3, in("a4") arg4,
+            in("a6") function, in("a7") extension,
+            lateout("a0") error, lateout("a1") value,
+        )
+    }
+    SbiRet { error, value }
+}
+
+/// 向hart_mask中的核心发送核间中断 hart_mask_base为掩码第0位对应的核心id
+pub fn send_ipi(hart_mask: usize, hart_mask_base: usize) -> SbiRet {
+    sbi_call_3(EXTENSION_IPI, FUNCTION_IPI_SEND_IPI, hart_mask, hart_mask_base, 0)
+}
+
+/// 让指定核心执行 fence.i
+pub fn remote_fence_i(hart_mask: usize, hart_mask_base: usize) -> SbiRet {
+    sbi_call_3(EXTENSION_RFENCE, FUNCTION_RFENCE_REMOTE_FENCE_I, hart_mask, hart_mask_base, 0)
+}
+
+/// 让指定核心刷新 [start_addr, start_addr + size) 范围内的TLB
+pub fn remote_sfence_vma(hart_mask: usize, hart_mask_base: usize, start_addr: usize, size: usize) -> SbiRet {
+    sbi_call_5(EXTENSION_RFENCE, FUNCTION_RFENCE_REMOTE_SFENCE_VMA, hart_mask, hart_mask_base, start_addr, size, 0)
+}
+
+/// 让指定核心刷新asid对应地址空间中 [start_addr, start_addr + size) 范围内的TLB
+pub fn remote_sfence_vma_asid(hart_mask: usize, hart_mask_base: usize, start_addr: usize, size: usize, asid: usize) -> SbiRet {
+    sbi_call_5(EXTENSION_RFENCE, FUNCTION_RFENCE_REMOTE_SFENCE_VMA_ASID, hart_mask, hart_mask_base, start_addr, size, asid)
 }
\ No newline at end of file
@@ -1 +1,2 @@
-pub mod mutex;
\ No newline at end of file
+pub mod mutex;
+pub mod rwlock;
@@ -6,6 +6,8 @@ use core::option::Option::{self, None, Some};
 use core::default::Default;
 use core::hint::spin_loop;
 
+use crate::cpu::handle_cross_call;
+
 
 // 在编译时已经确定大小
 // 所有参数都必须实现了Sized绑定
@@ -38,19 +40,21 @@ impl<T> Mutex<T> {
 }
 
 
-unsafe impl<T: ?Sized> Sync for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
 
-unsafe impl<T: ?Sized> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
 
 impl<T: ?Sized> Mutex<T> {
     fn obtain_lock(&self) {
         // 尝试获得锁
         loop {
-            if let Ok(_) = self.lock.compare_exchange(false, true, Ordering::Relaxed, Ordering::Relaxed) {
+            if let Ok(_) = self.lock.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed) {
                 break;
             }
             // 循环判断是否已经解锁如果没有解锁
+            // 持有锁的核心可能在等待当前核心执行核间调用
             while self.lock.load(Ordering::Relaxed) {
+                handle_cross_call();
                 // 向处理器发出信号，表明现在处于自旋状态
                 spin_loop();
             }
@@ -73,20 +77,11 @@ impl<T: ?Sized> Mutex<T> {
         self.lock.store(false, Ordering::Release)
     }
 
-    #[allow(unused)]
-    // 强制获取数据
-    pub fn force_get(&self)->MutexGuard<T> {
-        MutexGuard {
-            lock: &self.lock,
-            data: unsafe { &mut *self.data.get() },
-        }
-    }
-
 
     #[allow(unused)]
     // 尝试锁定
     pub fn try_lock(&self) -> Option<MutexGuard<T>> {
-        if let Ok(res) = self.lock.compare_exchange(false, true, Ordering::Relaxed, Ordering::Relaxed) {
+        if let Ok(_) = self.lock.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed) {
             return Some(MutexGuard {
                 lock: &self.lock,
                 data: unsafe { &mut *self.data.get() },
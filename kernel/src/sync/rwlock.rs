@@ -0,0 +1,132 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::hint::spin_loop;
+
+use crate::cpu::handle_cross_call;
+
+// 写者标志 读者数量从第1位开始计数
+const WRITER: usize = 1;
+const READER: usize = 2;
+
+// 读写自旋锁 多个读者或一个写者
+// 同一核心上重复获取写锁会死锁 与RefCell重复借用时panic相对应
+pub struct RwLock<T: ?Sized> {
+    state: AtomicUsize,
+    data: UnsafeCell<T>,
+}
+
+pub struct RwLockReadGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+}
+
+pub struct RwLockWriteGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            state: AtomicUsize::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    // 取出数据
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+// 等待锁释放 持有锁的核心可能在等待当前核心执行核间调用
+#[inline]
+fn wait() {
+    handle_cross_call();
+    spin_loop();
+}
+
+impl<T: ?Sized> RwLock<T> {
+    // 获取读锁
+    pub fn read(&self) -> RwLockReadGuard<T> {
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+            if state & WRITER == 0 && self.state.compare_exchange_weak(state, state + READER,
+                    Ordering::Acquire, Ordering::Relaxed).is_ok() {
+                return RwLockReadGuard { lock: self };
+            }
+            wait();
+        }
+    }
+
+    // 获取写锁
+    pub fn write(&self) -> RwLockWriteGuard<T> {
+        loop {
+            if let Some(guard) = self.try_write() {
+                return guard;
+            }
+            while self.state.load(Ordering::Relaxed) != 0 {
+                wait();
+            }
+        }
+    }
+
+    // 尝试获取写锁 已经被持有时返回None
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<T>> {
+        self.state.compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed).ok()
+            .map(|_| RwLockWriteGuard { lock: self })
+    }
+
+    // 获取数据的指针 用于比较是否为同一个对象
+    pub fn data_ptr(&self) -> *mut T {
+        self.data.get()
+    }
+
+    // 独占时直接获取数据
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for RwLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.state.fetch_sub(READER, Ordering::Release);
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.state.store(0, Ordering::Release);
+    }
+}
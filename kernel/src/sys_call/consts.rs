@@ -29,6 +29,9 @@ pub const ENOSPC: usize = -28 as isize as usize; /* No space left on device */
 pub const ESPIPE: usize = -29 as isize as usize; /* Illegal seek */
 pub const EROFS: usize = -0 as isize as usize; /* Read-only file system */
 pub const EMLINK: usize = -31 as isize as usize; /* Too many links */
-pub const EPIPE: usize = -2 as isize as usize; /* Broken pipe */
+pub const EPIPE: usize = -32 as isize as usize; /* Broken pipe */
 pub const EDOM: usize = -3 as isize as usize; /* Math argument out of domain of func */
 pub const ERANGE: usize = -34 as isize as usize; /* Math result not representable */
+pub const EDEADLK: usize = -35 as isize as usize; /* Resource deadlock would occur */
+pub const ENOSYS: usize = -38 as isize as usize; /* Invalid system call number */
+pub const ETIMEDOUT: usize = -110 as isize as usize; /* Connection timed out */
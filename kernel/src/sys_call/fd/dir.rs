@@ -1,17 +1,17 @@
-use crate::{task::{task::Task, fd_table::FD_NULL}, memory::addr::UserAddr, runtime_err::RuntimeError, fs::filetree::INode};
+use crate::{task::{task::Task, fd_table::FD_NULL, cred::{W_OK, X_OK}}, memory::addr::UserAddr, runtime_err::RuntimeError, fs::filetree::INode, sys_call::consts::EACCES};
 
 impl Task {
     
     // 获取当前路径
     pub fn get_cwd(&self, buf: UserAddr<u8>, size: usize) -> Result<(), RuntimeError> {
         debug!("get_cwd size: {}", size);
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
 
         // 获取参数
         let buf = buf.transfer_vec(size);
         // 获取路径
-        let pwd = process.workspace.clone();
+        let pwd = process.workspace.read().clone();
         let pwd_buf = pwd.get_pwd();
         // let pwd_buf = process.workspace.as_bytes();
         // 将路径复制到缓冲区
@@ -24,11 +24,12 @@ impl Task {
     // 更改工作目录
     pub fn sys_chdir(&self, filename: UserAddr<u8>) -> Result<(), RuntimeError> {
         let filename = filename.read_string();
-        let mut inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
 
         // process.workspace = process.workspace.clone() + "/" + &filename;
-        process.workspace = INode::get(Some(process.workspace.clone()), &filename)?;
+        let workspace = INode::get(Some(process.workspace.read().clone()), &filename)?;
+        *process.workspace.write() = workspace;
 
         drop(process);
         inner.context.x[10] = 0;
@@ -38,8 +39,8 @@ impl Task {
     // 创建文件
     pub fn sys_mkdirat(&self, dir_fd: usize, filename: UserAddr<u8>, flags: usize) -> Result<(), RuntimeError> {
         let filename = filename.read_string();
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
         debug!("dir_fd: {:#x}, filename: {}", dir_fd, filename);
 
         // 判断文件描述符是否存在
@@ -48,11 +49,20 @@ impl Task {
             None
         } else {
             // 判度是否存在节点
-            let file = process.fd_table.get_file(dir_fd)?;
+            let file = process.fd_table.read().get_file(dir_fd)?;
             Some(file.get_inode())
         };
-        if filename != "/" {
-            INode::mkdir(current, &filename, flags as u16)?;
+        if filename != "/" && INode::get(current.clone(), &filename).is_err() {
+            // 在目录中创建子目录需要目录的写权限
+            let pnode = INode::get_parent_dir(current.clone(), &filename)?;
+            if !process.cred.permitted(&pnode.stat(), W_OK | X_OK) {
+                drop(process);
+                inner.context.x[10] = EACCES;
+                return Ok(());
+            }
+            let dir = INode::mkdir(current, &filename, flags as u16)?;
+            dir.set_owner(process.cred.euid, process.cred.egid);
+            dir.set_mode(flags as u32);
         }
         drop(process);
         inner.context.x[10] = 0;
@@ -61,17 +71,25 @@ impl Task {
     // 取消链接文件
     pub fn sys_unlinkat(&self, fd: usize, filename: UserAddr<u8>, _flags: usize) -> Result<(), RuntimeError> {
         let filename = filename.read_string();
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
 
         // 判断文件描述符是否存在
         let current = if fd == FD_NULL {
             None
         } else {
-            let file = process.fd_table.get_file(fd)?;
+            let file = process.fd_table.read().get_file(fd)?;
             Some(file.get_inode())
         };
         let cnode = INode::get(current, &filename)?;
+        // 删除文件需要父目录的写权限
+        let permitted = cnode.get_parent()
+            .map_or(true, |x| process.cred.permitted(&x.stat(), W_OK | X_OK));
+        if !permitted {
+            drop(process);
+            inner.context.x[10] = EACCES;
+            return Ok(());
+        }
         cnode.del_self();
         drop(process);
         inner.context.x[10] = 0;
@@ -1,5 +1,59 @@
-use crate::task::task::Task;
+use crate::{task::task::Task, runtime_err::RuntimeError, memory::addr::UserAddr, fs::file::fcntl_cmd, sys_call::{OpenFlags, consts::{EBADF, EINVAL}}};
 
-impl Task {
+// 文件描述符标志
+const FD_CLOEXEC: usize = 1;
+
+// 记录锁类型
+const F_UNLCK: u16 = 2;
 
-}
\ No newline at end of file
+impl Task {
+    // 控制文件描述符
+    pub fn sys_fcntl(&self, fd: usize, cmd: usize, arg: usize) -> Result<(), RuntimeError> {
+        debug!("val: fd {}  cmd {:#x} arg {:#x}", fd, cmd, arg);
+        let process = self.get_process();
+        let process = process.write();
+        let mut fd_table = process.fd_table.write();
+        let file = match fd_table.get(fd) {
+            Ok(file) => file,
+            Err(_) => {
+                drop(fd_table);
+                drop(process);
+                self.update_context(|x| x.x[10] = EBADF);
+                return Ok(());
+            }
+        };
+        let result = match cmd {
+            // 复制文件描述符 新的fd不小于arg
+            fcntl_cmd::DUPFD | fcntl_cmd::DUPFD_CLOEXEC if arg >= process.nofile_limit() => EINVAL,
+            fcntl_cmd::DUPFD | fcntl_cmd::DUPFD_CLOEXEC => {
+                let new_fd = fd_table.alloc_from(arg, process.nofile_limit())?;
+                fd_table.set_with_cloexec(new_fd, file, cmd == fcntl_cmd::DUPFD_CLOEXEC);
+                new_fd
+            }
+            fcntl_cmd::GETFD => {
+                if fd_table.get_cloexec(fd)? { FD_CLOEXEC } else { 0 }
+            }
+            fcntl_cmd::SETFD => {
+                fd_table.set_cloexec(fd, arg & FD_CLOEXEC != 0)?;
+                0
+            }
+            fcntl_cmd::GETFL => file.get_flags().bits() as usize,
+            fcntl_cmd::SETFL => {
+                file.set_flags(OpenFlags::from_bits_truncate(arg as u32));
+                0
+            }
+            // 不支持记录锁 总是可以加锁
+            fcntl_cmd::GETLK => {
+                *UserAddr::<u16>::from(arg).transfer() = F_UNLCK;
+                0
+            }
+            fcntl_cmd::SETLK | fcntl_cmd::SETLKW => 0,
+            _ => EINVAL
+        };
+        drop(fd_table);
+        drop(process);
+        let mut inner = self.inner.write();
+        inner.context.x[10] = result;
+        Ok(())
+    }
+}
@@ -1,95 +1,96 @@
-use alloc::{rc::Rc, string::ToString};
+use alloc::{sync::Arc, string::ToString};
 
-use crate::{task::{task::Task, fd_table::{FileDesc, FD_NULL}, pipe::new_pipe}, runtime_err::RuntimeError, memory::addr::UserAddr, sys_call::OpenFlags, fs::{stdio::{StdZero, StdNull}, specials::{proc_mounts::ProcMounts, proc_meminfo::ProcMeminfo, etc_adjtime::EtcAdjtime, dev_rtc::DevRtc}, filetree::INode}, interrupt::timer::TimeSpec};
+use crate::{task::{task::Task, fd_table::{FileDesc, FD_NULL}, cred::{Credentials, R_OK, W_OK, X_OK}, pipe::new_pipe, task_scheduler::get_current_task, wait_queue::{poll_events, poll_wait}}, runtime_err::RuntimeError, memory::addr::UserAddr, sys_call::{OpenFlags, consts::{EBADF, EINVAL, ENOENT, EEXIST, ENOTDIR, EISDIR, EACCES}}, fs::{specials::open_special, filetree::INode, file::File}, interrupt::timer::{TimeSpec, get_time_us}};
 
 impl Task {
     // 复制文件描述符
     pub fn sys_dup(&self, fd: usize) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
-        let fd_v = process.fd_table.get(fd)?.clone();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
+        let fd_v = process.fd_table.read().get(fd)?.clone();
         // 判断文件描述符是否存在
-        let new_fd = process.fd_table.push(fd_v);
+        let new_fd = process.fd_table.write().push(fd_v, process.nofile_limit())?;
         drop(process);
         inner.context.x[10] = new_fd;
         Ok(())
     }
-    // 复制文件描述符
-    pub fn sys_dup3(&self, fd: usize, new_fd: usize) -> Result<(), RuntimeError> {
+    // 复制文件描述符 新的文件描述符与原来的共享读写位置 只支持O_CLOEXEC标志
+    pub fn sys_dup3(&self, fd: usize, new_fd: usize, flags: usize) -> Result<(), RuntimeError> {
         debug!("dup fd: {} to fd: {}", fd, new_fd);
-        let mut inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
-        // 判断是否存在文件描述符
-        let fd_v = process.fd_table.get(fd)?.clone();
-        // if let Ok(file) = fd_v.clone().downcast::<File>() {
-        //     file.lseek(0, 0);
-        // }
-        process.fd_table.set(new_fd, fd_v);
+        let mut inner = self.inner.write();
+        let process = self.process.write();
+        let flags = OpenFlags::from_bits_truncate(flags as u32);
+        if fd == new_fd || !(flags - OpenFlags::CLOEXEC).is_empty() {
+            drop(process);
+            inner.context.x[10] = EINVAL;
+            return Ok(());
+        }
+        // 判断是否存在文件描述符 新的fd不能超出RLIMIT_NOFILE
+        let mut fd_table = process.fd_table.write();
+        let fd_v = match fd_table.get(fd) {
+            Ok(fd_v) if new_fd < process.nofile_limit() => fd_v,
+            _ => {
+                drop(fd_table);
+                drop(process);
+                inner.context.x[10] = EBADF;
+                return Ok(());
+            }
+        };
+        fd_table.set_with_cloexec(new_fd, fd_v, flags.contains(OpenFlags::CLOEXEC));
+        drop(fd_table);
         drop(process);
         inner.context.x[10] = new_fd;
         Ok(())
     }
     // 打开文件
-    pub fn sys_openat(&self, fd: usize, filename: UserAddr<u8>, flags: usize, _open_mod: usize) -> Result<(), RuntimeError> {
+    pub fn sys_openat(&self, fd: usize, filename: UserAddr<u8>, flags: usize, open_mod: usize) -> Result<(), RuntimeError> {
         let filename = filename.read_string();
         debug!("open file: {}  flags: {:#x}", filename, flags);
-        let mut inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
 
         // 获取文件信息
         let flags = OpenFlags::from_bits_truncate(flags as u32);
-
-        if filename == "/dev/zero" {
-            let fd = process.fd_table.push(FileDesc::new(Rc::new(StdZero)));
+        if !flags.valid_access() {
             drop(process);
-            inner.context.x[10] = fd;
-            return Ok(())
-        } else if filename == "/dev/null" {
-            let fd = process.fd_table.push(FileDesc::new(Rc::new(StdNull)));
-            drop(process);
-            inner.context.x[10] = fd;
-            return Ok(())
-        } else if filename == "/proc/mounts" {
-            let fd = process.fd_table.push(FileDesc::new(Rc::new(ProcMounts::new())));
-            drop(process);
-            inner.context.x[10] = fd;
-            return Ok(())
-        } else if filename == "/proc/meminfo" {
-            let fd = process.fd_table.push(FileDesc::new(Rc::new(ProcMeminfo::new())));
-            drop(process);
-            inner.context.x[10] = fd;
-            return Ok(())
-        } else if filename == "/etc/adjtime" {
-            let fd = process.fd_table.push(FileDesc::new(Rc::new(EtcAdjtime::new())));
-            drop(process);
-            inner.context.x[10] = fd;
-            return Ok(())
-        } else if filename == "/dev/rtc" {
-            let fd = process.fd_table.push(FileDesc::new(Rc::new(DevRtc::new())));
+            inner.context.x[10] = EINVAL;
+            return Ok(());
+        }
+
+        // 特殊文件
+        if let Some(file) = open_special(&filename) {
+            let result = if (flags.writeable() && !file.writeable())
+                || !process.cred.permitted(&file.stat(), open_access(flags)) {
+                EACCES
+            } else {
+                let fd = process.fd_table.write().alloc(process.nofile_limit())?;
+                process.fd_table.write().set_with_cloexec(fd, FileDesc::with_flags(file, flags),
+                    flags.contains(OpenFlags::CLOEXEC));
+                fd
+            };
             drop(process);
-            inner.context.x[10] = fd;
+            inner.context.x[10] = result;
             return Ok(())
         }
 
-
         // 判断文件描述符是否存在
         let current = if fd == FD_NULL {
             None
         } else {
-            let file = process.fd_table.get_file(fd)?;
+            let file = process.fd_table.read().get_file(fd)?;
             Some(file.get_inode())
         };
-        // 根据文件类型匹配
-        let file = if flags.contains(OpenFlags::CREATE) {
-            INode::open_or_create(current, &filename)?
-        } else {
-            INode::open(current, &filename)?
+        let file = match open_file(current, &filename, flags, &process.cred, open_mod as u32) {
+            Ok(file) => file,
+            Err(err) => {
+                drop(process);
+                inner.context.x[10] = err;
+                return Ok(());
+            }
         };
-        // if flags.contains(OpenFlags::WRONLY) {
-        //     file.lseek(0, 2);
-        // }
-        let fd = process.fd_table.alloc();
-        process.fd_table.set(fd, FileDesc::new(file));
+        let fd = process.fd_table.write().alloc(process.nofile_limit())?;
+        process.fd_table.write().set_with_cloexec(fd, FileDesc::with_flags(file, flags),
+            flags.contains(OpenFlags::CLOEXEC));
         drop(process);
         debug!("return fd: {}", fd);
         inner.context.x[10] = fd;
@@ -98,9 +99,9 @@ impl Task {
     // 关闭文件
     pub fn sys_close(&self, fd: usize) -> Result<(), RuntimeError> {
         debug!("close fd: {}", fd);
-        let mut inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
-        process.fd_table.dealloc(fd);
+        let mut inner = self.inner.write();
+        let process = self.process.write();
+        process.fd_table.write().dealloc(fd);
         drop(process);
         inner.context.x[10] = 0;
         Ok(())
@@ -108,7 +109,7 @@ impl Task {
 
     pub fn sys_readlinkat(&self, dir_fd: usize, path: UserAddr<u8>, 
         buf: UserAddr<u8>, len: usize) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.write();
         let path = path.read_string();
         debug!("read {} from dir_fd: {:#x} len: {}", path, dir_fd, len);
         let path = if path == "/proc/self/exe" {
@@ -127,27 +128,90 @@ impl Task {
         Ok(())
     }
 
-    pub fn sys_ppoll(&self, fds: UserAddr<PollFD>, nfds: usize, _timeout: UserAddr<TimeSpec>) -> Result<(), RuntimeError> {
+    pub fn sys_ppoll(&self, fds: UserAddr<PollFD>, nfds: usize, timeout: UserAddr<TimeSpec>) -> Result<(), RuntimeError> {
         let fds = fds.transfer_vec(nfds);
-        let mut inner = self.inner.borrow_mut();
+        let events = poll_events();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
         debug!("wait for fds: {}", fds.len());
-        for i in fds {
-            debug!("wait fd: {}", i.fd);
+        // 检查文件描述符状态
+        let mut ready = 0;
+        for i in fds.iter_mut() {
+            i.revents = 0;
+            match process.fd_table.read().get(i.fd as usize) {
+                Ok(fd) => {
+                    if i.envents & POLLIN != 0 && fd.read_ready() {
+                        i.revents |= POLLIN;
+                    }
+                    if i.envents & POLLOUT != 0 && fd.write_ready() {
+                        i.revents |= POLLOUT;
+                    }
+                },
+                Err(_) => i.revents |= POLLNVAL
+            }
+            if i.revents != 0 {
+                ready += 1;
+            }
         }
-        inner.context.x[10] = 1;
-        Ok(())
+        drop(process);
+
+        // 存在就绪的文件描述符
+        if ready > 0 {
+            inner.wake_time = 0;
+            inner.context.x[10] = ready;
+            return Ok(());
+        }
+
+        // 第一次执行 设置超时时间
+        if inner.wake_time == 0 && timeout.is_valid() {
+            inner.wake_time = get_time_us() + timeout.transfer().as_us();
+            if get_time_us() < inner.wake_time {
+                get_current_task().unwrap().wake_at(inner.wake_time);
+            }
+        }
+
+        // 已经超时
+        if timeout.is_valid() && get_time_us() >= inner.wake_time {
+            inner.wake_time = 0;
+            inner.context.x[10] = 0;
+            return Ok(());
+        }
+
+        // 进入等待队列 文件状态改变或定时器到期后重新执行
+        inner.context.sepc -= 4;
+        drop(inner);
+        poll_wait(get_current_task().unwrap(), events);
+        Err(RuntimeError::ChangeTask)
     }
 
     // 管道符
-    pub fn sys_pipe2(&self, req_ptr: UserAddr<u32>) -> Result<(), RuntimeError> {
+    pub fn sys_pipe2(&self, req_ptr: UserAddr<u32>, flags: usize) -> Result<(), RuntimeError> {
+        let flags = OpenFlags::from_bits_truncate(flags as u32);
+        if !(flags - OpenFlags::NONBLOCK - OpenFlags::CLOEXEC - OpenFlags::DIRECT).is_empty() {
+            self.update_context(|x| x.x[10] = EINVAL);
+            return Ok(());
+        }
         let pipe_arr =  req_ptr.transfer_vec(2);
-        let mut inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
         // 创建pipe
-        let (read_pipe, write_pipe) = new_pipe();
+        let (read_pipe, write_pipe) = new_pipe(flags & OpenFlags::NONBLOCK);
+        let cloexec = flags.contains(OpenFlags::CLOEXEC);
         // 写入数据
-        pipe_arr[0] = process.fd_table.push(read_pipe) as u32;
-        pipe_arr[1] = process.fd_table.push(write_pipe) as u32;
+        let mut fd_table = process.fd_table.write();
+        let read_fd = fd_table.alloc(process.nofile_limit())?;
+        fd_table.set_with_cloexec(read_fd, read_pipe, cloexec);
+        let write_fd = match fd_table.alloc(process.nofile_limit()) {
+            Ok(write_fd) => write_fd,
+            Err(err) => {
+                fd_table.dealloc(read_fd);
+                return Err(err);
+            }
+        };
+        fd_table.set_with_cloexec(write_fd, write_pipe, cloexec);
+        drop(fd_table);
+        pipe_arr[0] = read_fd as u32;
+        pipe_arr[1] = write_fd as u32;
                 
         drop(process);
         // 创建成功
@@ -158,6 +222,86 @@ impl Task {
 
 }
 
+// 根据打开标志打开或创建文件 失败时返回错误码
+// 新创建的文件属于当前的有效用户和组 mode为文件的权限位
+fn open_file(current: Option<Arc<INode>>, filename: &str, flags: OpenFlags,
+        cred: &Credentials, mode: u32) -> Result<Arc<File>, usize> {
+    // 路径中的目录需要搜索权限
+    if !cred.can_search(current.clone(), filename) {
+        return Err(EACCES);
+    }
+    // O_TMPFILE 在目录中创建匿名文件
+    if flags.contains(OpenFlags::TMPFILE) {
+        if !flags.writeable() {
+            return Err(EINVAL);
+        }
+        let dir = INode::get(current, filename).map_err(|_| ENOENT)?;
+        if !dir.is_dir() {
+            return Err(ENOTDIR);
+        }
+        if !cred.permitted(&dir.stat(), W_OK | X_OK) {
+            return Err(EACCES);
+        }
+        let file = INode::create_anonymous(&dir).map_err(|_| ENOENT)?;
+        file.get_inode().set_owner(cred.euid, cred.egid);
+        file.get_inode().set_mode(mode);
+        return Ok(file);
+    }
+
+    let file = match INode::get(current.clone(), filename) {
+        Ok(inode) => {
+            if flags.contains(OpenFlags::CREATE) && flags.contains(OpenFlags::EXCL) {
+                return Err(EEXIST);
+            }
+            if !cred.permitted(&inode.stat(), open_access(flags)) {
+                return Err(EACCES);
+            }
+            INode::open_node(inode).map_err(|_| ENOENT)?
+        }
+        Err(_) if flags.contains(OpenFlags::CREATE) => {
+            // 在目录中创建文件需要目录的写权限
+            let dir = INode::get_parent_dir(current.clone(), filename).map_err(|_| ENOENT)?;
+            if !cred.permitted(&dir.stat(), W_OK | X_OK) {
+                return Err(EACCES);
+            }
+            let file = INode::open_or_create(current, filename).map_err(|_| ENOENT)?;
+            file.get_inode().set_owner(cred.euid, cred.egid);
+            file.get_inode().set_mode(mode);
+            file
+        }
+        Err(_) => return Err(ENOENT)
+    };
+
+    if file.get_inode().is_dir() {
+        // 目录只能以只读方式打开
+        if flags.writeable() {
+            return Err(EISDIR);
+        }
+    } else if flags.contains(OpenFlags::O_DIRECTORY) {
+        return Err(ENOTDIR);
+    } else if flags.contains(OpenFlags::TRUNC) && flags.writeable() {
+        file.truncate(0).map_err(|_| ENOENT)?;
+    }
+    Ok(file)
+}
+
+// 根据打开标志获取需要的访问权限
+fn open_access(flags: OpenFlags) -> u32 {
+    let mut access = 0;
+    if flags.readable() {
+        access |= R_OK;
+    }
+    if flags.writeable() {
+        access |= W_OK;
+    }
+    access
+}
+
+// ppoll 事件
+pub const POLLIN: u16 = 0x001;
+pub const POLLOUT: u16 = 0x004;
+pub const POLLNVAL: u16 = 0x020;
+
 #[repr(C)]
 pub struct PollFD {
     pub fd: u32,
@@ -1,71 +1,146 @@
-use crate::{task::{task::Task, fd_table::IoVec}, memory::addr::UserAddr, runtime_err::RuntimeError, fs::file::FileOP};
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{task::{task::Task, fd_table::{IoVec, FileDesc}, signal::Signal, task_scheduler::get_current_task}, memory::addr::UserAddr, runtime_err::RuntimeError, fs::file::FileOP, sys_call::consts::{EAGAIN, EBADF, EINVAL, EPIPE, ESPIPE}};
 
 impl Task {
     // 读取
     pub fn sys_read(&self, fd: usize, buf_ptr: UserAddr<u8>, count: usize) -> Result<(), RuntimeError> {
         debug!("sys_read, fd: {}, buf_ptr: {:#x}, count: {}", fd, buf_ptr.bits(), count);
         let buf = buf_ptr.transfer_vec(count);
-        let mut inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
 
         // 判断文件描述符是否存在
-        let reader = process.fd_table.get(fd)?;
-        let value = if reader.readable() {
-            reader.read(buf)
-        } else {
-            usize::MAX
-        };
+        let reader = process.fd_table.read().get(fd)?;
+        // 没有以读方式打开
+        if !reader.readable() {
+            drop(process);
+            inner.context.x[10] = EBADF;
+            return Ok(());
+        }
+        // 非阻塞模式下暂时没有数据
+        if !reader.read_ready() && reader.nonblock() {
+            drop(process);
+            inner.context.x[10] = EAGAIN;
+            return Ok(());
+        }
+        // 暂时没有数据 进入等待队列 写入后唤醒并重新执行
+        if !reader.read_ready() {
+            let reader = reader.clone();
+            drop(process);
+            inner.context.sepc -= 4;
+            drop(inner);
+            reader.wait_read(get_current_task().unwrap());
+            return Err(RuntimeError::ChangeTask);
+        }
+        let value = reader.read(buf);
         drop(process);
         debug!("read_size = {}", value);
         inner.context.x[10] = value;
         Ok(())
     }
 
+    // 写入缓冲区 阻塞模式下缓冲区已满时等待读取后重新执行 直到全部写入
+    // 已经写入的长度保存在io_done中 重新执行时从这里继续
+    fn write_all(&self, fd: FileDesc, buf: &[u8]) -> Result<(), RuntimeError> {
+        let mut inner = self.inner.write();
+        let mut done = inner.io_done;
+        let mut value;
+        loop {
+            value = fd.write(&buf[done..], buf.len() - done);
+            // 缓冲区已满 进入等待队列 读取后唤醒并重新执行
+            if value == EAGAIN && !fd.nonblock() {
+                inner.io_done = done;
+                inner.context.sepc -= 4;
+                drop(inner);
+                fd.wait_write(get_current_task().unwrap());
+                return Err(RuntimeError::ChangeTask);
+            }
+            if (value as isize) <= 0 {
+                break;
+            }
+            done += value;
+            if done == buf.len() {
+                break;
+            }
+        }
+        inner.io_done = 0;
+        // 已经写入部分时返回写入的长度 否则返回错误码
+        inner.context.x[10] = if done > 0 || buf.is_empty() { done } else { value };
+        // 读端已经关闭 发送SIGPIPE
+        if value == EPIPE {
+            drop(inner);
+            self.signal(Signal::SIGPIPE as usize)?;
+        }
+        Ok(())
+    }
+
     // 写入
     pub fn sys_write(&self, fd: usize, buf_ptr: UserAddr<u8>, count: usize) -> Result<(), RuntimeError> {
         debug!("write fd: {} buf_ptr: {:#x} count: {}", fd, buf_ptr.bits(), count);
         let buf = buf_ptr.transfer_vec(count);
-        let mut inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
         
         // 判断文件描述符是否存在
-        let writer = process.fd_table.get(fd)?;
-        let value = if writer.writeable() {
-            writer.write(buf, buf.len())
-        } else {
-            usize::MAX
-        };
+        let writer = process.fd_table.read().get(fd)?;
         drop(process);
-        inner.context.x[10] = value;
-        Ok(())
+        // 没有以写方式打开
+        if !writer.writeable() {
+            inner.context.x[10] = EBADF;
+            return Ok(());
+        }
+        drop(inner);
+        self.write_all(writer, buf)
     }
     // 写入
     pub fn sys_writev(&self, fd: usize, iov: UserAddr<IoVec>, iovcnt: usize) -> Result<(), RuntimeError> {
         let iov_vec = iov.transfer_vec(iovcnt);
         
-        let mut inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
         
-        let fd = process.fd_table.get(fd)?;
-        let mut cnt = 0;
+        let fd = process.fd_table.read().get(fd)?;
+        drop(process);
+        if !fd.writeable() {
+            inner.context.x[10] = EBADF;
+            return Ok(());
+        }
+        drop(inner);
+        // 合并后一次写入 保证不超过PIPE_BUF的写入是原子的
+        let mut buf = Vec::new();
         for i in iov_vec {
-            // let buf = get_buf_from_phys_addr(i.iov_base.translate(process.pmm.clone()), 
-            //     i.iov_len);
-            let buf = i.iov_base.transfer_vec(i.iov_len);
-            cnt += fd.write(buf, i.iov_len);
+            buf.extend_from_slice(i.iov_base.transfer_vec(i.iov_len));
         }
-        drop(process);
-        inner.context.x[10] = cnt;
-        Ok(())
+        self.write_all(fd, &buf)
     }
 
     pub fn sys_readv(&self, fd: usize, iov: UserAddr<IoVec>, iovcnt: usize) -> Result<(), RuntimeError> {
         let iov_vec = iov.transfer_vec(iovcnt);
 
-        let mut inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
         
-        let fd = process.fd_table.get(fd)?;
+        let fd = process.fd_table.read().get(fd)?;
+        if !fd.readable() {
+            drop(process);
+            inner.context.x[10] = EBADF;
+            return Ok(());
+        }
+        if !fd.read_ready() && fd.nonblock() {
+            drop(process);
+            inner.context.x[10] = EAGAIN;
+            return Ok(());
+        }
+        // 暂时没有数据 进入等待队列 写入后唤醒并重新执行
+        if !fd.read_ready() {
+            let fd = fd.clone();
+            drop(process);
+            inner.context.sepc -= 4;
+            drop(inner);
+            fd.wait_read(get_current_task().unwrap());
+            return Err(RuntimeError::ChangeTask);
+        }
         let mut cnt = 0;
         for i in iov_vec {
             // let buf = get_buf_from_phys_addr(i.iov_base, 
@@ -80,40 +155,113 @@ impl Task {
 
     pub fn sys_lseek(&self, fd: usize, offset: usize, whence: usize) -> Result<(), RuntimeError> {
         debug!("lseek: fd {}, offset: {}, whench: {}", fd, offset as isize, whence);
-        let mut inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
 
-        let file = process.fd_table.get(fd)?;
+        let file = process.fd_table.read().get(fd)?;
         let offset = file.lseek(offset, whence);
-        // debug!("lseek Filename: {}", file.get_inode().get_filename());
-        // let inode = file.get_inode();
         drop(process);
         inner.context.x[10] = offset;
         Ok(())
     }
 
-    // 原子读
+    // 获取可以指定位置读写的文件 出错时返回错误码 不能移动位置的文件返回ESPIPE
+    fn get_positional_file(&self, fd: usize, offset: usize) -> Result<Arc<dyn FileOP>, usize> {
+        let process = self.get_process();
+        let process = process.write();
+        let file = process.fd_table.read().get(fd).map_err(|_| EBADF)?;
+        if !file.seekable() {
+            return Err(ESPIPE);
+        }
+        if (offset as isize) < 0 {
+            return Err(EINVAL);
+        }
+        Ok(file.file.clone())
+    }
+
+    // 原子读 不改变文件描述符的位置
     pub fn sys_pread(&self, fd: usize, ptr: UserAddr<u8>, len: usize, offset: usize) -> Result<(), RuntimeError> {
         let buf = ptr.transfer_vec(len);
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.borrow_mut();
-        let file = process.fd_table.get_file(fd)?;
-        let ret = file.read_at(offset, buf);
-        drop(process);
+        let ret = match self.get_positional_file(fd, offset) {
+            Ok(file) => file.read_at(offset, buf),
+            Err(err) => err
+        };
+        let mut inner = self.inner.write();
+        inner.context.x[10] = ret;
+        Ok(())
+    }
+
+    // 原子写 不改变文件描述符的位置 忽略O_APPEND
+    pub fn sys_pwrite(&self, fd: usize, ptr: UserAddr<u8>, len: usize, offset: usize) -> Result<(), RuntimeError> {
+        let buf = ptr.transfer_vec(len);
+        let ret = match self.get_positional_file(fd, offset) {
+            Ok(file) => file.write_at(offset, buf, len),
+            Err(err) => err
+        };
+        let mut inner = self.inner.write();
+        inner.context.x[10] = ret;
+        Ok(())
+    }
+
+    // 从指定位置读取到多个缓冲区
+    pub fn sys_preadv(&self, fd: usize, iov: UserAddr<IoVec>, iovcnt: usize, offset: usize) -> Result<(), RuntimeError> {
+        let iov_vec = iov.transfer_vec(iovcnt);
+        let ret = match self.get_positional_file(fd, offset) {
+            Ok(file) => {
+                let mut cnt = 0;
+                for i in iov_vec {
+                    let buf = i.iov_base.transfer_vec(i.iov_len);
+                    let read_len = file.read_at(offset + cnt, buf);
+                    cnt += read_len;
+                    // 已经读取到文件末尾
+                    if read_len < i.iov_len {
+                        break;
+                    }
+                }
+                cnt
+            }
+            Err(err) => err
+        };
+        let mut inner = self.inner.write();
+        inner.context.x[10] = ret;
+        Ok(())
+    }
+
+    // 将多个缓冲区写入到指定位置
+    pub fn sys_pwritev(&self, fd: usize, iov: UserAddr<IoVec>, iovcnt: usize, offset: usize) -> Result<(), RuntimeError> {
+        let iov_vec = iov.transfer_vec(iovcnt);
+        let ret = match self.get_positional_file(fd, offset) {
+            Ok(file) => {
+                let mut cnt = 0;
+                for i in iov_vec {
+                    let buf = i.iov_base.transfer_vec(i.iov_len);
+                    let write_len = file.write_at(offset + cnt, buf, i.iov_len);
+                    // 写入失败时返回错误码
+                    if (write_len as isize) < 0 {
+                        cnt = write_len;
+                        break;
+                    }
+                    cnt += write_len;
+                }
+                cnt
+            }
+            Err(err) => err
+        };
+        let mut inner = self.inner.write();
         inner.context.x[10] = ret;
         Ok(())
     }
 
     pub fn sys_sendfile(&self, out_fd: usize, in_fd: usize, offset_ptr: usize, count: usize) -> Result<(), RuntimeError> {
         debug!("out_fd: {}  in_fd: {}  offset_ptr: {:#x}   count: {}", out_fd, in_fd, offset_ptr, count);
-        let mut inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
-        let in_file = process.fd_table.get(in_fd)?;
+        let mut inner = self.inner.write();
+        let process = self.process.write();
+        let in_file = process.fd_table.read().get(in_fd)?;
         let size = in_file.get_size();
         let mut buf = vec![0u8; size];
         let read_size = in_file.read(&mut buf);
 
-        let out_file = process.fd_table.get(out_fd)?;
+        let out_file = process.fd_table.read().get(out_fd)?;
         out_file.write(&buf, buf.len());
 
         drop(process);
@@ -1,162 +1,233 @@
-use crate::{task::{task::Task, fd_table::FD_NULL}, memory::addr::UserAddr, fs::{file::{Kstat, FileType}, filetree::INode, StatFS}, runtime_err::RuntimeError};
+use alloc::sync::Arc;
+
+use crate::{task::{task::Task, fd_table::{FD_NULL, AT_EMPTY_PATH}, cred::{S_ISUID, S_ISGID}}, memory::addr::UserAddr, fs::{file::{Kstat, Statx, File, FileType, S_IFMT}, filetree::INode, specials::open_special, StatFS, mount_dev}, runtime_err::RuntimeError, sys_call::consts::{EINVAL, ENOTDIR, EPERM}};
+
+// linux_dirent64 中文件名之前的长度
+const DIRENT_HEADER_SIZE: usize = 19;
+
+// 参数为-1时不修改所有者或所属组
+const ID_UNCHANGED: u32 = u32::MAX;
 
 impl Task {
+    // 根据目录和路径获取文件信息 设置AT_EMPTY_PATH且路径为空时获取dir_fd的信息
+    fn get_kstat(&self, dir_fd: usize, path: &str, flags: usize) -> Result<Kstat, RuntimeError> {
+        let process = self.get_process();
+        let process = process.write();
+        if path.is_empty() && flags & AT_EMPTY_PATH != 0 {
+            if dir_fd == FD_NULL {
+                return Ok(process.workspace.read().stat());
+            }
+            let file = process.fd_table.read().get(dir_fd).map_err(|_| RuntimeError::EBADF)?;
+            return Ok(file.file.stat());
+        }
+        if let Some(file) = open_special(path) {
+            return Ok(file.stat());
+        }
+        // 判断文件描述符是否存在
+        let current = if dir_fd == FD_NULL {
+            None
+        } else {
+            let file = process.fd_table.read().get_file(dir_fd).map_err(|_| RuntimeError::EBADF)?;
+            Some(file.get_inode())
+        };
+        Ok(INode::get(current, path)?.stat())
+    }
+
     pub fn sys_fstat(&self, fd: usize, buf_ptr: UserAddr<Kstat>) -> Result<(), RuntimeError> {
         debug!("sys_fstat: {}", fd);
-        let kstat = buf_ptr.transfer();
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.borrow_mut();
-
-        // // 判断文件描述符是否存在
-        let inode = process.fd_table.get_file(fd)?;
-        let inode = inode.get_inode();
-        let _inode = inode.0.borrow_mut();
-        kstat.st_dev = 1;
-        kstat.st_ino = 1;
-        kstat.st_mode = 0;
-        kstat.st_nlink = 1;
-        kstat.st_uid = 0;
-        kstat.st_gid = 0;
-        kstat.st_rdev = 0;
-        kstat.__pad = 0;
-        kstat.st_blksize = 512; // 磁盘扇区大小
-        // kstat.st_size = inode.size as u64;
-        // kstat.st_blocks = ((inode.size - 1 + 512) / 512) as u64;
-        // kstat.st_atime_sec = inode.st_atime_sec;
-        // kstat.st_atime_nsec = inode.st_atime_nsec;
-        // kstat.st_mtime_sec = inode.st_mtime_sec;
-        // kstat.st_mtime_nsec = inode.st_mtime_nsec;
-        // kstat.st_ctime_sec = inode.st_ctime_sec;
-        // kstat.st_ctime_nsec = inode.st_ctime_nsec;
-
-        // debug
-        kstat.st_size = 0;
-        kstat.st_blocks = 0;
-        kstat.st_atime_sec  = 0;
-        kstat.st_atime_nsec = 0;
-        kstat.st_mtime_sec  = 0;
-        kstat.st_mtime_nsec = 0;
-        kstat.st_ctime_sec  = 0;
-        kstat.st_ctime_nsec = 0;
-        drop(process);
-        inner.context.x[10] = 0;
+        let kstat = self.get_kstat(fd, "", AT_EMPTY_PATH)?;
+        *buf_ptr.transfer() = kstat;
+        self.update_context(|x| x.x[10] = 0);
         Ok(())
     }
 
     // 获取文件信息
-    pub fn sys_fstatat(&self, dir_fd: usize, filename: UserAddr<u8>, stat_ptr: UserAddr<Kstat>, _flags: usize) -> Result<(), RuntimeError> {
+    pub fn sys_fstatat(&self, dir_fd: usize, filename: UserAddr<u8>, stat_ptr: UserAddr<Kstat>, flags: usize) -> Result<(), RuntimeError> {
         let filename = filename.read_string();
-        let kstat = stat_ptr.transfer();
         debug!("sys_fstatat: dir_fd {:#x}, filename: {}, filename_len: {}", dir_fd, filename, filename.len());
+        let kstat = self.get_kstat(dir_fd, &filename, flags)?;
+        *stat_ptr.transfer() = kstat;
+        self.update_context(|x| x.x[10] = 0);
+        Ok(())
+    }
 
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.borrow_mut();
-
-        if filename != "/dev/null" {
-            // 判断文件描述符是否存在
-            let file = if dir_fd == FD_NULL {
-                None
-            } else {
-                let file = process.fd_table.get_file(dir_fd)?;
-                Some(file.get_inode())
-            };
-
-            let inode = INode::get(file, &filename)?;
-            let inode = inode.0.borrow_mut();
-            kstat.st_dev = 1;
-            kstat.st_ino = 1;
-            // kstat_ptr.st_mode = 0;
-            if inode.file_type == FileType::Directory {
-                kstat.st_mode = 0o40000;
-            } else {
-                kstat.st_mode = 0;
-            }
-            // kstat.st_nlink = inode.nlinkes as u32;
-            // kstat.st_uid = 0;
-            // kstat.st_gid = 0;
-            // kstat.st_rdev = 0;
-            // kstat.__pad = 0;
-            // kstat.st_size = inode.size as u64;
-            // kstat.st_blksize = 512;
-            // kstat.st_blocks = ((inode.size - 1 + 512) / 512) as u64;
-            // kstat.st_atime_sec = inode.st_atime_sec;
-            // kstat.st_atime_nsec = inode.st_atime_nsec;
-            // kstat.st_mtime_sec = inode.st_mtime_sec;
-            // kstat.st_mtime_nsec = inode.st_mtime_nsec;
-            // kstat.st_ctime_sec = inode.st_ctime_sec;
-            // kstat.st_ctime_nsec = inode.st_ctime_nsec;
-            drop(process);
-            inner.context.x[10] = 0;
-            Ok(())
-        } else {
-            // kstat.
-            kstat.st_mode = 0o20000;
-            drop(process);
-            inner.context.x[10] = 0;
-            Ok(())
-        }
+    // 获取文件扩展信息 所有文件都支持基本信息 忽略mask
+    pub fn sys_statx(&self, dir_fd: usize, filename: UserAddr<u8>, flags: usize, _mask: usize, statx_ptr: UserAddr<Statx>) -> Result<(), RuntimeError> {
+        let filename = filename.read_string();
+        debug!("sys_statx: dir_fd {:#x}, filename: {}", dir_fd, filename);
+        let kstat = self.get_kstat(dir_fd, &filename, flags)?;
+        *statx_ptr.transfer() = Statx::from_kstat(&kstat);
+        self.update_context(|x| x.x[10] = 0);
+        Ok(())
     }
 
-    // 获取文件信息
+    // 读取目录项 从文件描述符的位置继续读取 返回写入的长度
     pub fn sys_getdents(&self, fd: usize, ptr: UserAddr<u8>, len: usize) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.borrow_mut();
         debug!("get dents: fd: {} ptr: {:#x} len: {:#x}", fd, ptr.bits(), len);
+        let process = self.get_process();
+        let process = process.write();
+        let file_desc = process.fd_table.read().get(fd).map_err(|_| RuntimeError::EBADF)?;
+        let dir = match file_desc.downcast::<File>() {
+            Ok(file) if file.get_file_type() == FileType::Directory => file.get_inode(),
+            _ => {
+                drop(process);
+                self.update_context(|x| x.x[10] = ENOTDIR);
+                return Ok(());
+            }
+        };
         let buf = ptr.transfer_vec(len);
-        let dir_file = process.fd_table.get_file(fd)?;
-        
+
         let mut pos = 0;
-        while let Some((i, inode)) = dir_file.entry_next() {
-            let sub_node_name = inode.get_filename();
-            debug!("子节点: {}  filetype: {:?} filename len: {}", sub_node_name, inode.get_file_type(), sub_node_name.len());
-            let sub_node_name = sub_node_name.as_bytes();
-            let node_size = ((19 + sub_node_name.len() as u16 + 1 + 7) / 8) * 8;
-            let next = pos + node_size as usize;
-            buf[pos..pos+8].copy_from_slice(&(i as u64).to_ne_bytes());
-            pos += 8;
-            buf[pos..pos+8].copy_from_slice(&(i as u64).to_ne_bytes());
-            pos += 8;
-            buf[pos..pos+2].copy_from_slice(&node_size.to_ne_bytes());
-            pos += 2;
-            // buf[pos] = 8;   // 写入type  支持文件夹类型
-            buf[pos] = match inode.get_file_type() {
-                FileType::File => 8,
-                FileType::Directory => 4,
-                _ => 0
-            };
-            pos += 1;
-            buf[pos..pos + sub_node_name.len()].copy_from_slice(sub_node_name);
-            // pos += node_size as usize;
-            pos += sub_node_name.len();
-            buf[pos..next].fill(0);   // 写入结束符
+        let mut truncated = false;
+        while let Some((offset, name, inode)) = dir.entry_at(file_desc.get_offset()) {
+            // 特殊文件使用与stat相同的信息
+            let kstat = open_special(&inode.get_pwd()).map_or_else(|| inode.stat(), |x| x.stat());
+            let name = name.as_bytes();
+            // 头部19字节 文件名以0结尾 按照8字节对齐
+            let reclen = (DIRENT_HEADER_SIZE + name.len() + 1 + 7) / 8 * 8;
+            if pos + reclen > len {
+                truncated = true;
+                break;
+            }
+            let next = pos + reclen;
+            // d_off 为下一个目录项的位置
+            buf[pos..pos + 8].copy_from_slice(&kstat.st_ino.to_ne_bytes());
+            buf[pos + 8..pos + 16].copy_from_slice(&((offset + 1) as u64).to_ne_bytes());
+            buf[pos + 16..pos + 18].copy_from_slice(&(reclen as u16).to_ne_bytes());
+            buf[pos + 18] = ((kstat.st_mode & S_IFMT) >> 12) as u8;
+            buf[pos + DIRENT_HEADER_SIZE..pos + DIRENT_HEADER_SIZE + name.len()].copy_from_slice(name);
+            buf[pos + DIRENT_HEADER_SIZE + name.len()..next].fill(0);
             pos = next;
-
+            file_desc.set_offset(offset + 1);
         }
-
         drop(process);
         debug!("written size: {}", pos);
-        inner.context.x[10] = pos;
-        // 运行时使用
-        // inner.context.x[10] = 0;
-        
+        // 缓冲区无法放下一个目录项
+        let mut inner = self.inner.write();
+        inner.context.x[10] = if pos == 0 && truncated { EINVAL } else { pos };
         Ok(())
     }
 
-    pub fn sys_statfs(&self, _fd: usize, buf_ptr: UserAddr<StatFS>) -> Result<(), RuntimeError> {
-        let buf = buf_ptr.transfer();
-        
-        buf.f_type = 32;
-        buf.f_bsize = 512;
-        buf.f_blocks = 80;
-        buf.f_bfree = 40;
-        buf.f_bavail = 0;
-        buf.f_files = 32;
-        buf.f_ffree = 0;
-        buf.f_fsid = 32;
-        buf.f_namelen = 20;
-
-        let mut inner = self.inner.borrow_mut();
-        inner.context.x[10] = 0;
+    // 获取路径所在文件系统的信息
+    pub fn sys_statfs(&self, path: UserAddr<u8>, buf_ptr: UserAddr<StatFS>) -> Result<(), RuntimeError> {
+        let path = path.read_string();
+        debug!("sys_statfs: {}", path);
+        let dev = match mount_dev(&path) {
+            Some(dev) => dev,
+            None => self.get_kstat(FD_NULL, &path, 0)?.st_dev
+        };
+        *buf_ptr.transfer() = StatFS::from_dev(dev);
+        self.update_context(|x| x.x[10] = 0);
         Ok(())
-    }    
-}
\ No newline at end of file
+    }
+
+    // 获取文件描述符所在文件系统的信息
+    pub fn sys_fstatfs(&self, fd: usize, buf_ptr: UserAddr<StatFS>) -> Result<(), RuntimeError> {
+        let dev = self.get_kstat(fd, "", AT_EMPTY_PATH)?.st_dev;
+        *buf_ptr.transfer() = StatFS::from_dev(dev);
+        self.update_context(|x| x.x[10] = 0);
+        Ok(())
+    }
+
+    // 根据目录和路径获取文件节点 设置AT_EMPTY_PATH且路径为空时获取dir_fd的节点
+    // 特殊文件和管道等没有文件节点 返回None
+    fn get_path_inode(&self, dir_fd: usize, path: &str, flags: usize) -> Result<Option<Arc<INode>>, RuntimeError> {
+        let process = self.get_process();
+        let process = process.read();
+        if path.is_empty() && flags & AT_EMPTY_PATH != 0 {
+            if dir_fd == FD_NULL {
+                return Ok(Some(process.workspace.read().clone()));
+            }
+            let file = process.fd_table.read().get(dir_fd).map_err(|_| RuntimeError::EBADF)?;
+            return Ok(file.downcast::<File>().ok().map(|x| x.get_inode()));
+        }
+        if open_special(path).is_some() {
+            return Ok(None);
+        }
+        let current = if dir_fd == FD_NULL {
+            None
+        } else {
+            let file = process.fd_table.read().get_file(dir_fd).map_err(|_| RuntimeError::EBADF)?;
+            Some(file.get_inode())
+        };
+        Ok(Some(INode::get(current, path)?))
+    }
+
+    // 修改权限位 只有所有者和特权进程可以修改
+    fn chmod_inode(&self, inode: Option<Arc<INode>>, mode: usize) -> Result<(), RuntimeError> {
+        let inode = match inode {
+            Some(inode) => inode,
+            None => {
+                self.update_context(|x| x.x[10] = EPERM);
+                return Ok(());
+            }
+        };
+        let cred = self.get_process().read().cred.clone();
+        let kstat = inode.stat();
+        if !cred.owns(&kstat) {
+            self.update_context(|x| x.x[10] = EPERM);
+            return Ok(());
+        }
+        // 不属于文件所属组时不能设置S_ISGID
+        let mut mode = mode as u32;
+        if !cred.is_root() && !cred.in_group(kstat.st_gid) {
+            mode &= !S_ISGID;
+        }
+        inode.set_mode(mode);
+        self.update_context(|x| x.x[10] = 0);
+        Ok(())
+    }
+
+    // 修改所有者和所属组 特权进程可以任意修改 所有者只能修改为自己所在的组
+    fn chown_inode(&self, inode: Option<Arc<INode>>, uid: usize, gid: usize) -> Result<(), RuntimeError> {
+        let inode = match inode {
+            Some(inode) => inode,
+            None => {
+                self.update_context(|x| x.x[10] = EPERM);
+                return Ok(());
+            }
+        };
+        let cred = self.get_process().read().cred.clone();
+        let kstat = inode.stat();
+        let (uid, gid) = (uid as u32, gid as u32);
+        let uid = if uid == ID_UNCHANGED { kstat.st_uid } else { uid };
+        let gid = if gid == ID_UNCHANGED { kstat.st_gid } else { gid };
+        let permitted = cred.is_root() || (cred.owns(&kstat) && uid == kstat.st_uid
+            && (gid == kstat.st_gid || cred.in_group(gid)));
+        if !permitted {
+            self.update_context(|x| x.x[10] = EPERM);
+            return Ok(());
+        }
+        inode.set_owner(uid, gid);
+        // 修改普通文件的所有者后清除S_ISUID和S_ISGID
+        if !inode.is_dir() {
+            inode.set_mode(kstat.st_mode & !(S_ISUID | S_ISGID));
+        }
+        self.update_context(|x| x.x[10] = 0);
+        Ok(())
+    }
+
+    pub fn sys_fchmod(&self, fd: usize, mode: usize) -> Result<(), RuntimeError> {
+        let inode = self.get_path_inode(fd, "", AT_EMPTY_PATH)?;
+        self.chmod_inode(inode, mode)
+    }
+
+    // 修改文件权限位
+    pub fn sys_fchmodat(&self, dir_fd: usize, filename: UserAddr<u8>, mode: usize, _flags: usize) -> Result<(), RuntimeError> {
+        let filename = filename.read_string();
+        let inode = self.get_path_inode(dir_fd, &filename, 0)?;
+        self.chmod_inode(inode, mode)
+    }
+
+    pub fn sys_fchown(&self, fd: usize, uid: usize, gid: usize) -> Result<(), RuntimeError> {
+        let inode = self.get_path_inode(fd, "", AT_EMPTY_PATH)?;
+        self.chown_inode(inode, uid, gid)
+    }
+
+    // 修改文件所有者和所属组
+    pub fn sys_fchownat(&self, dir_fd: usize, filename: UserAddr<u8>, uid: usize, gid: usize, flags: usize) -> Result<(), RuntimeError> {
+        let filename = filename.read_string();
+        let inode = self.get_path_inode(dir_fd, &filename, flags)?;
+        self.chown_inode(inode, uid, gid)
+    }
+}
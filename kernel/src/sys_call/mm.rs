@@ -1,6 +1,9 @@
 use crate::fs::file::FileOP;
 use crate::memory::mem_map::MemMap;
 use crate::memory::mem_map::MapFlags;
+use crate::memory::mem_map::ProtFlags;
+use crate::sys_call::consts::EINVAL;
+use crate::sys_call::consts::ENOMEM;
 use crate::memory::page::alloc;
 use crate::memory::page::get_free_page_num;
 use crate::memory::page_table::PTEFlags;
@@ -11,23 +14,36 @@ use crate::memory::addr::VirtAddr;
 use crate::memory::addr::get_buf_from_phys_addr;
 use crate::task::fd_table::FD_NULL;
 use crate::task::fd_table::FD_RANDOM;
+use crate::task::rlimit::{RLIMIT_AS, RLIMIT_DATA};
+use crate::task::user_heap::DEFAULT_HEAP_PAGE_NUM;
 
 impl Task {
     pub fn sys_brk(&self, top_pos: usize) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.read();
+        let mut mm = process.mm.write();
         if top_pos == 0 {
-            let top = process.heap.get_heap_top();
+            let top = mm.heap.get_heap_top();
+            drop(mm);
             drop(process);
             debug!("[sys_brk] brk_addr: {:X}; new_addr: {:X} caller addr: {:X}", top_pos, top, inner.context.sepc);
             inner.context.x[10] = top;
         } else {
-            let ret = if top_pos > process.heap.get_heap_top() + PAGE_SIZE {
-                process.heap.get_heap_top()
+            // 新增加的内存不能超出RLIMIT_DATA和RLIMIT_AS 超出时保持原来的位置
+            let grow = if mm.heap.start == 0 {
+                DEFAULT_HEAP_PAGE_NUM * PAGE_SIZE
             } else {
-                process.heap.set_heap_top(top_pos)?
+                top_pos.saturating_sub(mm.heap.end)
+            };
+            let exceeded = process.rlimits[RLIMIT_DATA].exceeded(mm.get_data_size() + grow)
+                || process.rlimits[RLIMIT_AS].exceeded(mm.get_mapped_size() + grow);
+            let ret = if top_pos > mm.heap.get_heap_top() + PAGE_SIZE || exceeded {
+                mm.heap.get_heap_top()
+            } else {
+                mm.heap.set_heap_top(top_pos)?
             };
             debug!("[sys_brk] brk_addr: {:X}; new_addr: {:X} caller addr: {:X}", top_pos, ret, inner.context.sepc);
+            drop(mm);
             drop(process);
             inner.context.x[10] = ret;
         }
@@ -36,11 +52,20 @@ impl Task {
 
     pub fn sys_mmap(&self, start: usize, len: usize, _prot: usize, 
             flags: usize, fd: usize, offset: usize) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.read();
+        let mut mm = process.mm.write();
         debug!("start: {:#x}, len: {}", start, len);
+        // 映射后的内存不能超出RLIMIT_AS 匿名映射不能超出RLIMIT_DATA
+        if process.rlimits[RLIMIT_AS].exceeded(mm.get_mapped_size() + len)
+            || (fd == FD_NULL && process.rlimits[RLIMIT_DATA].exceeded(mm.get_data_size() + len)) {
+            drop(mm);
+            drop(process);
+            inner.context.x[10] = ENOMEM;
+            return Ok(());
+        }
         let start = if start == 0 {
-            let latest_addr = process.mem_set.get_last_addr();
+            let latest_addr = mm.mem_set.get_last_addr();
             if latest_addr < 0xd000_0000 {
                 0xd000_0000
             } else {
@@ -57,39 +82,40 @@ impl Task {
             debug!("free pae: {:#x}  start_page: {:#x} end_page: {:#x}", get_free_page_num(), start_page, end_page);
             let mem_map = MemMap::new(start_page.into(), 1, PTEFlags::UVRWX)?;
             for i in start_page..end_page {
-                process.pmm.add_mapping(mem_map.ppn, i.into(), PTEFlags::UVRWX)?;
+                mm.pmm.add_mapping(mem_map.ppn, i.into(), PTEFlags::UVRWX)?;
             }
-            process.mem_set.0.push(mem_map);
+            mm.mem_set.0.push(mem_map);
+            drop(mm);
             drop(process);
             inner.context.x[10] = start;
             return Ok(());
         }
         debug!("mmap start: {:#x}, len: {:#x}, prot: {}, flags: {}, fd: {:#x}, offset: {:#x}", start, len, _prot, flags, fd, offset);
         let flags = MapFlags::from_bits_truncate(flags as u32);
-        let mut p_start = process.pmm.get_phys_addr(start.into())?;
+        let mut p_start = mm.pmm.get_phys_addr(start.into())?;
         debug!("申请: {:#x}", p_start.0);
         if p_start.0 < 0x8000_0000 {
             let page_num = len / PAGE_SIZE;
             let mem_map = MemMap::new(VirtAddr::from(start).into(), page_num, PTEFlags::UVRWX)?;
             p_start = mem_map.ppn.into();
-            process.pmm.add_mapping_by_map(&mem_map)?;
+            mm.pmm.add_mapping_by_map(&mem_map)?;
 
             // let parent = process.parent.clone();
             // if let Some(parent) = parent.map_or(None, |x| x.upgrade()) {
-            //     let mut parent = parent.borrow_mut();
+            //     let mut parent = parent.write();
             //     parent.pmm.add_mapping_by_map(&mem_map)?;
             //     parent.mem_set.0.push(mem_map.clone());
 
             //     let parent = parent.parent.clone();
             //     if let Some(parent) = parent.map_or(None, |x| x.upgrade()) {
-            //         let mut parent = parent.borrow_mut();
+            //         let mut parent = parent.write();
             //         parent.pmm.add_mapping_by_map(&mem_map)?;
             //         parent.mem_set.0.push(mem_map.clone());
 
             //     }
 
             // }
-            process.mem_set.0.push(mem_map);
+            mm.mem_set.0.push(mem_map);
         }
         let buf = get_buf_from_phys_addr(p_start, len);
 
@@ -99,30 +125,57 @@ impl Task {
         if fd == FD_NULL {
             todo!()
         } else if fd == FD_RANDOM {
+            drop(mm);
             drop(process);
             inner.context.x[10] = start;
             Ok(())
         } else {
-            let file = process.fd_table.get_file(fd)?;
+            let file = process.fd_table.read().get_file(fd)?;
             debug!("file size: {:#x}", file.get_size());
             file.copy_to(offset, buf);
+            drop(mm);
             drop(process);
             inner.context.x[10] = start;
             Ok(())
         }
     }
 
-    pub fn sys_mprotect(&self, _addr: usize, _len: usize, _prot: usize) -> Result<(), RuntimeError> {
-        debug!("保护页面: {:#x}  len: {:#x}", _addr, _len);
-        let mut inner = self.inner.borrow_mut();
-        inner.context.x[10] = 0;
+    pub fn sys_mprotect(&self, addr: usize, len: usize, prot: usize) -> Result<(), RuntimeError> {
+        debug!("保护页面: {:#x}  len: {:#x}", addr, len);
+        let mut inner = self.inner.write();
+        // 起始地址需要页对齐
+        if addr % PAGE_SIZE != 0 {
+            inner.context.x[10] = EINVAL;
+            return Ok(());
+        }
+        let prot = match ProtFlags::from_bits(prot as u32) {
+            Some(prot) => prot,
+            None => {
+                inner.context.x[10] = EINVAL;
+                return Ok(());
+            }
+        };
+        let process = self.process.write();
+        // 修改页表项 并刷新所有核心的TLB 范围内存在没有映射的页面时返回ENOMEM
+        let result = process.mm.read().pmm.protect_mapping_range(addr.into(), len, prot.to_pte_flags());
+        drop(process);
+        inner.context.x[10] = if result.is_ok() { 0 } else { ENOMEM };
         Ok(())
     }
 
-    pub fn sys_munmap(&self, start: usize, _len: usize) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.borrow_mut();
-        process.pmm.remove_mapping(start.into());
+    pub fn sys_munmap(&self, start: usize, len: usize) -> Result<(), RuntimeError> {
+        let mut inner = self.inner.write();
+        if start % PAGE_SIZE != 0 || len == 0 {
+            inner.context.x[10] = EINVAL;
+            return Ok(());
+        }
+        let process = self.process.write();
+        // 删除范围内所有页面的映射 并刷新所有核心的TLB
+        let mut mm = process.mm.write();
+        mm.pmm.remove_mapping_range(start.into(), len);
+        // 删除或者拆分范围内的内存映射 释放范围内的物理页
+        mm.mem_set.remove_range(start / PAGE_SIZE, (start + len + PAGE_SIZE - 1) / PAGE_SIZE);
+        drop(mm);
         drop(process);
         inner.context.x[10] = 0;
         Ok(())
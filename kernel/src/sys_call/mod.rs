@@ -6,13 +6,17 @@ use riscv::register::scause::Interrupt;
 use riscv::register::stval;
 use riscv::register::sstatus;
 use crate::interrupt::timer;
+use crate::cpu::handle_cross_call;
 use crate::sync::mutex::Mutex;
 use crate::sys_call::consts::ENOENT;
 use crate::task::task_scheduler::kill_task;
 use crate::sys_call::consts::EBADF;
+use crate::sys_call::consts::EMFILE;
+use crate::sys_call::consts::ENOEXEC;
+use crate::task::rlimit::RLIMIT_STACK;
+use crate::task::signal::Signal;
 use crate::interrupt::timer::set_last_ticks;
 use crate::runtime_err::RuntimeError;
-use crate::task::signal::SignalUserContext;
 use crate::task::task::Task;
 use crate::task::task_scheduler::switch_next;
 
@@ -34,7 +38,12 @@ pub const SYS_UNLINKAT:usize= 35;
 pub const SYS_UMOUNT2: usize= 39;
 pub const SYS_MOUNT: usize  = 40;
 pub const SYS_STATFS: usize = 43;
+pub const SYS_FSTATFS: usize = 44;
 pub const SYS_CHDIR: usize  = 49;
+pub const SYS_FCHMOD: usize = 52;
+pub const SYS_FCHMODAT: usize = 53;
+pub const SYS_FCHOWNAT: usize = 54;
+pub const SYS_FCHOWN: usize = 55;
 pub const SYS_OPENAT:usize  = 56;
 pub const SYS_CLOSE: usize  = 57;
 pub const SYS_PIPE2: usize  = 59;
@@ -45,6 +54,9 @@ pub const SYS_WRITE: usize  = 64;
 pub const SYS_READV:  usize  = 65;
 pub const SYS_WRITEV: usize = 66;
 pub const SYS_PREAD: usize  = 67;
+pub const SYS_PWRITE: usize = 68;
+pub const SYS_PREADV: usize = 69;
+pub const SYS_PWRITEV: usize = 70;
 pub const SYS_SENDFILE: usize = 71;
 pub const SYS_PPOLL: usize = 73;
 pub const SYS_READLINKAT: usize = 78;
@@ -53,27 +65,60 @@ pub const SYS_FSTAT: usize  = 80;
 pub const SYS_UTIMEAT:usize = 88;
 pub const SYS_EXIT:  usize  = 93;
 pub const SYS_EXIT_GROUP: usize = 94;
+pub const SYS_WAITID: usize = 95;
 pub const SYS_SET_TID_ADDRESS: usize = 96;
 pub const SYS_FUTEX: usize  = 98;
+pub const SYS_SET_ROBUST_LIST: usize = 99;
+pub const SYS_GET_ROBUST_LIST: usize = 100;
 pub const SYS_NANOSLEEP: usize = 101;
+pub const SYS_GETITIMER: usize = 102;
+pub const SYS_SETITIMER: usize = 103;
 pub const SYS_GETTIME: usize = 113;
+pub const SYS_CLOCK_NANOSLEEP: usize = 115;
+pub const SYS_SCHED_SETPARAM: usize = 118;
+pub const SYS_SCHED_SETSCHEDULER: usize = 119;
+pub const SYS_SCHED_GETSCHEDULER: usize = 120;
+pub const SYS_SCHED_GETPARAM: usize = 121;
 pub const SYS_SCHED_YIELD: usize = 124;
+pub const SYS_SCHED_GET_PRIORITY_MAX: usize = 125;
+pub const SYS_SCHED_GET_PRIORITY_MIN: usize = 126;
 pub const SYS_KILL: usize = 129;
 pub const SYS_TKILL: usize = 130;
 pub const SYS_TGKILL: usize = 131;
+pub const SYS_SIGALTSTACK: usize = 132;
 pub const SYS_SIGACTION: usize = 134;
 pub const SYS_SIGPROCMASK: usize = 135;
+pub const SYS_SIGPENDING: usize = 136;
 pub const SYS_SIGTIMEDWAIT: usize = 137;
 pub const SYS_SIGRETURN: usize = 139;
+pub const SYS_SETPRIORITY: usize = 140;
+pub const SYS_GETPRIORITY: usize = 141;
+pub const SYS_SETREGID: usize = 143;
+pub const SYS_SETGID: usize = 144;
+pub const SYS_SETREUID: usize = 145;
+pub const SYS_SETUID: usize = 146;
+pub const SYS_SETRESUID: usize = 147;
+pub const SYS_GETRESUID: usize = 148;
+pub const SYS_SETRESGID: usize = 149;
+pub const SYS_GETRESGID: usize = 150;
 pub const SYS_TIMES: usize  = 153;
+pub const SYS_SETPGID: usize = 154;
+pub const SYS_GETPGID: usize = 155;
+pub const SYS_GETSID: usize = 156;
+pub const SYS_SETSID: usize = 157;
+pub const SYS_GETGROUPS: usize = 158;
+pub const SYS_SETGROUPS: usize = 159;
 pub const SYS_UNAME: usize  = 160;
+pub const SYS_GETRLIMIT: usize = 163;
+pub const SYS_SETRLIMIT: usize = 164;
 pub const SYS_GETRUSAGE: usize = 165;
 pub const SYS_GETTIMEOFDAY: usize= 169;
 pub const SYS_GETPID:usize  = 172;
 pub const SYS_GETPPID:usize = 173;
 pub const SYS_GETUID: usize = 174;
-// pub const SYS_GETEUID: usize = 175;
+pub const SYS_GETEUID: usize = 175;
 pub const SYS_GETGID: usize = 176;
+pub const SYS_GETEGID: usize = 177;
 pub const SYS_GETTID: usize = 178;
 pub const SYS_SOCKET: usize = 198;
 pub const SYS_BIND: usize   = 200;
@@ -90,6 +135,8 @@ pub const SYS_MMAP: usize   = 222;
 pub const SYS_MPROTECT:usize= 226;
 pub const SYS_MUNMAP:usize  = 215;
 pub const SYS_WAIT4: usize  = 260;
+pub const SYS_PRLIMIT64: usize = 261;
+pub const SYS_STATX: usize  = 291;
 
 // 系统调用错误码
 pub const SYS_CALL_ERR: usize = -1 as isize as usize;
@@ -102,8 +149,21 @@ bitflags! {
         const WRONLY = 1 << 0;
         const RDWR = 1 << 1;
         const CREATE = 1 << 6;
-        const TRUNC = 1 << 10;
-        const O_DIRECTORY = 1 << 21;
+        const EXCL = 1 << 7;
+        const NOCTTY = 1 << 8;
+        const TRUNC = 1 << 9;
+        const APPEND = 1 << 10;
+        const NONBLOCK = 1 << 11;
+        const DSYNC = 1 << 12;
+        const ASYNC = 1 << 13;
+        const DIRECT = 1 << 14;
+        const LARGEFILE = 1 << 15;
+        const O_DIRECTORY = 1 << 16;
+        const NOFOLLOW = 1 << 17;
+        const NOATIME = 1 << 18;
+        const CLOEXEC = 1 << 19;
+        const PATH = 1 << 21;
+        const TMPFILE = 1 << 22 | Self::O_DIRECTORY.bits;
     }
 
     pub struct SignalFlag: usize {
@@ -114,6 +174,15 @@ bitflags! {
         const SA_NODEFER   = 0x40000000;
         const SA_RESETHAND = 0x80000000;
         const SA_RESTORER  = 0x04000000;
+        const SA_ONSTACK   = 0x08000000;
+    }
+
+    pub struct WaitOptions: usize {
+        const WNOHANG    = 0x1;
+        const WUNTRACED  = 0x2;         // 与WSTOPPED相同
+        const WEXITED    = 0x4;
+        const WCONTINUED = 0x8;
+        const WNOWAIT    = 0x01000000;
     }
 
     pub struct CloneFlags: usize {
@@ -145,6 +214,23 @@ bitflags! {
     }
 }
 
+impl OpenFlags {
+    // 访问模式是否有效 O_WRONLY和O_RDWR不能同时设置
+    pub fn valid_access(&self) -> bool {
+        !self.contains(Self::WRONLY | Self::RDWR)
+    }
+
+    // 是否可以读取 O_PATH打开的文件无法读写
+    pub fn readable(&self) -> bool {
+        !self.intersects(Self::WRONLY | Self::PATH)
+    }
+
+    // 是否可以写入
+    pub fn writeable(&self) -> bool {
+        self.intersects(Self::WRONLY | Self::RDWR) && !self.contains(Self::PATH)
+    }
+}
+
 // 系统信息结构
 pub struct UTSname  {
     sysname: [u8;65],
@@ -176,7 +262,7 @@ impl Task {
             // 复制文件描述符
             SYS_DUP => self.sys_dup(args[0]),
             // 复制文件描述符
-            SYS_DUP3 => self.sys_dup3(args[0], args[1]),
+            SYS_DUP3 => self.sys_dup3(args[0], args[1], args[2]),
             // 控制资源
             SYS_FCNTL => self.sys_fcntl(args[0], args[1], args[2]),
             // 创建文件夹
@@ -188,7 +274,9 @@ impl Task {
             // mount设备
             SYS_MOUNT => Ok(()),
             // 获取文件系统信息
-            SYS_STATFS => self.sys_statfs(args[0], args[1].into()),
+            SYS_STATFS => self.sys_statfs(args[0].into(), args[1].into()),
+            // 获取文件描述符所在文件系统的信息
+            SYS_FSTATFS => self.sys_fstatfs(args[0], args[1].into()),
             // 改变文件信息
             SYS_CHDIR => self.sys_chdir(args[0].into()),
             // 打开文件地址
@@ -196,7 +284,7 @@ impl Task {
             // 关闭文件描述符
             SYS_CLOSE => self.sys_close(args[0]),
             // 进行PIPE
-            SYS_PIPE2 => self.sys_pipe2(args[0].into()),
+            SYS_PIPE2 => self.sys_pipe2(args[0].into(), args[1]),
             // 获取文件节点
             SYS_GETDENTS => self.sys_getdents(args[0], args[1].into(), args[2]),
             // 移动读取位置
@@ -211,6 +299,12 @@ impl Task {
             SYS_WRITEV => self.sys_writev(args[0], args[1].into(), args[2]),
             // 读取数据
             SYS_PREAD => self.sys_pread(args[0], args[1].into(), args[2], args[3]),
+            // 写入数据
+            SYS_PWRITE => self.sys_pwrite(args[0], args[1].into(), args[2], args[3]),
+            // 读取数据
+            SYS_PREADV => self.sys_preadv(args[0], args[1].into(), args[2], args[3]),
+            // 写入数据
+            SYS_PWRITEV => self.sys_pwritev(args[0], args[1].into(), args[2], args[3]),
             // 发送文件
             SYS_SENDFILE => self.sys_sendfile(args[0], args[1], args[2], args[3]),
             // 等待ppoll
@@ -221,35 +315,83 @@ impl Task {
             SYS_FSTATAT => self.sys_fstatat(args[0], args[1].into(), args[2].into(), args[3]),
             // 获取文件数据信息
             SYS_FSTAT => self.sys_fstat(args[0], args[1].into()),
+            // 修改权限和所有者
+            SYS_FCHMOD => self.sys_fchmod(args[0], args[1]),
+            SYS_FCHMODAT => self.sys_fchmodat(args[0], args[1].into(), args[2], args[3]),
+            SYS_FCHOWNAT => self.sys_fchownat(args[0], args[1].into(), args[2], args[3], args[4]),
+            SYS_FCHOWN => self.sys_fchown(args[0], args[1], args[2]),
+            // 获取文件扩展信息
+            SYS_STATX => self.sys_statx(args[0], args[1].into(), args[2], args[3], args[4].into()),
             // 改变文件时间
             SYS_UTIMEAT => self.sys_utimeat(args[0], args[1].into(), args[2].into(), args[3]),
             // 退出文件信息
             SYS_EXIT => self.sys_exit(args[0]),
             // 退出组
             SYS_EXIT_GROUP => self.sys_exit_group(args[0]),
+            // 等待子进程状态改变
+            SYS_WAITID => self.sys_waitid(args[0], args[1], args[2].into(), args[3], args[4].into()),
             // 设置tid
             SYS_SET_TID_ADDRESS => self.sys_set_tid_address(args[0].into()),
             // 互斥锁
-            SYS_FUTEX => self.sys_futex(args[0].into(), args[1] as u32, args[2] as _, args[3], args[4]),
+            SYS_FUTEX => self.sys_futex(args[0].into(), args[1] as u32, args[2] as _, args[3], args[4].into(), args[5] as u32),
+            // 设置robust futex链表
+            SYS_SET_ROBUST_LIST => self.sys_set_robust_list(args[0].into(), args[1]),
+            // 获取robust futex链表
+            SYS_GET_ROBUST_LIST => self.sys_get_robust_list(args[0], args[1].into(), args[2].into()),
             // 文件休眠
             SYS_NANOSLEEP => self.sys_nanosleep(args[0].into(), args[1].into()),
+            // 获取间隔定时器
+            SYS_GETITIMER => self.sys_getitimer(args[0], args[1].into()),
+            // 设置间隔定时器
+            SYS_SETITIMER => self.sys_setitimer(args[0], args[1].into(), args[2].into()),
+            // 指定时钟休眠
+            SYS_CLOCK_NANOSLEEP => self.sys_clock_nanosleep(args[0], args[1], args[2].into(), args[3].into()),
             // 获取系统时间
             SYS_GETTIME => self.sys_gettime(args[0], args[1].into()),
             // 转移文件权限
             SYS_SCHED_YIELD => self.sys_sched_yield(),
+            // 设置调度参数
+            SYS_SCHED_SETPARAM => self.sys_sched_setparam(args[0], args[1].into()),
+            // 设置调度策略
+            SYS_SCHED_SETSCHEDULER => self.sys_sched_setscheduler(args[0], args[1], args[2].into()),
+            // 获取调度策略
+            SYS_SCHED_GETSCHEDULER => self.sys_sched_getscheduler(args[0]),
+            // 获取调度参数
+            SYS_SCHED_GETPARAM => self.sys_sched_getparam(args[0], args[1].into()),
+            // 获取最大优先级
+            SYS_SCHED_GET_PRIORITY_MAX => self.sys_sched_get_priority_max(args[0]),
+            // 获取最小优先级
+            SYS_SCHED_GET_PRIORITY_MIN => self.sys_sched_get_priority_min(args[0]),
+            // 设置nice值
+            SYS_SETPRIORITY => self.sys_setpriority(args[0], args[1], args[2]),
+            // 获取nice值
+            SYS_GETPRIORITY => self.sys_getpriority(args[0], args[1]),
+            // 用户和组
+            SYS_SETREGID => self.sys_setregid(args[0], args[1]),
+            SYS_SETGID => self.sys_setgid(args[0]),
+            SYS_SETREUID => self.sys_setreuid(args[0], args[1]),
+            SYS_SETUID => self.sys_setuid(args[0]),
+            SYS_SETRESUID => self.sys_setresuid(args[0], args[1], args[2]),
+            SYS_GETRESUID => self.sys_getresuid(args[0].into(), args[1].into(), args[2].into()),
+            SYS_SETRESGID => self.sys_setresgid(args[0], args[1], args[2]),
+            SYS_GETRESGID => self.sys_getresgid(args[0].into(), args[1].into(), args[2].into()),
             // 结束进程
             SYS_KILL => self.sys_kill(args[0], args[1]),
             // 结束任务进程
             SYS_TKILL => self.sys_tkill(args[0], args[1]),
             // 结束进程
             SYS_TGKILL => self.sys_tgkill(args[0], args[1], args[2]),
+            // 设置备用信号栈
+            SYS_SIGALTSTACK => self.sys_sigaltstack(args[0].into(), args[1].into()),
             // 释放sigacrtion
             SYS_SIGACTION => self.sys_sigaction(args[0], args[1].into(),args[2].into(), args[3]),
             // 遮盖信号
             SYS_SIGPROCMASK => self.sys_sigprocmask(args[0] as _, args[1].into(),args[2].into(), args[3] as _),
+            // 获取待处理的信号
+            SYS_SIGPENDING => self.sys_sigpending(args[0].into()),
             //
             // SYS_SIGTIMEDWAIT => {
-            //     let mut inner = self.inner.borrow_mut();
+            //     let mut inner = self.inner.write();
             //     inner.context.x[10] = 0;
             //     Ok(())
             // }
@@ -257,10 +399,24 @@ impl Task {
             SYS_SIGRETURN => self.sys_sigreturn(),
             // 获取文件时间
             SYS_TIMES => self.sys_times(args[0]),
+            // 设置进程组
+            SYS_SETPGID => self.sys_setpgid(args[0], args[1]),
+            // 获取进程组
+            SYS_GETPGID => self.sys_getpgid(args[0]),
+            // 获取会话
+            SYS_GETSID => self.sys_getsid(args[0]),
+            // 创建会话
+            SYS_SETSID => self.sys_setsid(),
+            SYS_GETGROUPS => self.sys_getgroups(args[0], args[1].into()),
+            SYS_SETGROUPS => self.sys_setgroups(args[0], args[1].into()),
             // 获取系统信息
             SYS_UNAME => self.sys_uname(args[0].into()),
             // 获取任务获取信息
+            SYS_GETRLIMIT => self.sys_getrlimit(args[0], args[1].into()),
+            SYS_SETRLIMIT => self.sys_setrlimit(args[0], args[1].into()),
             SYS_GETRUSAGE => self.sys_getrusage(args[0], args[1].into()),
+            // 获取并设置资源限制
+            SYS_PRLIMIT64 => self.sys_prlimit64(args[0], args[1], args[2].into(), args[3].into()),
             // 获取时间信息
             SYS_GETTIMEOFDAY => self.sys_gettimeofday(args[0]),
             // 获取进程信息
@@ -268,15 +424,11 @@ impl Task {
             // 获取进程父进程
             SYS_GETPPID => self.sys_getppid(),
             // 获取uid
-            SYS_GETUID => {
-                self.update_context(|x| x.x[10] = 1);
-                Ok(())
-            },
+            SYS_GETUID => self.sys_getuid(),
+            SYS_GETEUID => self.sys_geteuid(),
             // 获取gid
-            SYS_GETGID => {
-                self.update_context(|x| x.x[10] = 1);
-                Ok(())
-            },
+            SYS_GETGID => self.sys_getgid(),
+            SYS_GETEGID => self.sys_getegid(),
             // 获取tid
             SYS_GETTID => self.sys_gettid(),
             // 申请socket
@@ -308,7 +460,7 @@ impl Task {
             // 取消文件映射
             SYS_MUNMAP => self.sys_munmap(args[0], args[1]),
             // 等待进程
-            SYS_WAIT4 => self.sys_wait4(args[0], args[1].into(), args[2]),
+            SYS_WAIT4 => self.sys_wait4(args[0], args[1].into(), args[2], args[3].into()),
             _ => {
                 warn!("未识别调用号 {}", call_type);
                 Ok(())
@@ -327,20 +479,30 @@ impl Task {
                     panic!("No Enough Page");
                 }
                 RuntimeError::NoMatchedFileDesc => {
-                    let mut inner = self.inner.borrow_mut();
+                    let mut inner = self.inner.write();
                     warn!("未找到匹配的文件描述符");
                     inner.context.x[10] = SYS_CALL_ERR;
                 }
                 RuntimeError::FileNotFound => {
-                    let mut inner = self.inner.borrow_mut();
+                    let mut inner = self.inner.write();
                     warn!("文件未找到");
                     inner.context.x[10] = ENOENT;
                 }
                 RuntimeError::EBADF => {
-                    let mut inner = self.inner.borrow_mut();
+                    let mut inner = self.inner.write();
                     warn!("文件未找到  EBADF");
                     inner.context.x[10] = EBADF;
                 }
+                RuntimeError::EMFILE => {
+                    let mut inner = self.inner.write();
+                    warn!("打开的文件过多  EMFILE");
+                    inner.context.x[10] = EMFILE;
+                }
+                RuntimeError::ENOEXEC => {
+                    let mut inner = self.inner.write();
+                    warn!("无法执行的文件格式  ENOEXEC");
+                    inner.context.x[10] = ENOEXEC;
+                }
                 // 统一处理任务切换
                 RuntimeError::ChangeTask => switch_next(),
                 _ => {
@@ -350,58 +512,21 @@ impl Task {
         }
     }
 
-    pub fn signal(&self, signal: usize) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
-
-        process.pmm.change_satp();
-        
-        let sig_action = process.sig_actions[signal];
-
-        let handler = sig_action.handler;
-        // 如果没有处理器
-        if handler == 0 {
-            return Ok(());
-        }
-        // 保存上下文
-        let mut temp_context = inner.context.clone();
-        let pmm = process.pmm.clone();
-        // 获取临时页表 对数据进行处理
-        let ucontext = process.heap.get_temp(pmm)?.tranfer::<SignalUserContext>();
-        // 中断正在处理中
-        if ucontext.context.x[0] != 0 {
-            return Ok(());
-        }
-        let restorer = sig_action.restorer;
-        let _flags = SignalFlag::from_bits_truncate(sig_action.flags);
-        
-        drop(process);
-        inner.context.sepc = handler;
-        inner.context.x[1] = restorer;
-        inner.context.x[10] = signal;
-        inner.context.x[11] = 0;
-        inner.context.x[12] = 0xe0000000;
-        ucontext.context.clone_from(&temp_context);
-        ucontext.context.x[0] = ucontext.context.sepc;
-        drop(inner);
-
-        debug!("handle signal: {}  handler: {:#x}", signal, handler);
-        loop {
-            self.run();
-            if let Err(RuntimeError::SigReturn) = self.interrupt() {
-                break;
+    // 处理用户态的缺页 用户栈中没有映射的地址扩展用户栈 其他情况发送SIGSEGV
+    fn handle_page_fault(&self, stval: usize) -> Result<(), RuntimeError> {
+        let process = self.get_process();
+        let process = process.read();
+        let mut mm = process.mm.write();
+        if stval > 0xef00_0000 && stval < mm.stack.top {
+            let limit = process.rlimits[RLIMIT_STACK].rlim_cur;
+            // 栈大小超出RLIMIT_STACK时发送SIGSEGV
+            if mm.stack.alloc_until(stval, limit).is_ok() {
+                return Ok(());
             }
         }
-        // 修改回调地址
-        temp_context.sepc = ucontext.context.x[0];
-
-        // 恢复上下文 并 移除临时页
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.borrow_mut();
-        process.heap.release_temp();
+        drop(mm);
         drop(process);
-        inner.context.clone_from(&temp_context);
-        Ok(())
+        self.force_signal(Signal::SIGSEGV as usize)
     }
 
     pub fn interrupt(&self) -> Result<(), RuntimeError> {
@@ -410,7 +535,7 @@ impl Task {
         }
         let scause = scause::read();
         let stval = stval::read();
-        let mut task_inner = self.inner.borrow_mut();
+        let mut task_inner = self.inner.write();
         let context = &mut task_inner.context;
         // warn!("中断发生: {:#x}, 地址: {:#x}", scause.bits(), context.sepc);
         // 更新TICKS
@@ -425,19 +550,23 @@ impl Task {
             },
             // 时钟中断
             Trap::Interrupt(Interrupt::SupervisorTimer) => {
-                timer::timer_handler();
+                // 时间片用完 切换到下一个任务
+                if timer::timer_handler() {
+                    return Err(RuntimeError::ChangeTask);
+                }
             },
-            // 页处理错误
-            Trap::Exception(Exception::StorePageFault) | Trap::Exception(Exception::StoreFault) => {
-                error!("缺页中断触发 缺页地址: {:#x} 触发地址:{:#x} 已同步映射", stval, context.sepc);
+            // 核间中断 执行其他核心发来的请求
+            Trap::Interrupt(Interrupt::SupervisorSoft) => {
+                handle_cross_call();
+            },
+            // 页处理错误 用户栈以外的地址或者没有访问权限时发送SIGSEGV
+            Trap::Exception(Exception::StorePageFault) | Trap::Exception(Exception::StoreFault)
+            | Trap::Exception(Exception::LoadPageFault) | Trap::Exception(Exception::LoadFault)
+            | Trap::Exception(Exception::InstructionPageFault) | Trap::Exception(Exception::InstructionFault) => {
+                warn!("缺页中断触发 缺页地址: {:#x} 触发地址:{:#x}", stval, context.sepc);
                 drop(context);
-                if stval > 0xef00_0000 && stval < 0xf00010000 {
-                    error!("处理缺页中断;");
-                    let mut process = task_inner.process.borrow_mut();
-                    process.stack.alloc_until(stval)?;
-                } else {
-                    panic!("无法 恢复的缺页中断");
-                }
+                drop(task_inner);
+                self.handle_page_fault(stval)?;
             },
             // 用户请求
             Trap::Exception(Exception::UserEnvCall) => {
@@ -460,22 +589,17 @@ impl Task {
 
                 self.sys_call(call_type, args)?;
             },
-            // 加载页面错误
-            Trap::Exception(Exception::LoadPageFault) => {
-                panic!("加载权限异常 地址:{:#x} 调用地址: {:#x}", stval, context.sepc)
-            },
-            // 页面未对齐错误
+            // 页面未对齐错误 发送SIGBUS
             Trap::Exception(Exception::StoreMisaligned) => {
-                warn!("页面未对齐");
+                warn!("页面未对齐 地址 {:#x} stval: {:#x}", context.sepc, stval);
+                drop(task_inner);
+                self.force_signal(Signal::SIGBUS as usize)?;
             }
+            // 非法指令 发送SIGILL
             Trap::Exception(Exception::IllegalInstruction) => {
                 warn!("中断 {:#x} 地址 {:#x} stval: {:#x}", scause.bits(), context.sepc, stval);
-                // panic!("指令页错误");
-
-            }
-            Trap::Exception(Exception::InstructionPageFault) => {
-                warn!("中断 {:#x} 地址 {:#x} stval: {:#x}", scause.bits(), context.sepc, stval);
-                panic!("指令页错误");
+                drop(task_inner);
+                self.force_signal(Signal::SIGILL as usize)?;
             }
             // 其他情况，终止当前线程
             _ => {
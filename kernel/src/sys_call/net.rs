@@ -1,9 +1,9 @@
 
 use alloc::collections::VecDeque;
-use alloc::rc::Rc;
-use core::cell::RefCell;
-use crate::fs::file::FileOP;
-use crate::fs::file::fcntl_cmd;
+use alloc::sync::Arc;
+use crate::sync::rwlock::RwLock;
+use crate::fs::file::{FileOP, Kstat, DEV_SOCK, S_IFSOCK};
+use crate::fs::filetree::alloc_ino;
 use crate::memory::addr::UserAddr;
 use crate::runtime_err::RuntimeError;
 use crate::task::fd_table::FileDesc;
@@ -17,17 +17,17 @@ pub struct SocketAddr {
     sa_data: [u8; 14],
 }
 
-pub struct SocketFile(RefCell<VecDeque<u8>>);
+pub struct SocketFile(RwLock<VecDeque<u8>>, u64);
 
 impl SocketFile {
-    fn new() -> Rc<Self> {
-        Rc::new(SocketFile(RefCell::new(VecDeque::new())))
+    fn new() -> Arc<Self> {
+        Arc::new(SocketFile(RwLock::new(VecDeque::new()), alloc_ino()))
     }
 }
 
 impl FileOP for SocketFile {
     fn readable(&self) -> bool {
-        todo!()
+        true
     }
 
     fn writeable(&self) -> bool {
@@ -36,7 +36,7 @@ impl FileOP for SocketFile {
 
     fn read_at(&self, _pos: usize, buf: &mut [u8]) -> usize {
         let mut read_index = 0;
-        let mut queue = self.0.borrow_mut();
+        let mut queue = self.0.write();
         loop {
             if read_index >= buf.len() {
                 break;
@@ -55,7 +55,7 @@ impl FileOP for SocketFile {
 
     fn write_at(&self, _pos: usize, buf: &[u8], count: usize) -> usize {
         let mut write_index = 0;
-        let mut queue = self.0.borrow_mut();
+        let mut queue = self.0.write();
         loop {
             if write_index >= buf.len() || write_index >= count {
                 break;
@@ -68,36 +68,40 @@ impl FileOP for SocketFile {
     }
 
     fn get_size(&self) -> usize {
-        self.0.borrow().len()
+        self.0.read().len()
+    }
+
+    fn stat(&self) -> Kstat {
+        Kstat::new(DEV_SOCK, self.1, S_IFSOCK | 0o777, 0)
     }
 }
 
 impl Task {
     pub fn sys_socket(&self, _domain: usize, _ty: usize, _protocol: usize) -> Result<(), RuntimeError> {
         let file = SocketFile::new();
-        let mut inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
 
-        let fd = process.fd_table.push_sock(FileDesc::new(file));
+        let fd = process.fd_table.write().push_sock(FileDesc::new(file), process.nofile_limit())?;
         drop(process);
         inner.context.x[10] = fd;
         Ok(())
     }
 
     pub fn sys_bind(&self) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.write();
         inner.context.x[10] = 0;
         Ok(())
     }
 
     pub fn sys_getsockname(&self) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.write();
         inner.context.x[10] = 0;
         Ok(())
     }
 
     pub fn sys_setsockopt(&self) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.write();
         inner.context.x[10] = 0;
         Ok(())
     }
@@ -105,11 +109,11 @@ impl Task {
     pub fn sys_sendto(&self, _fd: usize, _buf: UserAddr<u8>, _len: usize, _flags: usize,
                             _sa: UserAddr<SocketAddr>, _sa_size: usize) -> Result<(), RuntimeError> {
         // let sa = sa.transfer();
-        // let mut inner = self.inner.borrow_mut();
-        // let process = inner.process.borrow_mut();
+        // let mut inner = self.inner.write();
+        // let process = self.process.write();
         // let buf = buf.transfer_vec(len);
 
-        // let file = process.fd_table.get(fd)?;
+        // let file = process.fd_table.read().get(fd)?;
 
         // let send_size = file.write(buf, buf.len());
         // SOCKET_BUF.lock().socket_buf.insert(sa.clone(), file);
@@ -123,7 +127,7 @@ impl Task {
         _sa: UserAddr<SocketAddr>, _addr_len: usize) -> Result<(), RuntimeError> {
 
         // let sa = sa.transfer();
-        // let mut inner = self.inner.borrow_mut();
+        // let mut inner = self.inner.write();
         // let buf = buf.transfer_vec(len);
 
         // let file = SOCKET_BUF.lock().socket_buf.get(sa).unwrap().clone();
@@ -134,50 +138,20 @@ impl Task {
     }
 
     pub fn sys_listen(&self) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.write();
         inner.context.x[10] = 0;
         Ok(())
     }
 
     pub fn sys_connect(&self) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.write();
         inner.context.x[10] = 0;
         Ok(())
     }
 
     pub fn sys_accept(&self) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.write();
         inner.context.x[10] = 0;
         Ok(())
     }
-
-    pub fn sys_fcntl(&self, fd: usize, cmd: usize, _arg: usize) -> Result<(), RuntimeError> {
-        debug!("val: fd {}  cmd {:#x} arg {:#x}", fd, cmd, _arg);
-        // let mut inner = self.inner.borrow_mut();
-        // let node = self.map.get_mut(&fd).ok_or(SysError::EBADF)?;
-        if fd >= 50 {
-            // 暂时注释掉 后面使用socket
-            // match cmd {
-            //     // 复制文件描述符
-            //     1 => {
-            //         inner.context.x[10] = 1;
-            //     }
-            //     3 => {
-            //         inner.context.x[10] = 0o4000;
-            //     },
-            //     _n => {
-            //         debug!("not imple {}", _n);
-            //     },
-            // };
-        } else {
-            match cmd {
-                fcntl_cmd::DUPFD_CLOEXEC => {
-                    debug!("copy value");
-                    self.sys_dup(fd)?;
-                }
-                _ => {}
-            }
-        }
-        Ok(())
-    }
 }
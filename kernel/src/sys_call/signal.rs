@@ -1,14 +1,205 @@
+use core::arch::asm;
+use core::mem::size_of;
 use crate::memory::addr::UserAddr;
 use crate::task::task::Task;
+use crate::task::stack::DEFAULT_STACK_ADDR;
+use crate::task::futex::FUTEX_TABLE;
+use crate::task::process::signaled_status;
+use crate::task::task_scheduler::switch_next;
+use crate::task::task_scheduler::kick_task;
+use crate::task::futex::FutexState;
+use crate::task::rlimit::RLIMIT_STACK;
 use crate::task::signal::SigSet;
 use crate::task::signal::SigAction;
+use crate::task::signal::SigInfo;
+use crate::task::signal::Signal;
+use crate::task::signal::SignalFrame;
+use crate::task::signal::SignalStack;
+use crate::task::signal::SignalStackFlags;
+use crate::task::signal::{SignalDefault, default_action};
+use crate::task::signal::{NSIG, SIG_DFL, SIG_IGN, SI_USER, MINSIGSTKSZ, SIG_TRAMPOLINE};
 use crate::runtime_err::RuntimeError;
+use crate::sys_call::SignalFlag;
+use crate::sys_call::time::interrupt_sleep;
+use crate::sys_call::consts::{EINTR, EINVAL, ENOMEM, EPERM};
 
 impl Task {
+    // 向线程发送信号 线程没有屏蔽且没有忽略该信号时打断等待
+    pub fn signal(&self, signum: usize) -> Result<(), RuntimeError> {
+        let process = self.get_process();
+        let mut process = process.write();
+        process.prepare_signal(signum);
+        let ignored = process.is_ignored(signum);
+        drop(process);
+        let mut signal = self.signal.lock();
+        signal.sig_pending.add(signum);
+        let blocked = signal.sig_mask.contains(signum);
+        drop(signal);
+        // 被忽略的信号不打断等待
+        if !blocked && !ignored {
+            self.interrupt_wait();
+            kick_task(self);
+        }
+        Ok(())
+    }
+
+    // 发送由异常产生的信号 信号被屏蔽或忽略时恢复默认行为并解除屏蔽
+    // 否则返回用户态后会再次触发同样的异常
+    pub fn force_signal(&self, signum: usize) -> Result<(), RuntimeError> {
+        let process = self.get_process();
+        let process = process.read();
+        let mut signal = self.signal.lock();
+        let blocked = signal.sig_mask.contains(signum);
+        if blocked || process.sig_actions.read()[signum].handler == SIG_IGN {
+            process.sig_actions.write()[signum].handler = SIG_DFL;
+            signal.sig_mask.remove(signum);
+        }
+        drop(signal);
+        drop(process);
+        self.signal(signum)
+    }
+
+    // 返回用户态之前处理信号 每次处理一个没有被屏蔽的信号
+    // 默认行为结束或停止进程时当前任务不再运行
+    pub fn handle_signals(&self) {
+        let mut inner = self.inner.write();
+        let process = self.process.clone();
+        let mut process = process.write();
+
+        // 等待被打断 清除等待状态 默认重新执行系统调用
+        // 保留唤醒时间 没有执行处理函数时重新执行的睡眠仍在原来的时间唤醒
+        let mut interrupted = self.take_interrupted();
+        if interrupted {
+            let state = self.futex.lock().state;
+            if let FutexState::Waiting(key) = state {
+                FUTEX_TABLE.lock().remove(key, self.tid);
+                self.futex.lock().state = FutexState::None;
+            }
+            // 阻塞的写入已经完成部分时返回写入的长度 不再重新执行
+            let done = core::mem::take(&mut inner.io_done);
+            if done > 0 {
+                inner.context.x[10] = done;
+                inner.context.sepc += 4;
+                interrupted = false;
+            }
+        }
+
+        // 先处理发送给线程的信号 再处理发送给进程的信号
+        let mut signal = self.signal.lock();
+        let mask = signal.sig_mask;
+        let signum = if let Some(signum) = signal.sig_pending.first_unmasked(&mask) {
+            signal.sig_pending.remove(signum);
+            signum
+        } else if let Some(signum) = process.sig_pending.first_unmasked(&mask) {
+            process.sig_pending.remove(signum);
+            signum
+        } else {
+            return;
+        };
+        drop(signal);
+
+        let action = process.sig_actions.read()[signum];
+        if action.handler == SIG_IGN {
+            return;
+        }
+        // 没有处理函数时执行默认行为
+        if action.handler == SIG_DFL {
+            drop(process);
+            drop(inner);
+            match default_action(signum) {
+                SignalDefault::Terminate => self.exit_process(signaled_status(signum, false)),
+                SignalDefault::Core => self.exit_process(signaled_status(signum, true)),
+                SignalDefault::Stop => {
+                    self.get_process().write().stop(signum);
+                    switch_next();
+                }
+                // 继续运行在发送信号时已经处理
+                SignalDefault::Continue | SignalDefault::Ignore => {}
+            }
+            return;
+        }
+        let flags = SignalFlag::from_bits_truncate(action.flags);
+
+        // 被处理函数打断的睡眠总是返回EINTR 没有SA_RESTART时被打断的系统调用返回EINTR
+        if interrupted {
+            let wake_time = core::mem::take(&mut inner.wake_time);
+            if interrupt_sleep(&inner.context, wake_time) || !flags.contains(SignalFlag::SA_RESTART) {
+                inner.context.x[10] = EINTR;
+                inner.context.sepc += 4;
+            }
+        }
+
+        // 设置了SA_ONSTACK时使用备用信号栈 已经在备用栈上时继续使用当前栈
+        let sp = inner.context.x[2];
+        let stack = inner.sig_stack;
+        let sp = if flags.contains(SignalFlag::SA_ONSTACK)
+            && !stack.flags.contains(SignalStackFlags::DISABLE) && !stack.contains(sp) {
+            stack.sp + stack.size
+        } else {
+            sp
+        };
+        let frame_addr = (sp - size_of::<SignalFrame>()) & !0xf;
+        // 用户栈空间不足时扩展 超出RLIMIT_STACK时无法压入信号帧
+        // 与force_sigsegv相同 SIGSEGV恢复默认处理并结束进程
+        if frame_addr > 0xef00_0000 && frame_addr < DEFAULT_STACK_ADDR {
+            let limit = process.rlimits[RLIMIT_STACK].rlim_cur;
+            let result = process.mm.write().stack.alloc_until(frame_addr, limit);
+            if let Err(err) = result {
+                warn!("信号栈空间不足: {:?}", err);
+                process.sig_actions.write()[Signal::SIGSEGV as usize] = SigAction::empty();
+                drop(process);
+                drop(inner);
+                self.exit_process(signaled_status(Signal::SIGSEGV as usize, true));
+                return;
+            }
+        }
+        process.mm.read().pmm.change_satp();
+
+        // 保存上下文和信号掩码 x[0]的位置保存返回地址
+        let frame = UserAddr::<SignalFrame>::from(frame_addr).transfer();
+        frame.info = SigInfo::new(signum, SI_USER, 0);
+        frame.ucontext.flags = 0;
+        frame.ucontext.link = 0;
+        frame.ucontext.stack = stack;
+        frame.ucontext.sig_mask = mask;
+        frame.ucontext.save(&inner.context, &inner.fp);
+        frame.trampoline = SIG_TRAMPOLINE;
+        unsafe {
+            asm!("fence.i");
+        }
+
+        // 没有设置restorer时使用栈上的返回代码
+        let restorer = if flags.contains(SignalFlag::SA_RESTORER) && action.restorer != 0 {
+            action.restorer
+        } else {
+            frame.trampoline.as_ptr() as usize
+        };
+        debug!("handle signal: {}  handler: {:#x}  frame: {:#x}", signum, action.handler, frame_addr);
+        inner.context.sepc = action.handler;
+        inner.context.x[1] = restorer;
+        inner.context.x[2] = frame_addr;
+        inner.context.x[10] = signum;
+        inner.context.x[11] = &frame.info as *const SigInfo as usize;
+        inner.context.x[12] = &frame.ucontext as *const _ as usize;
+
+        // 处理期间屏蔽sa_mask 没有SA_NODEFER时屏蔽当前信号
+        let mut signal = self.signal.lock();
+        signal.sig_mask.block(&action.mask);
+        if !flags.contains(SignalFlag::SA_NODEFER) {
+            signal.sig_mask.add(signum);
+        }
+        signal.sig_mask.sanitize();
+        drop(signal);
+        // SA_RESETHAND 处理一次后恢复默认处理
+        if flags.contains(SignalFlag::SA_RESETHAND) {
+            process.sig_actions.write()[signum] = SigAction::empty();
+        }
+    }
+
     pub fn sys_sigprocmask(&self, how: u32, set:  UserAddr<SigSet>, oldset: UserAddr<SigSet>,
             _sigsetsize: usize) -> Result<(), RuntimeError> {
         // let pmm = self.get_pmm();
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.write();
         debug!(
             "rt_sigprocmask: how={:#x}, set={:#?}, oldset={:#?}, sigsetsize={}, thread={}",
             how,
@@ -17,32 +208,58 @@ impl Task {
             _sigsetsize,
             self.tid
         );
+        let mut signal = self.signal.lock();
         if oldset.is_valid() {
-            oldset.transfer().copy_from(&inner.sig_mask);
+            oldset.transfer().copy_from(&signal.sig_mask);
         }
         if set.is_valid() {
             let sig = set.transfer();
             match how {
                 // block
-                0 => inner.sig_mask.block(sig),
+                0 => signal.sig_mask.block(sig),
                 // unblock
-                1 => inner.sig_mask.unblock(sig),
+                1 => signal.sig_mask.unblock(sig),
                 // setmask
-                2 => inner.sig_mask.copy_from(sig),
-                _ => unimplemented!()
+                2 => signal.sig_mask.copy_from(sig),
+                _ => {
+                    inner.context.x[10] = EINVAL;
+                    return Ok(());
+                }
             }
+            signal.sig_mask.sanitize();
         }
+        drop(signal);
         inner.context.x[10] = 0;
         Ok(())
     }
 
-    pub fn sys_sigaction(&self, signum: usize, act: UserAddr<SigAction>, oldact: UserAddr<SigAction>, 
+    // 获取被屏蔽的待处理信号
+    pub fn sys_sigpending(&self, set: UserAddr<SigSet>) -> Result<(), RuntimeError> {
+        let mut inner = self.inner.write();
+        let process_pending: u64 = self.process.read().sig_pending.into();
+        let signal = self.signal.lock();
+        let pending: u64 = signal.sig_pending.into();
+        let mask: u64 = signal.sig_mask.into();
+        drop(signal);
+        *set.transfer() = SigSet::new((pending | process_pending) & mask);
+        inner.context.x[10] = 0;
+        Ok(())
+    }
+
+    pub fn sys_sigaction(&self, signum: usize, act: UserAddr<SigAction>, oldact: UserAddr<SigAction>,
             _sigsetsize: usize) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        // SIGKILL和SIGSTOP的处理无法修改
+        if signum == 0 || signum > NSIG || (act.is_valid()
+            && (signum == Signal::SIGKILL as usize || signum == Signal::SIGSTOP as usize)) {
+            inner.context.x[10] = EINVAL;
+            return Ok(());
+        }
+        let process = self.process.clone();
+        let mut process = process.write();
 
         if oldact.is_valid() {
-            oldact.transfer().copy_from(&process.sig_actions[signum]);
+            oldact.transfer().copy_from(&process.sig_actions.read()[signum]);
         }
         if act.is_valid() {
             let act = act.transfer();
@@ -54,15 +271,62 @@ impl Task {
                 _sigsetsize,
                 self.tid
             );
-            process.sig_actions[signum].copy_from(act);
+            process.sig_actions.write()[signum].copy_from(act);
+            // 忽略信号时丢弃待处理的信号
+            if act.handler == SIG_IGN {
+                process.sig_pending.remove(signum);
+                self.signal.lock().sig_pending.remove(signum);
+            }
         }
         drop(process);
         inner.context.x[10] = 0;
         Ok(())
     }
 
+    // 设置备用信号栈
+    pub fn sys_sigaltstack(&self, ss: UserAddr<SignalStack>, old_ss: UserAddr<SignalStack>) -> Result<(), RuntimeError> {
+        let mut inner = self.inner.write();
+        let on_stack = inner.sig_stack.contains(inner.context.x[2]);
+        if old_ss.is_valid() {
+            let mut old = inner.sig_stack;
+            if on_stack {
+                old.flags.insert(SignalStackFlags::ONSTACK);
+            }
+            *old_ss.transfer() = old;
+        }
+        if ss.is_valid() {
+            let mut new = *ss.transfer();
+            new.flags = SignalStackFlags::from_bits_truncate(new.flags.bits());
+            inner.context.x[10] = if on_stack {
+                // 正在使用备用信号栈时无法修改
+                EPERM
+            } else if new.flags.contains(SignalStackFlags::DISABLE) {
+                inner.sig_stack = SignalStack::new();
+                0
+            } else if !(new.flags - SignalStackFlags::AUTODISARM).is_empty() {
+                EINVAL
+            } else if new.size < MINSIGSTKSZ {
+                ENOMEM
+            } else {
+                inner.sig_stack = new;
+                0
+            };
+            return Ok(());
+        }
+        inner.context.x[10] = 0;
+        Ok(())
+    }
+
+    // 信号处理完毕 从用户栈上的frame恢复上下文和信号掩码
     pub fn sys_sigreturn(&self) -> Result<(), RuntimeError> {
-        debug!("sig return");
-        Err(RuntimeError::SigReturn)
+        let mut inner = self.inner.write();
+        let frame = UserAddr::<SignalFrame>::from(inner.context.x[2]).transfer();
+        debug!("sig return frame: {:#x}  sepc: {:#x}", inner.context.x[2], frame.ucontext.regs[0]);
+        let inner = &mut *inner;
+        frame.ucontext.restore(&mut inner.context, &mut inner.fp);
+        let mut signal = self.signal.lock();
+        signal.sig_mask = frame.ucontext.sig_mask;
+        signal.sig_mask.sanitize();
+        Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,216 @@
+use crate::{runtime_err::RuntimeError, memory::addr::UserAddr, sys_call::consts::{EINVAL, EPERM}, task::{task::Task, cred::{Credentials, NGROUPS_MAX}}};
+
+// 参数为-1时不修改对应的id
+const ID_UNCHANGED: u32 = u32::MAX;
+
+impl Task {
+    // 修改当前进程的用户和组 f返回系统调用的结果
+    fn update_cred(&self, f: impl FnOnce(&mut Credentials) -> usize) -> Result<(), RuntimeError> {
+        let process = self.get_process();
+        let result = f(&mut process.write().cred);
+        self.inner.write().context.x[10] = result;
+        Ok(())
+    }
+
+    // 获取当前进程的用户和组
+    fn get_cred(&self) -> Credentials {
+        self.get_process().read().cred.clone()
+    }
+
+    // 获取实际用户
+    pub fn sys_getuid(&self) -> Result<(), RuntimeError> {
+        let uid = self.get_cred().uid;
+        self.inner.write().context.x[10] = uid as usize;
+        Ok(())
+    }
+
+    // 获取有效用户
+    pub fn sys_geteuid(&self) -> Result<(), RuntimeError> {
+        let euid = self.get_cred().euid;
+        self.inner.write().context.x[10] = euid as usize;
+        Ok(())
+    }
+
+    // 获取实际组
+    pub fn sys_getgid(&self) -> Result<(), RuntimeError> {
+        let gid = self.get_cred().gid;
+        self.inner.write().context.x[10] = gid as usize;
+        Ok(())
+    }
+
+    // 获取有效组
+    pub fn sys_getegid(&self) -> Result<(), RuntimeError> {
+        let egid = self.get_cred().egid;
+        self.inner.write().context.x[10] = egid as usize;
+        Ok(())
+    }
+
+    // 设置用户 特权进程同时设置实际 有效和保存的用户 否则只能将有效用户设置为实际或保存的用户
+    pub fn sys_setuid(&self, uid: usize) -> Result<(), RuntimeError> {
+        let uid = uid as u32;
+        self.update_cred(|cred| {
+            if cred.is_root() {
+                cred.uid = uid;
+                cred.euid = uid;
+                cred.suid = uid;
+            } else if uid == cred.uid || uid == cred.suid {
+                cred.euid = uid;
+            } else {
+                return EPERM;
+            }
+            0
+        })
+    }
+
+    // 设置组
+    pub fn sys_setgid(&self, gid: usize) -> Result<(), RuntimeError> {
+        let gid = gid as u32;
+        self.update_cred(|cred| {
+            if cred.is_root() {
+                cred.gid = gid;
+                cred.egid = gid;
+                cred.sgid = gid;
+            } else if gid == cred.gid || gid == cred.sgid {
+                cred.egid = gid;
+            } else {
+                return EPERM;
+            }
+            0
+        })
+    }
+
+    // 设置实际和有效用户 修改实际用户或有效用户与原来的实际用户不同时 保存的用户设置为新的有效用户
+    pub fn sys_setreuid(&self, ruid: usize, euid: usize) -> Result<(), RuntimeError> {
+        let (ruid, euid) = (ruid as u32, euid as u32);
+        self.update_cred(|cred| {
+            if !cred.is_root() && ((ruid != ID_UNCHANGED && ruid != cred.uid && ruid != cred.euid)
+                || (euid != ID_UNCHANGED && !cred.has_uid(euid))) {
+                return EPERM;
+            }
+            let old_uid = cred.uid;
+            if ruid != ID_UNCHANGED {
+                cred.uid = ruid;
+            }
+            if euid != ID_UNCHANGED {
+                cred.euid = euid;
+            }
+            if ruid != ID_UNCHANGED || (euid != ID_UNCHANGED && euid != old_uid) {
+                cred.suid = cred.euid;
+            }
+            0
+        })
+    }
+
+    // 设置实际和有效组
+    pub fn sys_setregid(&self, rgid: usize, egid: usize) -> Result<(), RuntimeError> {
+        let (rgid, egid) = (rgid as u32, egid as u32);
+        self.update_cred(|cred| {
+            if !cred.is_root() && ((rgid != ID_UNCHANGED && rgid != cred.gid && rgid != cred.egid)
+                || (egid != ID_UNCHANGED && !cred.has_gid(egid))) {
+                return EPERM;
+            }
+            let old_gid = cred.gid;
+            if rgid != ID_UNCHANGED {
+                cred.gid = rgid;
+            }
+            if egid != ID_UNCHANGED {
+                cred.egid = egid;
+            }
+            if rgid != ID_UNCHANGED || (egid != ID_UNCHANGED && egid != old_gid) {
+                cred.sgid = cred.egid;
+            }
+            0
+        })
+    }
+
+    // 设置实际 有效和保存的用户 非特权进程只能设置为三者之一
+    pub fn sys_setresuid(&self, ruid: usize, euid: usize, suid: usize) -> Result<(), RuntimeError> {
+        let ids = [ruid as u32, euid as u32, suid as u32];
+        self.update_cred(|cred| {
+            if !cred.is_root() && ids.iter().any(|&id| id != ID_UNCHANGED && !cred.has_uid(id)) {
+                return EPERM;
+            }
+            if ids[0] != ID_UNCHANGED {
+                cred.uid = ids[0];
+            }
+            if ids[1] != ID_UNCHANGED {
+                cred.euid = ids[1];
+            }
+            if ids[2] != ID_UNCHANGED {
+                cred.suid = ids[2];
+            }
+            0
+        })
+    }
+
+    // 设置实际 有效和保存的组
+    pub fn sys_setresgid(&self, rgid: usize, egid: usize, sgid: usize) -> Result<(), RuntimeError> {
+        let ids = [rgid as u32, egid as u32, sgid as u32];
+        self.update_cred(|cred| {
+            if !cred.is_root() && ids.iter().any(|&id| id != ID_UNCHANGED && !cred.has_gid(id)) {
+                return EPERM;
+            }
+            if ids[0] != ID_UNCHANGED {
+                cred.gid = ids[0];
+            }
+            if ids[1] != ID_UNCHANGED {
+                cred.egid = ids[1];
+            }
+            if ids[2] != ID_UNCHANGED {
+                cred.sgid = ids[2];
+            }
+            0
+        })
+    }
+
+    // 获取实际 有效和保存的用户
+    pub fn sys_getresuid(&self, ruid: UserAddr<u32>, euid: UserAddr<u32>, suid: UserAddr<u32>) -> Result<(), RuntimeError> {
+        let cred = self.get_cred();
+        *ruid.transfer() = cred.uid;
+        *euid.transfer() = cred.euid;
+        *suid.transfer() = cred.suid;
+        self.update_context(|x| x.x[10] = 0);
+        Ok(())
+    }
+
+    // 获取实际 有效和保存的组
+    pub fn sys_getresgid(&self, rgid: UserAddr<u32>, egid: UserAddr<u32>, sgid: UserAddr<u32>) -> Result<(), RuntimeError> {
+        let cred = self.get_cred();
+        *rgid.transfer() = cred.gid;
+        *egid.transfer() = cred.egid;
+        *sgid.transfer() = cred.sgid;
+        self.update_context(|x| x.x[10] = 0);
+        Ok(())
+    }
+
+    // 获取附加组 size为0时只返回数量
+    pub fn sys_getgroups(&self, size: usize, list: UserAddr<u32>) -> Result<(), RuntimeError> {
+        let groups = self.get_cred().groups;
+        let result = if size == 0 {
+            groups.len()
+        } else if size < groups.len() {
+            EINVAL
+        } else {
+            list.transfer_vec(groups.len()).copy_from_slice(&groups);
+            groups.len()
+        };
+        self.inner.write().context.x[10] = result;
+        Ok(())
+    }
+
+    // 设置附加组 需要特权
+    pub fn sys_setgroups(&self, size: usize, list: UserAddr<u32>) -> Result<(), RuntimeError> {
+        if size > NGROUPS_MAX {
+            self.update_context(|x| x.x[10] = EINVAL);
+            return Ok(());
+        }
+        let groups = if size == 0 { vec![] } else { list.transfer_vec(size).to_vec() };
+        self.update_cred(|cred| {
+            if !cred.is_root() {
+                return EPERM;
+            }
+            cred.groups = groups;
+            0
+        })
+    }
+}
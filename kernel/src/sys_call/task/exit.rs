@@ -1,89 +1,123 @@
-use crate::{task::{task::Task, task_scheduler::get_task}, runtime_err::RuntimeError, sys_call::{remove_vfork_wait, SYS_CALL_ERR}, memory::page::get_free_page_num};
+use alloc::vec::Vec;
+use alloc::sync::Arc;
+
+use crate::{task::{task::Task, task_scheduler::{get_task, get_task_by_tid, get_all_processes}, process::{exit_status, INIT_PID}, signal::NSIG}, runtime_err::RuntimeError, sys_call::consts::{EINVAL, EPERM, ESRCH}, memory::page::get_free_page_num};
 
 impl Task {
     /// 退出当前任务 
     pub fn sys_exit(&self, exit_code: usize) -> Result<(), RuntimeError> {
-        let inner = self.inner.borrow();
-        if self.tid == 0 {
-            inner.process.borrow_mut().exit(exit_code);
+        // 在释放内存之前释放持有的futex
+        self.release_futexes();
+
+        let mut process = self.process.write();
+        // 从进程中移除当前线程 最后一个线程退出时结束进程
+        process.tasks.retain(|x| x.upgrade().map_or(false, |x| x.tid != self.tid));
+        if process.tasks.len() == 0 {
+            process.exit(exit_status(exit_code));
         } else {
+            drop(process);
             self.exit();
         }
-
-        let clear_child_tid = self.clear_child_tid.borrow().clone();
-        if clear_child_tid.is_valid() {
-            *clear_child_tid.transfer() = 0;
-        }
         Err(RuntimeError::KillCurrentTask)
     }
     
-    // 退出当前进程？ eg: 功能也许有待完善
-    pub fn sys_exit_group(&self, exit_code: usize) -> Result<(), RuntimeError> {
-        let inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
+    // 结束进程中的所有线程 status为wait4获取的状态
+    pub fn exit_process(&self, status: usize) {
+        // 在释放内存之前释放所有线程持有的futex
+        let tasks: Vec<_> = self.get_process().read().tasks.iter().filter_map(|x| x.upgrade()).collect();
+        for task in tasks {
+            task.release_futexes();
+        }
         debug!("exit pid: {}", self.pid);
-        process.exit(exit_code);
-        match &process.parent {
-            Some(parent) => {
-                let parent = parent.upgrade().unwrap();
-                let parent = parent.borrow();
-                remove_vfork_wait(parent.pid);
-
-                // let end: UserAddr<TimeSpec> = 0x10bb78.into();
-                // let start: UserAddr<TimeSpec> = 0x10bad0.into();
-
-                // println!("start: {:?}   end: {:?}",start.transfer(), end.transfer());
-
-                // let target_end: UserAddr<TimeSpec> = parent.pmm.get_phys_addr(0x10bb78usize.into())?.0.into();
-                // let target_start: UserAddr<TimeSpec> = parent.pmm.get_phys_addr(0x10bad0usize.into())?.0.into();
-                // *target_start.transfer() = *start.transfer();
-                // *target_end.transfer() = *end.transfer();
+        self.get_process().write().exit(status);
+    }
 
-                // let task = parent.tasks[0].clone().upgrade().unwrap();
-                // drop(parent);
-                // // 处理signal 17 SIGCHLD
-                // task.signal(17);
-            }
-            None => {}
-        }
+    // 退出当前进程？ eg: 功能也许有待完善
+    pub fn sys_exit_group(&self, exit_code: usize) -> Result<(), RuntimeError> {
+        self.exit_process(exit_status(exit_code));
+        // 父进程在进程退出时收到SIGCHLD
         debug!("剩余页表: {}", get_free_page_num());
         debug!("exit_code: {:#x}", exit_code);
         Err(RuntimeError::ChangeTask)
     }
 
-    // kill task
-    pub fn sys_kill(&self, _pid: usize, _signum: usize) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
-        debug!(
-            "kill: thread {} kill process {} with signal {:?}",
-            0,
-            _pid,
-            _signum
-        );
-
-        inner.context.x[10] = 0;
+    // 发送信号 pid > 0 时发送给对应进程 0 发送给当前进程组
+    // -1 发送给除根进程和当前进程以外的所有进程 < -1 发送给进程组 -pid
+    pub fn sys_kill(&self, pid: usize, signum: usize) -> Result<(), RuntimeError> {
+        debug!("kill: thread {} kill process {} with signal {:?}", self.tid, pid as isize, signum);
+        if signum > NSIG {
+            self.update_context(|x| x.x[10] = EINVAL);
+            return Ok(());
+        }
+        let process = self.get_process();
+        let (pgid, cred) = {
+            let process = process.read();
+            (process.pgid, process.cred.clone())
+        };
+        let pid = pid as isize;
+        let targets: Vec<_> = get_all_processes().into_iter().filter(|x| {
+            let process = x.read();
+            match pid {
+                0 => process.pgid == pgid,
+                // 除init进程和调用者以外的所有进程
+                -1 => process.pid != self.pid && process.pid != INIT_PID,
+                _ if pid < -1 => process.pgid == (-pid) as usize,
+                _ => process.pid == pid as usize
+            }
+        }).collect();
+        if targets.len() == 0 {
+            self.update_context(|x| x.x[10] = ESRCH);
+            return Ok(());
+        }
+        // 只能向有权限的进程发送信号
+        let targets: Vec<_> = targets.into_iter().filter(|x| {
+            Arc::ptr_eq(x, &process) || cred.can_signal(&x.read().cred)
+        }).collect();
+        if targets.len() == 0 {
+            self.update_context(|x| x.x[10] = EPERM);
+            return Ok(());
+        }
+        // 信号为0时只检查进程是否存在
+        if signum != 0 {
+            for process in targets {
+                process.write().send_signal(signum);
+            }
+        }
+        self.update_context(|x| x.x[10] = 0);
         Ok(())
     }
 
-    // kill task
+    // 向线程发送信号
     pub fn sys_tkill(&self, tid: usize, signum: usize) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
-        inner.context.x[10] = 0;
-        let signal_task = get_task(self.pid, tid);
-        debug!("signum: {}", signum);
-        if let Some(signal_task) = signal_task {
-            drop(inner);
-            signal_task.signal(signum)?;
-        }
-        Ok(())
+        debug!("tkill: tid: {}  signum: {}", tid, signum);
+        self.send_to_task(get_task_by_tid(tid), signum)
     }
 
+    // 向进程中的线程发送信号
     pub fn sys_tgkill(&self, tgid: usize, tid: usize, signum: usize) -> Result<(), RuntimeError> {
         debug!("tgkill: tgid: {}  tid: {}  signum {}", tgid, tid, signum);
-        if let Some(task) = get_task(tgid, tid) {
-            task.signal(signum)?;
-        } else {
-            self.update_context(|x| x.x[10] = SYS_CALL_ERR);
+        self.send_to_task(get_task(tgid, tid), signum)
+    }
+
+    // 向线程发送信号 信号为0时只检查线程是否存在
+    fn send_to_task(&self, task: Option<Arc<Task>>, signum: usize) -> Result<(), RuntimeError> {
+        if signum > NSIG {
+            self.update_context(|x| x.x[10] = EINVAL);
+            return Ok(());
+        }
+        // 不同时持有两个进程的锁
+        let cred = self.get_process().read().cred.clone();
+        match task {
+            Some(task) if task.pid != self.pid && !cred.can_signal(&task.get_process().read().cred) => {
+                self.update_context(|x| x.x[10] = EPERM);
+            }
+            Some(task) => {
+                if signum != 0 {
+                    task.signal(signum)?;
+                }
+                self.update_context(|x| x.x[10] = 0);
+            }
+            None => self.update_context(|x| x.x[10] = ESRCH)
         }
         Ok(())
     }
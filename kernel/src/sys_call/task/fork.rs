@@ -1,248 +1,356 @@
-use alloc::{string::String, vec::Vec, rc::Rc};
+use alloc::{string::String, vec::Vec, sync::Arc};
 use k210_pac::uart1::tar;
 
-use crate::{runtime_err::RuntimeError, sys_call::{SYS_CALL_ERR, CloneFlags, add_vfork_wait}, memory::{addr::UserAddr, page_table::switch_to_kernel_page}, task::{exec_with_process, task_scheduler::{get_task_num, add_task_to_scheduler}, task::{Task, TaskStatus}, pid::get_next_pid, process::Process}};
+use crate::{runtime_err::RuntimeError, fs::filetree::INode, sys_call::{CloneFlags, WaitOptions, add_vfork_wait, remove_vfork_wait, consts::{EACCES, EAGAIN, EINVAL, ECHILD, ENOENT, ENOEXEC}}, memory::{addr::UserAddr, page_table::switch_to_kernel_page}, task::{exec_with_process, check_exec, task_scheduler::{get_task_num, add_task_to_scheduler, get_current_task, kill_task, get_all_processes}, rlimit::RLIMIT_NPROC, cred::{X_OK, S_ISUID, S_ISGID}, task::{Task, TaskStatus, Rusage}, pid::get_next_pid, process::{Process, CONTINUED_STATUS}, signal::{SigAction, SigInfo, Signal, SignalStack, SIG_IGN}}};
 
-impl Task {
-
-    pub fn sys_sched_yield(&self) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
-        inner.status = TaskStatus::READY;
-        Err(RuntimeError::ChangeTask)
-    }
-    
-    // fork process
-    pub fn sys_fork(&self) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.clone();
-        let mut process = process.borrow_mut();
-
-        let (child_process, child_task) =
-            Process::new(get_next_pid(), Some(Rc::downgrade(&inner.process)))?;
-        process.children.push(child_process.clone());
-        let mut child_task_inner = child_task.inner.borrow_mut();
-        child_task_inner.context.clone_from(&inner.context);
-        child_task_inner.context.x[10] = 0;
-        drop(child_task_inner);
-        add_task_to_scheduler(child_task.clone());
-        let cpid = child_task.pid;
-        inner.context.x[10] = cpid;
-        let mut child_process = child_process.borrow_mut();
-        child_process.mem_set = process.mem_set.clone_with_data()?;
-        child_process.stack = process.stack.clone_with_data(child_process.pmm.clone())?;
-        // 复制fd_table
-        child_process.fd_table = process.fd_table.clone();
-        // 创建新的heap
-        // child_process.heap = UserHeap::new(child_process.pmm.clone())?;
-        child_process.heap = process.heap.clone_with_data(child_process.pmm.clone())?;
-        debug!("heap_pointer: {:#x}", child_process.heap.get_heap_top());
-        child_process.pmm.add_mapping_by_set(&child_process.mem_set)?;
-        drop(process);
-        drop(child_process);
-        drop(inner);
-        // Ok(())
-        Err(RuntimeError::ChangeTask)
-    }
-
-    pub fn sys_spec_fork(&self, flags: usize, _new_sp: usize, _ptid: UserAddr<u32>, _tls: usize, ctid_ptr: UserAddr<u32>) -> Result<(), RuntimeError>{
-        // return self.sys_fork();
-        let flags = CloneFlags::from_bits_truncate(flags);
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.clone();
+// waitid 的 idtype
+const P_ALL: usize = 0;
+const P_PID: usize = 1;
+const P_PGID: usize = 2;
 
-        let cpid = get_next_pid();
-        let (child_process, child_task) =
-            Process::fork(cpid, process.clone())?;
-        
-        let mut process = process.borrow_mut();
-        process.children.push(child_process.clone());
+// waitid 返回的 si_code
+const CLD_EXITED: i32 = 1;
+const CLD_KILLED: i32 = 2;
+const CLD_DUMPED: i32 = 3;
+const CLD_STOPPED: i32 = 5;
+const CLD_CONTINUED: i32 = 6;
 
-        let mut child_task_inner = child_task.inner.borrow_mut();
-        child_task_inner.context.clone_from(&inner.context);
-        child_task_inner.context.x[10] = 0;
-        drop(child_task_inner);
-
-        add_task_to_scheduler(child_task.clone());
-
-        let mut child_process = child_process.borrow_mut();
-        child_process.stack = process.stack.clone_with_data(child_process.pmm.clone())?;
-        // child_process.heap = process.heap.clone_with_data(child_process.pmm.clone())?;
-        inner.context.x[10] = cpid;
-
-        if flags.contains(CloneFlags::CLONE_CHILD_SETTID) {
-            *ctid_ptr.transfer() = cpid as u32;
-        }
-
-        if flags.contains(CloneFlags::CLONE_CHILD_CLEARTID) {
-            
-        }
+impl Task {
 
-        drop(process);
-        drop(child_process);
-        drop(inner);
-        // Ok(())
-        add_vfork_wait(self.pid);
+    // 放弃处理器 SCHED_FIFO 任务重新排到同优先级任务之后
+    pub fn sys_sched_yield(&self) -> Result<(), RuntimeError> {
+        self.set_status(TaskStatus::READY);
+        self.sched.lock().seq = 0;
         Err(RuntimeError::ChangeTask)
     }
     
-    // clone task
+    // 创建任务 根据flags决定与当前任务共享的资源
     pub fn sys_clone(&self, flags: usize, new_sp: usize, ptid: UserAddr<u32>, tls: usize, ctid_ptr: UserAddr<u32>) -> Result<(), RuntimeError> {
-        // let flags = flags & 0x4fff;
         debug!(
             "clone: flags={:#x}, newsp={:#x}, parent_tid={:#x}, child_tid={:#x}, newtls={:#x}",
             flags, new_sp, ptid.bits(), ctid_ptr.0 as usize, tls
         );
-        if flags == 0x4111 || flags == 0x11 {
-            // VFORK | VM | SIGCHILD
-            warn!("sys_clone is calling sys_fork instead, ignoring other args");
-            return self.sys_fork();
-        } else if flags == 0x1200011 {
-            return self.sys_spec_fork(0x11, new_sp, ptid, tls, ctid_ptr);
-            // return self.sys_fork();
+        let flags = CloneFlags::from_bits_truncate(flags);
+
+        // 线程需要共享信号处理 共享信号处理需要共享地址空间
+        if (flags.contains(CloneFlags::CLONE_THREAD) && !flags.contains(CloneFlags::CLONE_SIGHAND))
+            || (flags.contains(CloneFlags::CLONE_SIGHAND) && !flags.contains(CloneFlags::CLONE_VM)) {
+            self.update_context(|x| x.x[10] = EINVAL);
+            return Ok(());
         }
 
-        debug!(
-            "clone: flags={:#x}, newsp={:#x}, parent_tid={:#x}, child_tid={:#x}, newtls={:#x}",
-            flags, new_sp, ptid.bits(), ctid_ptr.0 as usize, tls
-        );
+        // 同一实际用户的进程数量不能超出RLIMIT_NPROC root不受限制
+        let (nproc, cred) = {
+            let process = self.get_process();
+            let process = process.read();
+            (process.rlimits[RLIMIT_NPROC], process.cred.clone())
+        };
+        let user_processes = || get_all_processes().iter().filter(|x| x.read().cred.uid == cred.uid).count();
+        if !flags.contains(CloneFlags::CLONE_THREAD) && !cred.is_root() && nproc.exceeded(user_processes() + 1) {
+            self.update_context(|x| x.x[10] = EAGAIN);
+            return Ok(());
+        }
 
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.clone();
-        let process = process.borrow();
-        
-        let ctid = process.tasks.len();
-        drop(process);
+        // 线程加入当前进程 共享进程中的所有资源 否则创建新的进程
+        let new_task = if flags.contains(CloneFlags::CLONE_THREAD) {
+            Task::new(get_next_pid(), self.get_process())
+        } else {
+            self.clone_process(flags)?
+        };
+        let ctid = new_task.tid;
 
-        let new_task = Task::new(ctid, inner.process.clone());
-        let mut new_task_inner = new_task.inner.borrow_mut();
+        let mut inner = self.inner.write();
+        let mut new_task_inner = new_task.inner.write();
         new_task_inner.context.clone_from(&inner.context);
-        new_task_inner.context.x[2] = new_sp;
-        new_task_inner.context.x[4] = tls;
+        new_task_inner.fp = inner.fp;
+        if new_sp != 0 {
+            new_task_inner.context.x[2] = new_sp;
+        }
+        if flags.contains(CloneFlags::CLONE_SETTLS) {
+            new_task_inner.context.x[4] = tls;
+        }
         new_task_inner.context.x[10] = 0;
-        add_task_to_scheduler(new_task.clone());
-        // 添加到process
-        inner.context.x[10] = ctid;
-        
-        debug!("tasks: len {}", get_task_num());
-
         drop(new_task_inner);
+        new_task.signal.lock().sig_mask = self.signal.lock().sig_mask;
+        *new_task.sched.lock() = self.sched.lock().fork();
+        inner.context.x[10] = ctid;
         drop(inner);
-        if ptid.is_valid() {
+
+        if flags.contains(CloneFlags::CLONE_PARENT_SETTID) && ptid.is_valid() {
             *ptid.transfer() = ctid as u32;
         }
-        // 执行 set_tid_address
-        new_task.set_tid_address(ctid_ptr);
-        // just finish clone, not change task
+        // 写入子任务的地址空间 不共享地址空间时需要通过子进程的页表转换
+        if flags.contains(CloneFlags::CLONE_CHILD_SETTID) && ctid_ptr.is_valid() {
+            if flags.contains(CloneFlags::CLONE_VM) {
+                *ctid_ptr.transfer() = ctid as u32;
+            } else {
+                let child_process = new_task.get_process();
+                let addr = child_process.read().mm.read().pmm.get_phys_addr(ctid_ptr.bits().into())?;
+                *addr.tranfer::<u32>() = ctid as u32;
+            }
+        }
+        // 子任务退出时清除ctid
+        if flags.contains(CloneFlags::CLONE_CHILD_CLEARTID) {
+            new_task.set_tid_address(ctid_ptr);
+        }
+
+        // vfork 父进程等待子进程执行新程序或退出 子进程可能立即在其他核心上运行
+        let vfork = flags.contains(CloneFlags::CLONE_VFORK);
+        if vfork {
+            add_vfork_wait(self.pid);
+        }
+        add_task_to_scheduler(new_task);
+        debug!("tasks: len {}", get_task_num());
+        if vfork {
+            return Err(RuntimeError::ChangeTask);
+        }
         Ok(())
-        // Err(RuntimeError::ChangeTask)
+    }
+
+    // 创建子进程 根据flags决定共享或复制地址空间 文件描述符表 工作目录和信号处理
+    fn clone_process(&self, flags: CloneFlags) -> Result<Arc<Task>, RuntimeError> {
+        let process_rc = self.get_process();
+        let (child_process, child_task) = Process::fork(get_next_pid(), process_rc.clone(), flags)?;
+        process_rc.write().children.push(child_process);
+        Ok(child_task)
     }
 
     // 执行文件
     pub fn sys_execve(&self, filename: UserAddr<u8>, argv: UserAddr<UserAddr<u8>>, 
-            _envp: UserAddr<UserAddr<u8>>) -> Result<(), RuntimeError> {
-        let inner = self.inner.borrow_mut();
-        let mut process = inner.process.borrow_mut();
+            envp: UserAddr<UserAddr<u8>>) -> Result<(), RuntimeError> {
+        let inner = self.inner.write();
+        let mut process = self.process.write();
         let filename = filename.read_string();
 
         debug!("run {}", filename);
+        // 相对路径从工作目录开始查找
+        let current = if filename.starts_with('/') {
+            None
+        } else {
+            Some(process.workspace.read().clone())
+        };
+        // 执行文件需要执行权限 程序或解释器不是合法的elf时返回ENOEXEC
+        let kstat = match INode::get(current.clone(), &filename) {
+            _ if !process.cred.can_search(current.clone(), &filename) => Err(EACCES),
+            Ok(inode) if inode.is_dir() => Err(EACCES),
+            Ok(inode) if !process.cred.permitted(&inode.stat(), X_OK) => Err(EACCES),
+            Ok(inode) => match check_exec(current.clone(), &filename) {
+                Ok(exec_file) => Ok((inode.stat(), exec_file)),
+                Err(RuntimeError::ENOEXEC) => Err(ENOEXEC),
+                Err(_) => Err(ENOENT)
+            },
+            Err(_) => Err(ENOENT)
+        };
+        let (kstat, exec_file) = match kstat {
+            Ok(result) => result,
+            Err(err) => {
+                drop(process);
+                drop(inner);
+                self.inner.write().context.x[10] = err;
+                return Ok(());
+            }
+        };
         let args = argv.transfer_until(|x| !x.is_valid());
         let args:Vec<String> = args.iter_mut().map(|x| x.read_string()).collect();
 
-        // 读取envp
-        // let envp = argv.translate_until(pmm.clone(), |x| !x.is_valid());
-        // let envp:Vec<String> = envp.iter_mut().map(|x| x.read_string(pmm.clone())).collect();
+        // 读取envp envp为空时没有环境变量
+        let envp: Vec<String> = if envp.is_valid() {
+            envp.transfer_until(|x| !x.is_valid()).iter_mut().map(|x| x.read_string()).collect()
+        } else {
+            vec![]
+        };
 
-        // 获取 envp
-        let task = process.tasks[self.tid].clone().upgrade().unwrap();
+        // 结束进程中的其他线程 由当前线程执行新的程序
+        let task = get_current_task().unwrap();
+        for other in process.tasks.iter().filter_map(|x| x.upgrade()).filter(|x| x.tid != self.tid) {
+            kill_task(other.pid, other.tid);
+        }
+        process.tasks.retain(|x| x.upgrade().map_or(false, |x| x.tid == task.tid));
+        // 释放旧的地址空间前离开进程的页表
+        switch_to_kernel_page();
         process.reset()?;
+        // 关闭设置了close_on_exec的文件描述符
+        process.fd_table.write().close_on_exec();
+        // 设置了S_ISUID和S_ISGID时切换到文件的所有者和所属组 保存的用户和组与有效的相同
+        if kstat.st_mode & S_ISUID != 0 {
+            process.cred.euid = kstat.st_uid;
+        }
+        if kstat.st_mode & S_ISGID != 0 {
+            process.cred.egid = kstat.st_gid;
+        }
+        process.cred.suid = process.cred.euid;
+        process.cred.sgid = process.cred.egid;
+        // 捕获的信号恢复默认处理 忽略的信号保持忽略
+        for action in process.sig_actions.write().iter_mut().filter(|x| x.handler != SIG_IGN) {
+            *action = SigAction::empty();
+        }
+        let parent = process.parent.as_ref().and_then(|x| x.upgrade());
         drop(process);
-        let process = inner.process.clone();
+        // vfork 的父进程在子进程执行新程序后继续运行
+        if let Some(parent) = parent {
+            remove_vfork_wait(parent.read().pid);
+        }
+        let process = self.process.clone();
         drop(inner);
-        switch_to_kernel_page();
-        exec_with_process(process.clone(), task, &filename, args.iter().map(AsRef::as_ref).collect())?;
-        // process.borrow_mut().new_heap()?;
+        self.inner.write().sig_stack = SignalStack::new();
+        exec_with_process(process.clone(), task, exec_file, &filename, args.iter().map(AsRef::as_ref).collect(),
+            envp.iter().map(AsRef::as_ref).collect())?;
+        // process.write().new_heap()?;
         self.before_run();
         Ok(())
     }
     
-    // wait task
-    pub fn sys_wait4(&self, pid: usize, ptr: UserAddr<i32>, _options: usize) -> Result<(), RuntimeError> {
-        debug!("pid: {:#x}, ptr: {:#x}, _options: {}", pid, ptr.bits(), _options);
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.clone();
-        let mut process = process.borrow_mut();
-
-
-        if pid != SYS_CALL_ERR {
-            // let target = 
-            // process.children.iter().find(|&x| x.borrow().pid == pid);
-
-            // if let Some(exit_code) = target.map_or(None, |x| x.borrow().exit_code) {
-            //     if ptr.is_valid() {
-            //         *ptr.transfer() = exit_code as i32;
-            //     }
-
-            //     inner.context.x[10] = pid;
-            //     return Ok(())
-            // }
-
-            
-            let target = 
-                process.children.iter().find(|&x| x.borrow().pid == pid);
-
-            if let Some(target) = target {
-                let target = target.borrow();
-                if let Some(exit_code) = target.exit_code {
-                    if ptr.is_valid() {
-                        *ptr.transfer() = exit_code as i32;
-                    }
-
-                    debug!("hava task");
-                    
-                    let t_pid = target.pid;
-                    drop(target);
-                    process.children.drain_filter(|x| x.borrow().pid == t_pid);
-
-                    inner.context.x[10] = pid;
-                    return Ok(())
+    // 等待子进程状态改变 pid > 0 时等待对应的子进程 -1 等待任意子进程
+    // 0 等待同一进程组的子进程 < -1 等待进程组为 -pid 的子进程
+    pub fn sys_wait4(&self, pid: usize, ptr: UserAddr<i32>, options: usize, rusage: UserAddr<Rusage>) -> Result<(), RuntimeError> {
+        debug!("pid: {:#x}, ptr: {:#x}, options: {}", pid, ptr.bits(), options);
+        let options = WaitOptions::from_bits_truncate(options) | WaitOptions::WEXITED;
+        let pgid = self.get_process().read().pgid;
+        let pid = pid as isize;
+        let matches = |child: &Process| match pid {
+            -1 => true,
+            0 => child.pgid == pgid,
+            _ if pid < -1 => child.pgid == (-pid) as usize,
+            _ => child.pid == pid as usize
+        };
+        match self.wait_child(&matches, options, rusage) {
+            WaitResult::Found(child_pid, status) => {
+                if ptr.is_valid() {
+                    *ptr.transfer() = status as i32;
                 }
-            } else {
-                debug!("not hava task");
-                inner.context.x[10] = -10 as isize as usize;
-                return Ok(());
+                self.inner.write().context.x[10] = child_pid;
             }
+            WaitResult::NotYet if options.contains(WaitOptions::WNOHANG) => self.update_context(|x| x.x[10] = 0),
+            WaitResult::NotYet => return self.wait_for_children(),
+            WaitResult::NoChild => self.update_context(|x| x.x[10] = ECHILD)
+        }
+        Ok(())
+    }
 
-            // if let Some(exit_code) = target.map_or(None, |x| x.borrow().exit_code) {
-            //     if ptr.is_valid() {
-            //         *ptr.transfer() = exit_code as i32;
-            //     }
-
-            //     inner.context.x[10] = pid;
-            //     return Ok(())
-            // }
-        } else {
-            if process.children.len() == 0 {
-                inner.context.x[10] = -10 as isize as usize;
+    // 等待子进程状态改变 将状态写入siginfo
+    pub fn sys_waitid(&self, idtype: usize, id: usize, infop: UserAddr<SigInfo>, options: usize, rusage: UserAddr<Rusage>) -> Result<(), RuntimeError> {
+        let options = WaitOptions::from_bits_truncate(options);
+        // 至少需要等待一种状态改变
+        if !options.intersects(WaitOptions::WEXITED | WaitOptions::WUNTRACED | WaitOptions::WCONTINUED) {
+            self.update_context(|x| x.x[10] = EINVAL);
+            return Ok(());
+        }
+        let matches = |child: &Process| match idtype {
+            P_PID => child.pid == id,
+            P_PGID => child.pgid == id,
+            _ => true
+        };
+        if idtype != P_ALL && idtype != P_PID && idtype != P_PGID {
+            self.update_context(|x| x.x[10] = EINVAL);
+            return Ok(());
+        }
+        let info = match self.wait_child(&matches, options, rusage) {
+            WaitResult::Found(child_pid, status) => child_siginfo(child_pid, status),
+            // WNOHANG 没有状态改变的子进程时 si_pid 为0
+            WaitResult::NotYet if options.contains(WaitOptions::WNOHANG) => SigInfo::new(0, 0, 0),
+            WaitResult::NotYet => return self.wait_for_children(),
+            WaitResult::NoChild => {
+                self.update_context(|x| x.x[10] = ECHILD);
                 return Ok(());
             }
+        };
+        if infop.is_valid() {
+            *infop.transfer() = info;
+        }
+        self.update_context(|x| x.x[10] = 0);
+        Ok(())
+    }
 
-            let cprocess_vec = 
-                process.children.drain_filter(|x| x.borrow().exit_code.is_some()).collect::<Vec<_>>();
-
-            debug!("cpro len: {}", cprocess_vec.len());
+    // 没有状态改变的子进程 已经在wait_child中进入等待队列 子进程状态改变时唤醒并重新执行
+    fn wait_for_children(&self) -> Result<(), RuntimeError> {
+        self.inner.write().context.sepc -= 4;
+        Err(RuntimeError::ChangeTask)
+    }
 
-            if cprocess_vec.len() != 0 {
-                let cprocess = cprocess_vec[0].borrow();
-                if ptr.is_valid() {
-                    *ptr.transfer() = cprocess.exit_code.unwrap() as i32;
+    // 查找状态改变的子进程 没有WNOWAIT时回收退出的子进程并清除停止和继续状态
+    fn wait_child(&self, matches: &dyn Fn(&Process) -> bool, options: WaitOptions,
+            rusage: UserAddr<Rusage>) -> WaitResult {
+        let process_rc = self.get_process();
+        let mut process = process_rc.write();
+        let mut found = false;
+        let mut result = None;
+        for (index, child) in process.children.iter().enumerate() {
+            let mut child = child.write();
+            if !matches(&child) {
+                continue;
+            }
+            found = true;
+            if let Some(status) = child.exit_code {
+                if options.contains(WaitOptions::WEXITED) {
+                    result = Some((index, child.pid, status, true));
+                    break;
+                }
+                continue;
+            }
+            if let Some(status) = child.stop_status {
+                let wanted = if status == CONTINUED_STATUS {
+                    options.contains(WaitOptions::WCONTINUED)
+                } else {
+                    options.contains(WaitOptions::WUNTRACED)
+                };
+                if wanted {
+                    if !options.contains(WaitOptions::WNOWAIT) {
+                        child.stop_status = None;
+                    }
+                    result = Some((index, child.pid, status, false));
+                    break;
                 }
-                inner.context.x[10] = cprocess.pid;
-                return Ok(());
             }
         }
-        inner.context.sepc -= 4;
-        drop(process);
-        drop(inner);
-        Err(RuntimeError::ChangeTask)
+        let (index, child_pid, status, exited) = match result {
+            Some(result) => result,
+            None if found => {
+                // 持有进程锁时进入等待队列 避免错过检查之后的状态改变
+                if !options.contains(WaitOptions::WNOHANG) {
+                    process.wait_queue.wait(get_current_task().unwrap());
+                }
+                return WaitResult::NotYet;
+            }
+            None => return WaitResult::NoChild
+        };
+        let (utime, stime) = {
+            let child = process.children[index].read();
+            (child.tms.tms_utime + child.tms.tms_cutime, child.tms.tms_stime + child.tms.tms_cstime)
+        };
+        if rusage.is_valid() {
+            *rusage.transfer() = Rusage::new(utime, stime);
+        }
+        // 回收子进程 运行时间计入父进程
+        if exited && !options.contains(WaitOptions::WNOWAIT) {
+            process.tms.tms_cutime += utime;
+            process.tms.tms_cstime += stime;
+            process.children.remove(index);
+        }
+        WaitResult::Found(child_pid, status)
     }
-}
\ No newline at end of file
+}
+
+// 等待子进程的结果
+enum WaitResult {
+    Found(usize, usize),    // 状态改变的子进程 (pid, 状态)
+    NotYet,                 // 有匹配的子进程但状态没有改变
+    NoChild                 // 没有匹配的子进程
+}
+
+// 根据wait状态生成waitid返回的siginfo
+fn child_siginfo(pid: usize, status: usize) -> SigInfo {
+    let (code, si_status) = if status == CONTINUED_STATUS {
+        (CLD_CONTINUED, Signal::SIGCONT as usize)
+    } else if status & 0xff == 0x7f {
+        (CLD_STOPPED, (status >> 8) & 0xff)
+    } else if status & 0x7f == 0 {
+        (CLD_EXITED, (status >> 8) & 0xff)
+    } else if status & 0x80 != 0 {
+        (CLD_DUMPED, status & 0x7f)
+    } else {
+        (CLD_KILLED, status & 0x7f)
+    };
+    let mut info = SigInfo::new(Signal::SIGCHLD as usize, code, pid);
+    info.status = si_status as i32;
+    info
+}
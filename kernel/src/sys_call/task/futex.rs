@@ -1,69 +1,481 @@
-use crate::{memory::addr::UserAddr, task::task::Task, runtime_err::RuntimeError};
-
-bitflags! {
-    struct FutexFlags: u32 {
-        const WAIT      = 0;
-        const WAKE      = 1;
-        const REQUEUE   = 3;
-        const FUTEX_WAKE_OP = 5;
-        const LOCK_PI   = 6;
-        const UNLOCK_PI = 7;
-        const PRIVATE   = 0x80;
-    }
-}
+use crate::sync::rwlock::RwLockWriteGuard;
+use core::mem::size_of;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::{memory::addr::UserAddr, task::{task::{Task, TaskInner, TaskStatus}, task_scheduler::{get_current_task, get_task_by_tid, requeue_task}, futex::{FutexState, FutexTable, FUTEX_TABLE, FUTEX_BITSET_MATCH_ANY, FUTEX_WAITERS, FUTEX_OWNER_DIED, FUTEX_TID_MASK, RobustListHead, futex_wake}}, runtime_err::RuntimeError, interrupt::timer::{TimeSpec, get_time_us}, sys_call::consts::{EINVAL, EAGAIN, EFAULT, ENOSYS, ETIMEDOUT, EPERM, ESRCH, EDEADLK}};
+
+// futex 操作
+const FUTEX_WAIT: u32 = 0;
+const FUTEX_WAKE: u32 = 1;
+const FUTEX_REQUEUE: u32 = 3;
+const FUTEX_CMP_REQUEUE: u32 = 4;
+const FUTEX_WAKE_OP: u32 = 5;
+const FUTEX_LOCK_PI: u32 = 6;
+const FUTEX_UNLOCK_PI: u32 = 7;
+const FUTEX_TRYLOCK_PI: u32 = 8;
+const FUTEX_WAIT_BITSET: u32 = 9;
+const FUTEX_WAKE_BITSET: u32 = 10;
+
+// futex 操作中的标志位
+const FUTEX_PRIVATE_FLAG: u32 = 128;
+const FUTEX_CLOCK_REALTIME: u32 = 256;
+
+// FUTEX_WAKE_OP 中对uaddr2的操作
+const FUTEX_OP_SET: u32 = 0;
+const FUTEX_OP_ADD: u32 = 1;
+const FUTEX_OP_OR: u32 = 2;
+const FUTEX_OP_ANDN: u32 = 3;
+const FUTEX_OP_XOR: u32 = 4;
+const FUTEX_OP_OPARG_SHIFT: u32 = 8;
+
+// FUTEX_WAKE_OP 中对uaddr2原来的值的比较
+const FUTEX_OP_CMP_EQ: u32 = 0;
+const FUTEX_OP_CMP_NE: u32 = 1;
+const FUTEX_OP_CMP_LT: u32 = 2;
+const FUTEX_OP_CMP_LE: u32 = 3;
+const FUTEX_OP_CMP_GT: u32 = 4;
+const FUTEX_OP_CMP_GE: u32 = 5;
+
+// robust链表的最大长度 防止用户态的链表成环
+const ROBUST_LIST_LIMIT: usize = 2048;
 
 impl Task {
-    // wait for futex
-    pub fn sys_futex(&self, uaddr: UserAddr<i32>, op: u32, value: i32, _value2: usize, _value3: usize) -> Result<(), RuntimeError> {
+    // futex 操作 目前所有的futex都以物理地址为key 私有futex与共享futex的处理相同
+    pub fn sys_futex(&self, uaddr: UserAddr<i32>, op: u32, value: i32, value2: usize,
+            uaddr2: UserAddr<i32>, value3: u32) -> Result<(), RuntimeError> {
         debug!("sys_futex uaddr: {:#x} op: {:#x} value: {:#x}", uaddr.bits(), op, value);
-        // let uaddr_ref = uaddr.transfer();
-        // let op = FutexFlags::from_bits_truncate(op);
-        // let mut inner = self.inner.borrow_mut();
-        // let process = inner.process.borrow_mut();
-
-        // let op = op - FutexFlags::PRIVATE;
-        // debug!(
-        //     "Futex uaddr: {:#x}, op: {:?}, val: {:#x}, val2(timeout_addr): {:x}",
-        //     uaddr.bits(), op, value, value2,
-        // );
-        // match op {
-        //     FutexFlags::WAIT => {
-        //         if *uaddr_ref == value {
-        //             drop(process);
-        //             debug!("等待进程");
-        //             inner.context.x[10] = 0;
-        //             inner.status = TaskStatus::WAITING;
-        //             drop(inner);
-        //             // futex_wait(uaddr.bits());
-        //             switch_next();
-        //         } else {
-        //             // *uaddr_value -= 1;
-        //             drop(process);
-        //             inner.context.x[10] = 0;
-        //         }
-        //     },
-        //     FutexFlags::WAKE => {
-        //         // *uaddr_value = -1;
-        //         drop(process);
-        //         debug!("debug for ");
-        //         // 值为唤醒的线程数
-        //         // let count = futex_wake(uaddr.bits(), value as usize);
-        //         // inner.context.x[10] = count;
-        //         // debug!("wake count : {}", count);
-        //         drop(inner);
-        //         switch_next();
-        //     }
-        //     FutexFlags::REQUEUE => {
-        //         drop(process);
-        //         inner.context.x[10] = 0;
-
-        //     }
-        //     _ => todo!(),
-        // }
-        // if op.contains(FutexFlags::WAKE) {
-        //     // *uaddr_value = 0;
-        //     // futex_requeue(uaddr.bits(), value as u32, value2, value3 as u32);
-        // }
+        let cmd = op & !(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME);
+        // 等待数量为负数时不唤醒任务
+        let count = if value < 0 { 0 } else { value as usize };
+        let ret = match cmd {
+            FUTEX_WAIT => return self.futex_wait(uaddr, value, value2.into(), FUTEX_BITSET_MATCH_ANY, false),
+            FUTEX_WAIT_BITSET => return self.futex_wait(uaddr, value, value2.into(), value3, true),
+            FUTEX_WAKE => self.futex_wake(uaddr, count, FUTEX_BITSET_MATCH_ANY),
+            FUTEX_WAKE_BITSET => self.futex_wake(uaddr, count, value3),
+            FUTEX_REQUEUE => self.futex_requeue(uaddr, count, value2, uaddr2, None),
+            FUTEX_CMP_REQUEUE => self.futex_requeue(uaddr, count, value2, uaddr2, Some(value3 as i32)),
+            FUTEX_WAKE_OP => self.futex_wake_op(uaddr, count, value2, uaddr2, value3),
+            FUTEX_LOCK_PI => return self.futex_lock_pi(uaddr, value2.into()),
+            FUTEX_TRYLOCK_PI => self.futex_trylock_pi(uaddr),
+            FUTEX_UNLOCK_PI => self.futex_unlock_pi(uaddr),
+            _ => ENOSYS
+        };
+        self.inner.write().context.x[10] = ret;
+        Ok(())
+    }
+
+    // 在uaddr上等待 uaddr的值不等于value时返回EAGAIN
+    // 被唤醒后重新执行系统调用 根据futex状态判断是否被唤醒或超时
+    fn futex_wait(&self, uaddr: UserAddr<i32>, value: i32, timeout: UserAddr<TimeSpec>,
+            bitset: u32, absolute: bool) -> Result<(), RuntimeError> {
+        let mut inner = self.inner.write();
+        let state = self.futex.lock().state;
+        match state {
+            FutexState::Woken => {
+                self.futex.lock().state = FutexState::None;
+                inner.wake_time = 0;
+                inner.context.x[10] = 0;
+                return Ok(());
+            }
+            FutexState::Waiting(key) => return self.futex_wait_again(inner, key),
+            FutexState::None => {}
+        }
+
+        if bitset == 0 {
+            inner.context.x[10] = EINVAL;
+            return Ok(());
+        }
+        let key = match futex_key(self, uaddr) {
+            Ok(key) => key,
+            Err(err) => {
+                inner.context.x[10] = err;
+                return Ok(());
+            }
+        };
+        // 持有futex表时比较并进入等待 唤醒者修改值后获取futex表 不会丢失唤醒
+        let mut futex_table = FUTEX_TABLE.lock();
+        if *uaddr.transfer() != value {
+            inner.context.x[10] = EAGAIN;
+            return Ok(());
+        }
+        // FUTEX_WAIT 的超时为相对时间 FUTEX_WAIT_BITSET 的超时为绝对时间
+        if timeout.is_valid() {
+            let timeout = timeout.transfer().as_us();
+            let deadline = if absolute { timeout } else { get_time_us() + timeout };
+            if get_time_us() >= deadline {
+                inner.context.x[10] = ETIMEDOUT;
+                return Ok(());
+            }
+            inner.wake_time = deadline;
+            get_current_task().unwrap().wake_at(deadline);
+        }
+        inner.context.sepc -= 4;
+        drop(inner);
+        futex_table.wait(key, get_current_task().unwrap(), bitset);
+        Err(RuntimeError::ChangeTask)
+    }
+
+    // 重新执行时仍在等待 到达超时时间后返回ETIMEDOUT 否则继续等待
+    fn futex_wait_again(&self, mut inner: RwLockWriteGuard<TaskInner>, key: usize) -> Result<(), RuntimeError> {
+        if inner.wake_time != 0 && get_time_us() >= inner.wake_time {
+            self.futex.lock().state = FutexState::None;
+            inner.wake_time = 0;
+            inner.context.x[10] = ETIMEDOUT;
+            drop(inner);
+            FUTEX_TABLE.lock().remove(key, self.tid);
+            return Ok(());
+        }
+        inner.context.sepc -= 4;
+        // 在此之前的唤醒由调度器补上
+        self.set_status(TaskStatus::WAITING);
+        Err(RuntimeError::ChangeTask)
+    }
+
+    // 获取PI锁 锁被占用时提升持有者的优先级并等待 解锁时持有者直接把锁交给等待者
+    fn futex_lock_pi(&self, uaddr: UserAddr<i32>, timeout: UserAddr<TimeSpec>) -> Result<(), RuntimeError> {
+        let mut inner = self.inner.write();
+        let state = self.futex.lock().state;
+        match state {
+            FutexState::Woken => {
+                self.futex.lock().state = FutexState::None;
+                inner.wake_time = 0;
+                // 持有者解锁时已经把锁交给当前任务 持有者退出时被唤醒需要重新获取
+                if *uaddr.transfer() as u32 & FUTEX_TID_MASK == self.tid as u32 {
+                    inner.context.x[10] = 0;
+                    return Ok(());
+                }
+            }
+            FutexState::Waiting(key) => return self.futex_wait_again(inner, key),
+            FutexState::None => {}
+        }
+
+        let key = match futex_key(self, uaddr) {
+            Ok(key) => key,
+            Err(err) => {
+                inner.context.x[10] = err;
+                return Ok(());
+            }
+        };
+        // 持有futex表时检查持有者并进入等待 持有者解锁时获取futex表
+        let mut futex_table = FUTEX_TABLE.lock();
+        let owner = match pi_try_acquire(&futex_table, uaddr, key, self.tid) {
+            Ok(_) => {
+                inner.context.x[10] = 0;
+                return Ok(());
+            }
+            Err(owner) => owner
+        };
+        if owner == self.tid {
+            inner.context.x[10] = EDEADLK;
+            return Ok(());
+        }
+        let owner = match get_task_by_tid(owner) {
+            Some(owner) => owner,
+            None => {
+                inner.context.x[10] = ESRCH;
+                return Ok(());
+            }
+        };
+        // FUTEX_LOCK_PI 的超时为绝对时间
+        if timeout.is_valid() {
+            let deadline = timeout.transfer().as_us();
+            if get_time_us() >= deadline {
+                inner.context.x[10] = ETIMEDOUT;
+                return Ok(());
+            }
+            inner.wake_time = deadline;
+            get_current_task().unwrap().wake_at(deadline);
+        }
+        // 设置等待标志 持有者解锁时进入内核
+        let value = uaddr.transfer();
+        *value = (*value as u32 | FUTEX_WAITERS) as i32;
+
+        inner.context.sepc -= 4;
+        drop(inner);
+        // 持有者继承等待者的优先级
+        let mut owner_futex = owner.futex.lock();
+        if !owner_futex.pi_locks.contains(&key) {
+            owner_futex.pi_locks.push(key);
+        }
+        drop(owner_futex);
+        let sched = *self.sched.lock();
+        let boosted = owner.sched.lock().inherit(&sched);
+        futex_table.wait(key, get_current_task().unwrap(), FUTEX_BITSET_MATCH_ANY);
+        drop(futex_table);
+        if boosted {
+            requeue_task(owner);
+        }
+        Err(RuntimeError::ChangeTask)
+    }
+
+    // 尝试获取PI锁 不等待
+    fn futex_trylock_pi(&self, uaddr: UserAddr<i32>) -> usize {
+        let key = match futex_key(self, uaddr) {
+            Ok(key) => key,
+            Err(err) => return err
+        };
+        match pi_try_acquire(&FUTEX_TABLE.lock(), uaddr, key, self.tid) {
+            Ok(_) => 0,
+            Err(owner) if owner == self.tid => EDEADLK,
+            Err(_) => EAGAIN
+        }
+    }
+
+    // 释放PI锁 恢复当前任务的优先级 把锁交给第一个等待者
+    fn futex_unlock_pi(&self, uaddr: UserAddr<i32>) -> usize {
+        let key = match futex_key(self, uaddr) {
+            Ok(key) => key,
+            Err(err) => return err
+        };
+        let value = uaddr.transfer();
+        if *value as u32 & FUTEX_TID_MASK != self.tid as u32 {
+            return EPERM;
+        }
+        // 当前任务的优先级在放回调度器时生效
+        self.futex.lock().pi_locks.retain(|x| *x != key);
+        self.sched.lock().restore();
+        // 恢复后仍然继承其他持有的PI锁上等待者的优先级
+        pi_inherit(self);
+
+        let mut futex_table = FUTEX_TABLE.lock();
+        let next = match futex_table.pop(key) {
+            Some(next) => next,
+            None => {
+                *value = 0;
+                return 0;
+            }
+        };
+        let waiters = futex_table.waiters(key);
+        // 新的持有者继承剩余等待者的优先级
+        let mut next_futex = next.futex.lock();
+        if !waiters.is_empty() {
+            next_futex.pi_locks.push(key);
+        }
+        next_futex.state = FutexState::Woken;
+        drop(next_futex);
+        *value = (next.tid as u32 | if waiters.is_empty() { 0 } else { FUTEX_WAITERS }) as i32;
+        drop(futex_table);
+        pi_inherit(&next);
+        next.wake();
+        requeue_task(next);
+        0
+    }
+
+    // 设置robust futex链表
+    pub fn sys_set_robust_list(&self, head: UserAddr<RobustListHead>, len: usize) -> Result<(), RuntimeError> {
+        let mut inner = self.inner.write();
+        if len != size_of::<RobustListHead>() {
+            inner.context.x[10] = EINVAL;
+            return Ok(());
+        }
+        inner.robust_list = head;
+        inner.context.x[10] = 0;
+        Ok(())
+    }
+
+    // 获取任务的robust futex链表 tid为0时为当前任务
+    pub fn sys_get_robust_list(&self, tid: usize, head_ptr: UserAddr<usize>, len_ptr: UserAddr<usize>) -> Result<(), RuntimeError> {
+        let head = if tid == 0 || tid == self.tid {
+            Some(self.inner.read().robust_list)
+        } else {
+            get_task_by_tid(tid).map(|x| x.inner.read().robust_list)
+        };
+        let mut inner = self.inner.write();
+        inner.context.x[10] = match head {
+            Some(head) => {
+                *head_ptr.transfer() = head.bits();
+                *len_ptr.transfer() = size_of::<RobustListHead>();
+                0
+            }
+            None => ESRCH
+        };
         Ok(())
-    }    
-}
\ No newline at end of file
+    }
+
+    // 线程退出时释放持有的futex 需要在释放内存之前调用
+    // 标记robust链表中仍然持有的锁为FUTEX_OWNER_DIED 清除ctid并唤醒pthread_join中等待的任务
+    pub fn release_futexes(&self) {
+        let robust_list = self.inner.read().robust_list;
+        if robust_list.is_valid() {
+            self.exit_robust_list(robust_list);
+        }
+        let clear_child_tid = self.clear_child_tid.read().clone();
+        if clear_child_tid.is_valid() {
+            if let Ok(key) = futex_key(self, clear_child_tid.bits().into()) {
+                *clear_child_tid.transfer() = 0;
+                futex_wake(key, 1);
+            }
+        }
+    }
+
+    // 遍历robust链表 链表项的最低位表示是否为PI锁
+    fn exit_robust_list(&self, head_ptr: UserAddr<RobustListHead>) {
+        if !is_user_mapped(self, head_ptr.bits()) {
+            return;
+        }
+        let head = *head_ptr.transfer();
+        let mut entry = head.list;
+        let mut count = 0;
+        while entry != head_ptr.bits() && count < ROBUST_LIST_LIMIT {
+            let entry_addr = entry & !1;
+            if !is_user_mapped(self, entry_addr) {
+                break;
+            }
+            let next = *UserAddr::<usize>::from(entry_addr).transfer();
+            // 正在操作的项最后处理
+            if entry != head.list_op_pending {
+                self.handle_futex_death((entry_addr as isize + head.futex_offset) as usize);
+            }
+            entry = next;
+            count += 1;
+        }
+        if head.list_op_pending != 0 {
+            let entry_addr = head.list_op_pending & !1;
+            self.handle_futex_death((entry_addr as isize + head.futex_offset) as usize);
+        }
+    }
+
+    // 当前任务持有的锁设置FUTEX_OWNER_DIED 有等待者时唤醒一个
+    fn handle_futex_death(&self, uaddr: usize) {
+        let uaddr: UserAddr<i32> = uaddr.into();
+        let key = match futex_key(self, uaddr) {
+            Ok(key) => key,
+            Err(_) => return
+        };
+        let value = uaddr.transfer();
+        let old_value = *value as u32;
+        if old_value & FUTEX_TID_MASK != self.tid as u32 {
+            return;
+        }
+        *value = ((old_value & FUTEX_WAITERS) | FUTEX_OWNER_DIED) as i32;
+        if old_value & FUTEX_WAITERS != 0 {
+            futex_wake(key, 1);
+        }
+    }
+
+    // 唤醒uaddr上最多count个任务 返回唤醒的数量
+    fn futex_wake(&self, uaddr: UserAddr<i32>, count: usize, bitset: u32) -> usize {
+        if bitset == 0 {
+            return EINVAL;
+        }
+        let key = match futex_key(self, uaddr) {
+            Ok(key) => key,
+            Err(err) => return err
+        };
+        FUTEX_TABLE.lock().wake(key, count, bitset)
+    }
+
+    // 唤醒uaddr上最多count个任务 并把最多requeue_count个任务移动到uaddr2上
+    // cmp_value存在时先比较uaddr的值
+    fn futex_requeue(&self, uaddr: UserAddr<i32>, count: usize, requeue_count: usize,
+            uaddr2: UserAddr<i32>, cmp_value: Option<i32>) -> usize {
+        let (key, key2) = match (futex_key(self, uaddr), futex_key(self, uaddr2)) {
+            (Ok(key), Ok(key2)) => (key, key2),
+            (Err(err), _) | (_, Err(err)) => return err
+        };
+        // 持有futex表时比较 与等待者的比较互斥
+        let mut futex_table = FUTEX_TABLE.lock();
+        if let Some(cmp_value) = cmp_value {
+            if *uaddr.transfer() != cmp_value {
+                return EAGAIN;
+            }
+        }
+        // requeue 的数量通过超时参数传递 为int类型
+        let requeue_count = requeue_count as i32;
+        let requeue_count = if requeue_count < 0 { 0 } else { requeue_count as usize };
+        futex_table.requeue(key, key2, count, requeue_count)
+    }
+
+    // 修改uaddr2的值 唤醒uaddr上最多count个任务
+    // uaddr2原来的值满足比较条件时 再唤醒uaddr2上最多count2个任务
+    fn futex_wake_op(&self, uaddr: UserAddr<i32>, count: usize, count2: usize,
+            uaddr2: UserAddr<i32>, encoded_op: u32) -> usize {
+        let (key, key2) = match (futex_key(self, uaddr), futex_key(self, uaddr2)) {
+            (Ok(key), Ok(key2)) => (key, key2),
+            (Err(err), _) | (_, Err(err)) => return err
+        };
+
+        // 解析操作 op:4 cmp:4 oparg:12 cmparg:12 参数为有符号数
+        let op = (encoded_op >> 28) & 0xf;
+        let cmp = (encoded_op >> 24) & 0xf;
+        let mut oparg = ((encoded_op << 8) as i32) >> 20;
+        let cmparg = ((encoded_op << 20) as i32) >> 20;
+        if op & FUTEX_OP_OPARG_SHIFT != 0 {
+            if oparg < 0 || oparg > 31 {
+                return EINVAL;
+            }
+            oparg = 1 << oparg;
+        }
+
+        let target = uaddr2.transfer();
+        let old_value = *target;
+        *target = match op & !FUTEX_OP_OPARG_SHIFT {
+            FUTEX_OP_SET => oparg,
+            FUTEX_OP_ADD => old_value.wrapping_add(oparg),
+            FUTEX_OP_OR => old_value | oparg,
+            FUTEX_OP_ANDN => old_value & !oparg,
+            FUTEX_OP_XOR => old_value ^ oparg,
+            _ => return ENOSYS
+        };
+        let matched = match cmp {
+            FUTEX_OP_CMP_EQ => old_value == cmparg,
+            FUTEX_OP_CMP_NE => old_value != cmparg,
+            FUTEX_OP_CMP_LT => old_value < cmparg,
+            FUTEX_OP_CMP_LE => old_value <= cmparg,
+            FUTEX_OP_CMP_GT => old_value > cmparg,
+            FUTEX_OP_CMP_GE => old_value >= cmparg,
+            _ => return ENOSYS
+        };
+
+        let mut futex_table = FUTEX_TABLE.lock();
+        let mut woken = futex_table.wake(key, count, FUTEX_BITSET_MATCH_ANY);
+        if matched {
+            // 第二个唤醒数量通过超时参数传递 为int类型
+            let count2 = count2 as i32;
+            let count2 = if count2 < 0 { 0 } else { count2 as usize };
+            woken += futex_table.wake(key2, count2, FUTEX_BITSET_MATCH_ANY);
+        }
+        woken
+    }
+}
+
+// 获取futex的key 用户地址需要4字节对齐并且已经映射
+pub fn futex_key(task: &Task, uaddr: UserAddr<i32>) -> Result<usize, usize> {
+    if uaddr.bits() % 4 != 0 {
+        return Err(EINVAL);
+    }
+    let process = task.process.read();
+    let mm = process.mm.read();
+    mm.pmm.get_phys_addr(uaddr.bits().into()).map(|x| x.0).map_err(|_| EFAULT)
+}
+
+// 判断用户地址是否已经映射
+fn is_user_mapped(task: &Task, addr: usize) -> bool {
+    task.process.read().mm.read().pmm.get_phys_addr(addr.into()).is_ok()
+}
+
+// 尝试获取PI锁 没有持有者时写入tid并保留OWNER_DIED标志 失败时返回持有者的tid
+fn pi_try_acquire(futex_table: &FutexTable, uaddr: UserAddr<i32>, key: usize, tid: usize) -> Result<(), usize> {
+    let value = uaddr.transfer();
+    let owner = *value as u32 & FUTEX_TID_MASK;
+    if owner != 0 {
+        return Err(owner as usize);
+    }
+    let waiters = if futex_table.waiters(key).is_empty() { 0 } else { FUTEX_WAITERS };
+    *value = (tid as u32 | (*value as u32 & FUTEX_OWNER_DIED) | waiters) as i32;
+    Ok(())
+}
+
+// 继承任务持有的所有PI锁上等待者中最高的优先级
+fn pi_inherit(task: &Task) {
+    let keys = task.futex.lock().pi_locks.clone();
+    let waiters: Vec<Arc<Task>> = {
+        let futex_table = FUTEX_TABLE.lock();
+        keys.iter().flat_map(|key| futex_table.waiters(*key)).collect()
+    };
+    for waiter in waiters {
+        let sched = *waiter.sched.lock();
+        task.sched.lock().inherit(&sched);
+    }
+}
@@ -3,7 +3,7 @@ use crate::{runtime_err::RuntimeError, sys_call::{SYS_CALL_ERR, UTSname}, task::
 impl Task {
     // 获取系统信息
     pub fn sys_uname(&self, ptr: UserAddr<UTSname>) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.write();
     
         // 获取参数
         let sys_info = ptr.transfer();
@@ -34,24 +34,21 @@ impl Task {
     
     // 获取pid
     pub fn sys_getpid(&self) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.write();
         inner.context.x[10] = self.pid;
         Ok(())
     }
     
     // 获取父id
     pub fn sys_getppid(&self) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.clone();
-        let process = process.borrow();
+        let mut inner = self.inner.write();
+        // 持有子进程时不能获取父进程
+        let parent = self.process.read().parent.as_ref().and_then(|x| x.upgrade());
 
-        inner.context.x[10] = match &process.parent {
-            Some(parent) => {
-                let parent = parent.upgrade().unwrap();
-                let x = parent.borrow().pid; 
-                x
-            },
-            None => SYS_CALL_ERR
+        // 孤儿进程由init进程收养 只有init进程没有父进程
+        inner.context.x[10] = match parent {
+            Some(parent) => parent.read().pid,
+            None => 0
         };
 
         Ok(())
@@ -59,13 +56,13 @@ impl Task {
     
     // 获取线程id
     pub fn sys_gettid(&self) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.write();
         inner.context.x[10] = self.tid;
         Ok(())
     }
 
     pub fn sys_getrusage(&self, _who: usize, usage: UserAddr<Rusage>) -> Result<(), RuntimeError>{
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.write();
         let usage = usage.transfer();
         usage.ru_stime = TimeSpec::now();
         usage.ru_utime = TimeSpec::now();
@@ -77,8 +74,8 @@ impl Task {
     pub fn sys_set_tid_address(&self, tid_ptr: UserAddr<u32>) -> Result<(), RuntimeError> {
         // 测试写入用户空间
         let tid_ptr = tid_ptr.transfer();
-        let mut inner = self.inner.borrow_mut();
-        let clear_child_tid = self.clear_child_tid.borrow().clone();
+        let mut inner = self.inner.write();
+        let clear_child_tid = self.clear_child_tid.read().clone();
 
         *tid_ptr = if clear_child_tid.is_valid() {
             clear_child_tid.transfer().clone()
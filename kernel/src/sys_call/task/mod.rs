@@ -1,4 +1,8 @@
 pub mod exit;
 pub mod futex;
 pub mod info;
-pub mod fork;
\ No newline at end of file
+pub mod fork;
+pub mod sched;
+pub mod session;
+pub mod rlimit;
+pub mod cred;
\ No newline at end of file
@@ -0,0 +1,70 @@
+use crate::{runtime_err::RuntimeError, memory::addr::UserAddr, sys_call::consts::{EINVAL, EPERM, ESRCH}, task::{task::Task, task_scheduler::get_process_by_pid, rlimit::{RLimit, RLIM_NLIMITS, RLIMIT_NOFILE, NR_OPEN}}};
+
+impl Task {
+    // 获取资源限制
+    pub fn sys_getrlimit(&self, resource: usize, rlim: UserAddr<RLimit>) -> Result<(), RuntimeError> {
+        self.sys_prlimit64(0, resource, UserAddr::from(0), rlim)
+    }
+
+    // 设置资源限制
+    pub fn sys_setrlimit(&self, resource: usize, rlim: UserAddr<RLimit>) -> Result<(), RuntimeError> {
+        self.sys_prlimit64(0, resource, rlim, UserAddr::from(0))
+    }
+
+    // 获取并设置进程的资源限制 pid为0时为当前进程
+    pub fn sys_prlimit64(&self, pid: usize, resource: usize, new_limit: UserAddr<RLimit>,
+            old_limit: UserAddr<RLimit>) -> Result<(), RuntimeError> {
+        debug!("prlimit64 pid: {} resource: {}", pid, resource);
+        if resource >= RLIM_NLIMITS {
+            self.update_context(|x| x.x[10] = EINVAL);
+            return Ok(());
+        }
+        let process = if pid == 0 || pid == self.pid {
+            Some(self.get_process())
+        } else {
+            get_process_by_pid(pid)
+        };
+        let process = match process {
+            Some(process) => process,
+            None => {
+                self.update_context(|x| x.x[10] = ESRCH);
+                return Ok(());
+            }
+        };
+        // 修改其他用户的进程或提高硬限制需要特权
+        let cred = self.get_process().read().cred.clone();
+        let mut process = process.write();
+        if process.pid != self.pid && !cred.can_signal(&process.cred) {
+            drop(process);
+            self.update_context(|x| x.x[10] = EPERM);
+            return Ok(());
+        }
+        let new = if new_limit.is_valid() { Some(*new_limit.transfer()) } else { None };
+        // 软限制不能超过硬限制 文件描述符数量不能超过NR_OPEN
+        if let Some(new) = new {
+            let result = if new.rlim_cur > new.rlim_max {
+                EINVAL
+            } else if resource == RLIMIT_NOFILE && new.rlim_max > NR_OPEN {
+                EPERM
+            } else if new.rlim_max > process.rlimits[resource].rlim_max && !cred.is_root() {
+                EPERM
+            } else {
+                0
+            };
+            if result != 0 {
+                drop(process);
+                self.inner.write().context.x[10] = result;
+                return Ok(());
+            }
+        }
+        if old_limit.is_valid() {
+            *old_limit.transfer() = process.rlimits[resource];
+        }
+        if let Some(new) = new {
+            process.rlimits[resource] = new;
+        }
+        drop(process);
+        self.update_context(|x| x.x[10] = 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+use alloc::sync::Arc;
+use crate::{runtime_err::RuntimeError, memory::addr::UserAddr, sys_call::consts::{EACCES, EINVAL, EPERM, ESRCH}, task::{task::Task, rlimit::{RLIMIT_NICE, RLIMIT_RTPRIO, RLIM_INFINITY}, task_scheduler::{get_current_task, get_task_by_tid, get_process_tasks, requeue_task}, scheduler::{SCHED_OTHER, SCHED_FIFO, SCHED_RR, SCHED_BATCH, SCHED_IDLE, MIN_RT_PRIO, MAX_RT_PRIO, MIN_NICE, MAX_NICE}}};
+
+// setpriority/getpriority 的 which 参数 目前只支持进程
+const PRIO_PROCESS: usize = 0;
+
+// sched_setscheduler 的 policy 中的标志位
+const SCHED_RESET_ON_FORK: usize = 0x40000000;
+
+#[repr(C)]
+pub struct SchedParam {
+    pub sched_priority: i32
+}
+
+impl Task {
+    // 根据tid获取任务 tid为0时为当前任务
+    fn get_sched_task(&self, tid: usize) -> Option<Arc<Task>> {
+        if tid == 0 || tid == self.tid {
+            get_current_task()
+        } else {
+            get_task_by_tid(tid)
+        }
+    }
+
+    // 非root用户只能修改有效用户与目标的实际或有效用户相同的任务
+    fn same_owner(&self, task: &Task) -> bool {
+        let process = self.get_process();
+        let process = process.read();
+        let target = task.get_process();
+        let target = target.read();
+        process.cred.is_root() || process.cred.euid == target.cred.uid || process.cred.euid == target.cred.euid
+    }
+
+    // 获取当前进程的资源软限制 root用户不受限制
+    fn sched_limit(&self, resource: usize) -> Option<usize> {
+        let process = self.get_process();
+        let process = process.read();
+        if process.cred.is_root() {
+            None
+        } else {
+            Some(process.rlimits[resource].rlim_cur)
+        }
+    }
+
+    // 设置nice值
+    pub fn sys_setpriority(&self, which: usize, who: usize, prio: usize) -> Result<(), RuntimeError> {
+        if which != PRIO_PROCESS {
+            self.update_context(|x| x.x[10] = EINVAL);
+            return Ok(());
+        }
+        let pid = if who == 0 { self.pid } else { who };
+        let tasks = get_process_tasks(pid);
+        if tasks.len() == 0 {
+            self.update_context(|x| x.x[10] = ESRCH);
+            return Ok(());
+        }
+        // nice超出范围时取边界值
+        let nice = prio as isize;
+        let nice = if nice < MIN_NICE { MIN_NICE } else if nice > MAX_NICE { MAX_NICE } else { nice };
+        if !self.same_owner(&tasks[0]) {
+            self.update_context(|x| x.x[10] = EPERM);
+            return Ok(());
+        }
+        // 降低nice值时不能低于 20 - RLIMIT_NICE
+        let lowered = tasks.iter().any(|task| {
+            let sched = *task.sched.lock();
+            nice < sched.pi_base.map_or(sched.nice, |x| x.2)
+        });
+        let limited = self.sched_limit(RLIMIT_NICE).map_or(false, |limit| {
+            limit != RLIM_INFINITY && ((20 - nice) as usize) > limit
+        });
+        if lowered && limited {
+            self.update_context(|x| x.x[10] = EACCES);
+            return Ok(());
+        }
+        for task in tasks {
+            let mut sched = task.sched.lock();
+            // 优先级继承期间修改继承前的值
+            match &mut sched.pi_base {
+                Some(base) => base.2 = nice,
+                None => sched.nice = nice
+            }
+            drop(sched);
+            requeue_task(task);
+        }
+        self.update_context(|x| x.x[10] = 0);
+        Ok(())
+    }
+
+    // 获取nice值 返回 20 - nice 由libc转换
+    pub fn sys_getpriority(&self, which: usize, who: usize) -> Result<(), RuntimeError> {
+        if which != PRIO_PROCESS {
+            self.update_context(|x| x.x[10] = EINVAL);
+            return Ok(());
+        }
+        let pid = if who == 0 { self.pid } else { who };
+        // 多个任务时返回优先级最高的
+        let nice = get_process_tasks(pid).iter().map(|x| x.sched.lock().nice).min();
+        let mut inner = self.inner.write();
+        inner.context.x[10] = match nice {
+            Some(nice) => (20 - nice) as usize,
+            None => ESRCH
+        };
+        Ok(())
+    }
+
+    // 设置调度策略
+    pub fn sys_sched_setscheduler(&self, pid: usize, policy: usize, param: UserAddr<SchedParam>) -> Result<(), RuntimeError> {
+        if !param.is_valid() {
+            self.update_context(|x| x.x[10] = EINVAL);
+            return Ok(());
+        }
+        let priority = param.transfer().sched_priority as usize;
+        self.set_scheduler(pid, Some(policy & !SCHED_RESET_ON_FORK), priority)
+    }
+
+    // 设置调度参数
+    pub fn sys_sched_setparam(&self, pid: usize, param: UserAddr<SchedParam>) -> Result<(), RuntimeError> {
+        if !param.is_valid() {
+            self.update_context(|x| x.x[10] = EINVAL);
+            return Ok(());
+        }
+        let priority = param.transfer().sched_priority as usize;
+        self.set_scheduler(pid, None, priority)
+    }
+
+    // 设置调度策略和实时优先级 policy为None时保持原来的策略
+    fn set_scheduler(&self, pid: usize, policy: Option<usize>, priority: usize) -> Result<(), RuntimeError> {
+        let task = match self.get_sched_task(pid) {
+            Some(task) => task,
+            None => {
+                self.update_context(|x| x.x[10] = ESRCH);
+                return Ok(());
+            }
+        };
+        if !self.same_owner(&task) {
+            self.update_context(|x| x.x[10] = EPERM);
+            return Ok(());
+        }
+        let rtprio_limit = self.sched_limit(RLIMIT_RTPRIO);
+        let mut sched = task.sched.lock();
+        let policy = policy.unwrap_or(sched.pi_base.map_or(sched.policy, |x| x.0));
+        // 实时任务的优先级为1~99 其他任务的优先级为0
+        let valid = match policy {
+            SCHED_FIFO | SCHED_RR => priority >= MIN_RT_PRIO && priority <= MAX_RT_PRIO,
+            SCHED_OTHER | SCHED_BATCH | SCHED_IDLE => priority == 0,
+            _ => false
+        };
+        if !valid {
+            drop(sched);
+            self.update_context(|x| x.x[10] = EINVAL);
+            return Ok(());
+        }
+        // 非root用户提高实时优先级时不能超过RLIMIT_RTPRIO
+        let current = sched.pi_base.map_or(sched.rt_priority, |x| x.1);
+        let limited = rtprio_limit.map_or(false, |limit| {
+            limit != RLIM_INFINITY && priority > current && priority > limit
+        });
+        if limited {
+            drop(sched);
+            self.update_context(|x| x.x[10] = EPERM);
+            return Ok(());
+        }
+        // 优先级继承期间修改继承前的值
+        match &mut sched.pi_base {
+            Some(base) => {
+                base.0 = policy;
+                base.1 = priority;
+            }
+            None => {
+                sched.policy = policy;
+                sched.rt_priority = priority;
+            }
+        }
+        drop(sched);
+        requeue_task(task);
+        self.update_context(|x| x.x[10] = 0);
+        Ok(())
+    }
+
+    // 获取调度策略
+    pub fn sys_sched_getscheduler(&self, pid: usize) -> Result<(), RuntimeError> {
+        let policy = self.get_sched_task(pid).map(|x| x.sched.lock().policy);
+        let mut inner = self.inner.write();
+        inner.context.x[10] = policy.unwrap_or(ESRCH);
+        Ok(())
+    }
+
+    // 获取调度参数
+    pub fn sys_sched_getparam(&self, pid: usize, param: UserAddr<SchedParam>) -> Result<(), RuntimeError> {
+        if !param.is_valid() {
+            self.update_context(|x| x.x[10] = EINVAL);
+            return Ok(());
+        }
+        let priority = self.get_sched_task(pid).map(|x| x.sched.lock().rt_priority);
+        let mut inner = self.inner.write();
+        inner.context.x[10] = match priority {
+            Some(priority) => {
+                param.transfer().sched_priority = priority as i32;
+                0
+            }
+            None => ESRCH
+        };
+        Ok(())
+    }
+
+    // 获取策略的最大优先级
+    pub fn sys_sched_get_priority_max(&self, policy: usize) -> Result<(), RuntimeError> {
+        let mut inner = self.inner.write();
+        inner.context.x[10] = match policy {
+            SCHED_FIFO | SCHED_RR => MAX_RT_PRIO,
+            SCHED_OTHER | SCHED_BATCH | SCHED_IDLE => 0,
+            _ => EINVAL
+        };
+        Ok(())
+    }
+
+    // 获取策略的最小优先级
+    pub fn sys_sched_get_priority_min(&self, policy: usize) -> Result<(), RuntimeError> {
+        let mut inner = self.inner.write();
+        inner.context.x[10] = match policy {
+            SCHED_FIFO | SCHED_RR => MIN_RT_PRIO,
+            SCHED_OTHER | SCHED_BATCH | SCHED_IDLE => 0,
+            _ => EINVAL
+        };
+        Ok(())
+    }
+}
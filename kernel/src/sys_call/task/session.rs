@@ -0,0 +1,94 @@
+use crate::sync::rwlock::RwLock;
+use alloc::sync::Arc;
+use crate::{runtime_err::RuntimeError, sys_call::consts::{EINVAL, EPERM, ESRCH}, task::{task::Task, process::Process, task_scheduler::{get_all_processes, get_process_by_pid}}};
+
+impl Task {
+    // 根据pid获取进程 pid为0时为当前进程
+    fn get_pgrp_process(&self, pid: usize) -> Option<Arc<RwLock<Process>>> {
+        if pid == 0 || pid == self.pid {
+            Some(self.get_process())
+        } else {
+            get_process_by_pid(pid)
+        }
+    }
+
+    // 设置进程组 只能设置当前进程和子进程 pgid为0时使用目标进程的pid
+    pub fn sys_setpgid(&self, pid: usize, pgid: usize) -> Result<(), RuntimeError> {
+        if (pgid as isize) < 0 {
+            self.update_context(|x| x.x[10] = EINVAL);
+            return Ok(());
+        }
+        let process_rc = self.get_process();
+        let process = process_rc.read();
+        let target = if pid == 0 || pid == self.pid {
+            Some(process_rc.clone())
+        } else {
+            process.children.iter().find(|x| x.read().pid == pid).cloned()
+        };
+        let target = match target {
+            Some(target) => target,
+            None => {
+                drop(process);
+                self.update_context(|x| x.x[10] = ESRCH);
+                return Ok(());
+            }
+        };
+        let sid = process.sid;
+        drop(process);
+
+        let (target_pid, target_sid) = {
+            let target = target.read();
+            (target.pid, target.sid)
+        };
+        let pgid = if pgid == 0 { target_pid } else { pgid };
+        // 会话首进程无法改变进程组 只能加入同一会话中的进程组
+        let valid = target_pid != target_sid && target_sid == sid && (pgid == target_pid
+            || get_all_processes().iter().any(|x| {
+                let x = x.read();
+                x.pgid == pgid && x.sid == sid
+            }));
+        if !valid {
+            self.update_context(|x| x.x[10] = EPERM);
+            return Ok(());
+        }
+        target.write().pgid = pgid;
+        self.update_context(|x| x.x[10] = 0);
+        Ok(())
+    }
+
+    // 获取进程组
+    pub fn sys_getpgid(&self, pid: usize) -> Result<(), RuntimeError> {
+        let pgid = self.get_pgrp_process(pid).map(|x| x.read().pgid);
+        let mut inner = self.inner.write();
+        inner.context.x[10] = pgid.unwrap_or(ESRCH);
+        Ok(())
+    }
+
+    // 创建新的会话 当前进程成为会话和进程组的首进程
+    pub fn sys_setsid(&self) -> Result<(), RuntimeError> {
+        let process_rc = self.get_process();
+        // 进程组首进程无法创建会话 检查其他进程时不持有当前进程的锁
+        let is_leader = get_all_processes().iter()
+            .any(|x| !Arc::ptr_eq(x, &process_rc) && x.read().pgid == self.pid);
+        let mut process = process_rc.write();
+        let result = if process.pgid == self.pid || is_leader {
+            EPERM
+        } else {
+            process.pgid = self.pid;
+            process.sid = self.pid;
+            self.pid
+        };
+        drop(process);
+        let mut inner = self.inner.write();
+        inner.context.x[10] = result;
+        Ok(())
+    }
+
+    // 获取会话
+    pub fn sys_getsid(&self, pid: usize) -> Result<(), RuntimeError> {
+        let sid = self.get_pgrp_process(pid).map(|x| x.read().sid);
+        let mut inner = self.inner.write();
+        inner.context.x[10] = sid.unwrap_or(ESRCH);
+        Ok(())
+    }
+}
@@ -1,47 +1,154 @@
 use crate::runtime_err::RuntimeError;
 use crate::task::task::Task;
+use crate::task::task::TaskStatus;
 use crate::task::fd_table::FD_CWD;
 use crate::interrupt::timer::{get_time_us, TimeSpec};
+use crate::interrupt::Context;
+use crate::sys_call::{SYS_NANOSLEEP, SYS_CLOCK_NANOSLEEP};
 use crate::interrupt::timer::TMS;
 use crate::memory::addr::{VirtAddr, UserAddr};
 use crate::fs::filetree::INode;
 use crate::interrupt::timer::get_ticks;
+use crate::interrupt::timer::us_to_clock_ticks;
+use crate::interrupt::timer::{ITimerVal, TimerId, add_timer, cancel_timer};
+use crate::task::process::Process;
+use crate::task::signal::Signal;
+use crate::task::task_scheduler::get_current_task;
+use crate::sys_call::consts::EINVAL;
+use crate::sys_call::consts::EFAULT;
+use crate::sync::rwlock::RwLock;
+use alloc::boxed::Box;
+use alloc::sync::{Arc, Weak};
+
+// clock_nanosleep 使用绝对时间
+const TIMER_ABSTIME: usize = 1;
+
+// 间隔定时器类型 目前只支持ITIMER_REAL
+const ITIMER_REAL: usize = 0;
+
+// 睡眠被信号处理函数打断 相对时间的睡眠向rem写入剩余时间
+// 被打断的不是睡眠时返回false
+pub fn interrupt_sleep(context: &Context, wake_time: usize) -> bool {
+    let rem: UserAddr<TimeSpec> = match context.x[17] {
+        SYS_NANOSLEEP => context.x[11].into(),
+        SYS_CLOCK_NANOSLEEP if context.x[11] & TIMER_ABSTIME == 0 => context.x[13].into(),
+        SYS_CLOCK_NANOSLEEP => return true,
+        _ => return false
+    };
+    if rem.is_valid() {
+        *rem.transfer() = TimeSpec::from_us(wake_time.saturating_sub(get_time_us()));
+    }
+    true
+}
 
 impl Task {
     pub fn sys_nanosleep(&self, req_ptr: UserAddr<TimeSpec>, _rem_ptr: VirtAddr) -> Result<(), RuntimeError> {
         let req_time = req_ptr.transfer();
+        self.sleep_until(get_time_us() + req_time.as_us())
+    }
 
-        let mut inner = self.inner.borrow_mut();
+    pub fn sys_clock_nanosleep(&self, _clock_id: usize, flags: usize, req_ptr: UserAddr<TimeSpec>, _rem_ptr: VirtAddr) -> Result<(), RuntimeError> {
+        let req_time = req_ptr.transfer();
+        // 时钟都以启动时间为起点 绝对时间可以直接作为唤醒时间
+        let deadline = if flags & TIMER_ABSTIME != 0 {
+            req_time.as_us()
+        } else {
+            get_time_us() + req_time.as_us()
+        };
+        self.sleep_until(deadline)
+    }
 
-        // 获取文件参数
+    // 睡眠到deadline(微秒) 由定时器唤醒后重新执行系统调用
+    // 被信号打断时在处理信号时写入剩余时间 重新执行时仍在原来的时间唤醒
+    fn sleep_until(&self, deadline: usize) -> Result<(), RuntimeError> {
+        let mut inner = self.inner.write();
+
+        // 第一次执行 设置唤醒时间和定时器 重新执行时忽略deadline
         if inner.wake_time == 0 {
-            inner.wake_time = get_time_us() + (req_time.tv_sec * 1000000) as usize + req_time.tv_nsec as usize;
-            inner.context.sepc -= 4;
-            return Ok(())
+            if get_time_us() >= deadline {
+                inner.context.x[10] = 0;
+                return Ok(());
+            }
+            inner.wake_time = deadline;
+            get_current_task().unwrap().wake_at(deadline);
         }
-        let task_wake_time = inner.wake_time;
 
-        if get_time_us() > task_wake_time {
-            // 到达解锁时间
+        if get_time_us() >= inner.wake_time {
+            // 到达唤醒时间
             inner.wake_time = 0;
-        } else {
-            // 未到达解锁时间 重复执行
-            inner.context.sepc -= 4;
+            inner.context.x[10] = 0;
+            return Ok(());
+        }
+        // 未到达唤醒时间 进入等待状态 定时器到期后唤醒并重新执行
+        inner.context.sepc -= 4;
+        self.set_status(TaskStatus::WAITING);
+        Err(RuntimeError::ChangeTask)
+    }
+
+    // 获取间隔定时器
+    pub fn sys_getitimer(&self, which: usize, value: UserAddr<ITimerVal>) -> Result<(), RuntimeError> {
+        let mut inner = self.inner.write();
+        if which != ITIMER_REAL {
+            inner.context.x[10] = EINVAL;
+            return Ok(());
         }
+        let process = self.process.write();
+        if value.is_valid() {
+            *value.transfer() = process.real_timer.get_value();
+        }
+        drop(process);
+        inner.context.x[10] = 0;
+        Ok(())
+    }
+
+    // 设置间隔定时器 到期后向进程发送SIGALRM
+    pub fn sys_setitimer(&self, which: usize, new_value: UserAddr<ITimerVal>, old_value: UserAddr<ITimerVal>) -> Result<(), RuntimeError> {
+        let mut inner = self.inner.write();
+        if which != ITIMER_REAL {
+            inner.context.x[10] = EINVAL;
+            return Ok(());
+        }
+        // new_value不能为空
+        if !new_value.is_valid() {
+            inner.context.x[10] = EFAULT;
+            return Ok(());
+        }
+        let process_rc = self.process.clone();
+        let mut process = process_rc.write();
+        if old_value.is_valid() {
+            *old_value.transfer() = process.real_timer.get_value();
+        }
+        // 取消之前的定时器
+        if let Some(timer) = process.real_timer.timer.take() {
+            cancel_timer(timer);
+        }
+        let new_value = *new_value.transfer();
+        process.real_timer.interval = new_value.it_interval.as_us();
+        process.real_timer.deadline = 0;
+        // it_value为0时关闭定时器
+        if new_value.it_value.as_us() != 0 {
+            let deadline = get_time_us() + new_value.it_value.as_us();
+            process.real_timer.deadline = deadline;
+            process.real_timer.timer = Some(arm_real_timer(Arc::downgrade(&process_rc), deadline));
+        }
+        drop(process);
+        inner.context.x[10] = 0;
         Ok(())
     }
     
     pub fn sys_times(&self, tms_ptr: usize) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
         // 等待添加
-        let tms = usize::from(process.pmm.get_phys_addr(tms_ptr.into()).unwrap()) 
+        let tms = usize::from(process.mm.read().pmm.get_phys_addr(tms_ptr.into()).unwrap()) 
             as *mut TMS;
         let tms = unsafe { tms.as_mut().unwrap() };
     
-        // 写入文件时间
-        tms.tms_cstime = process.tms.tms_cstime;
-        tms.tms_cutime = process.tms.tms_cutime;
+        // 写入文件时间 内核中以微秒记录
+        tms.tms_utime = us_to_clock_ticks(process.tms.tms_utime as usize) as u64;
+        tms.tms_stime = us_to_clock_ticks(process.tms.tms_stime as usize) as u64;
+        tms.tms_cstime = us_to_clock_ticks(process.tms.tms_cstime as usize) as u64;
+        tms.tms_cutime = us_to_clock_ticks(process.tms.tms_cutime as usize) as u64;
         drop(process);
 
         inner.context.x[10] = get_ticks();
@@ -49,10 +156,10 @@ impl Task {
     }
     
     pub fn sys_gettimeofday(&self, ptr: usize) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
     
-        let timespec = usize::from(process.pmm.get_phys_addr(ptr.into()).unwrap()) as *mut TimeSpec;
+        let timespec = usize::from(process.mm.read().pmm.get_phys_addr(ptr.into()).unwrap()) as *mut TimeSpec;
         unsafe { timespec.as_mut().unwrap().get_now() };
         drop(process);
         inner.context.x[10] = 0;
@@ -60,8 +167,8 @@ impl Task {
     }
 
     pub fn sys_gettime(&self, _clock_id: usize, times_ptr: UserAddr<TimeSpec>) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
 
         let req = times_ptr.transfer();
 
@@ -75,15 +182,15 @@ impl Task {
     }
 
     pub fn sys_utimeat(&self, dir_fd: usize, filename: UserAddr<u8>, times_ptr: UserAddr<TimeSpec>, _flags: usize) -> Result<(), RuntimeError> {
-        let mut inner = self.inner.borrow_mut();
-        let process = inner.process.borrow_mut();
+        let mut inner = self.inner.write();
+        let process = self.process.write();
 
         let mut inode = if dir_fd == FD_CWD {
             // process.workspace.clone()
             // INode::get(None, &process.workspace)?
-            process.workspace.clone()
+            process.workspace.read().clone()
         } else {
-            let file = process.fd_table.get_file(dir_fd).map_err(|_| (RuntimeError::EBADF))?;
+            let file = process.fd_table.read().get_file(dir_fd).map_err(|_| (RuntimeError::EBADF))?;
             file.get_inode()
         };
 
@@ -106,7 +213,7 @@ impl Task {
         const UTIME_NOW: usize = 0x3fffffff;
         const UTIME_OMIT: usize = 0x3ffffffe;
 
-        let _inode_inner = inode.0.borrow_mut();
+        let _inode_inner = inode.0.write();
 
         if times[0].tv_nsec as usize != UTIME_OMIT {
             let _time = if times[0].tv_nsec as usize == UTIME_NOW {
@@ -134,4 +241,30 @@ impl Task {
         inner.context.x[10] = 0;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+// 添加ITIMER_REAL定时器 在deadline(微秒)到期
+fn arm_real_timer(process: Weak<RwLock<Process>>, deadline: usize) -> TimerId {
+    add_timer(deadline, Box::new(move || real_timer_expired(process)))
+}
+
+// ITIMER_REAL到期 发送SIGALRM 并根据间隔重新计时
+fn real_timer_expired(process: Weak<RwLock<Process>>) {
+    let process_rc = match process.upgrade() {
+        Some(process) => process,
+        None => return
+    };
+    let mut process = process_rc.write();
+    if process.exit_code.is_some() {
+        return;
+    }
+    process.real_timer.timer = None;
+    if process.real_timer.interval != 0 {
+        let deadline = process.real_timer.deadline + process.real_timer.interval;
+        process.real_timer.deadline = deadline;
+        process.real_timer.timer = Some(arm_real_timer(Arc::downgrade(&process_rc), deadline));
+    } else {
+        process.real_timer.deadline = 0;
+    }
+    process.send_signal(Signal::SIGALRM as usize);
+}
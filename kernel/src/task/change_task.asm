@@ -128,4 +128,43 @@ __load_kernel_context:
     
     # 回收栈
     addi sp, sp, 32*8
-    ret
\ No newline at end of file
+    ret
+# 浮点寄存器 内核不使用浮点指令 只在运行用户程序前后恢复和保存
+.option push
+.option arch, +d
+
+.macro SAVE_FP n
+    fsd f\n, \n*8(a0)
+.endm
+
+.macro LOAD_FP n
+    fld f\n, \n*8(a0)
+.endm
+
+# 保存浮点寄存器到a0指向的FpContext
+    .global save_fp
+    .align 2
+save_fp:
+    .set n, 0
+    .rept 32
+        SAVE_FP %n
+        .set n, n+1
+    .endr
+    frcsr t0
+    sd t0, 32*8(a0)
+    ret
+
+# 从a0指向的FpContext恢复浮点寄存器
+    .global restore_fp
+    .align 2
+restore_fp:
+    .set n, 0
+    .rept 32
+        LOAD_FP %n
+        .set n, n+1
+    .endr
+    ld t0, 32*8(a0)
+    fscsr t0
+    ret
+
+.option pop
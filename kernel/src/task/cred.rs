@@ -0,0 +1,116 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use crate::fs::file::{Kstat, S_IFMT, S_IFDIR};
+use crate::fs::filetree::INode;
+
+// root用户 拥有所有权限
+pub const ROOT_UID: u32 = 0;
+pub const ROOT_GID: u32 = 0;
+
+// 附加组的最大数量
+pub const NGROUPS_MAX: usize = 65536;
+
+// 访问权限
+pub const R_OK: u32 = 4;
+pub const W_OK: u32 = 2;
+pub const X_OK: u32 = 1;
+
+// 执行时设置用户和组
+pub const S_ISUID: u32 = 0o4000;
+pub const S_ISGID: u32 = 0o2000;
+
+// 进程的用户和组
+#[derive(Clone)]
+pub struct Credentials {
+    pub uid: u32,           // 实际用户
+    pub euid: u32,          // 有效用户 用于权限检查
+    pub suid: u32,          // 保存的用户
+    pub gid: u32,           // 实际组
+    pub egid: u32,          // 有效组 用于权限检查
+    pub sgid: u32,          // 保存的组
+    pub groups: Vec<u32>    // 附加组
+}
+
+impl Credentials {
+    // 初始进程使用root用户
+    pub fn root() -> Self {
+        Self {
+            uid: ROOT_UID,
+            euid: ROOT_UID,
+            suid: ROOT_UID,
+            gid: ROOT_GID,
+            egid: ROOT_GID,
+            sgid: ROOT_GID,
+            groups: vec![]
+        }
+    }
+
+    // 有效用户为root时拥有特权
+    pub fn is_root(&self) -> bool {
+        self.euid == ROOT_UID
+    }
+
+    // 判断是否属于组
+    pub fn in_group(&self, gid: u32) -> bool {
+        self.egid == gid || self.groups.contains(&gid)
+    }
+
+    // 判断uid是否为实际 有效或保存的用户之一 非特权进程只能切换到这些用户
+    pub fn has_uid(&self, uid: u32) -> bool {
+        uid == self.uid || uid == self.euid || uid == self.suid
+    }
+
+    // 判断gid是否为实际 有效或保存的组之一
+    pub fn has_gid(&self, gid: u32) -> bool {
+        gid == self.gid || gid == self.egid || gid == self.sgid
+    }
+
+    // 检查文件的访问权限 access为R_OK W_OK X_OK的组合
+    // root可以读写所有文件 只有目录或者存在执行位时可以执行
+    pub fn permitted(&self, kstat: &Kstat, access: u32) -> bool {
+        let mode = kstat.st_mode;
+        if self.is_root() {
+            return access & X_OK == 0 || mode & S_IFMT == S_IFDIR || mode & 0o111 != 0;
+        }
+        let bits = if kstat.st_uid == self.euid {
+            mode >> 6
+        } else if self.in_group(kstat.st_gid) {
+            mode >> 3
+        } else {
+            mode
+        } & 0o7;
+        bits & access == access
+    }
+
+    // 检查路径中每一级目录的搜索权限 current为空时从根目录开始
+    // 不存在的目录不在这里处理 由之后的查找返回ENOENT
+    pub fn can_search(&self, current: Option<Arc<INode>>, path: &str) -> bool {
+        let mut node = current.unwrap_or_else(INode::root);
+        let dir = path.trim_matches('/').rsplit_once('/').map_or("", |(dir, _)| dir);
+        if !self.permitted(&node.stat(), X_OK) {
+            return false;
+        }
+        for name in dir.split('/').filter(|x| !x.is_empty()) {
+            node = match node.get_children(name) {
+                Ok(node) => node,
+                Err(_) => return true
+            };
+            if !self.permitted(&node.stat(), X_OK) {
+                return false;
+            }
+        }
+        true
+    }
+
+    // 判断是否为文件的所有者 root视为所有者
+    pub fn owns(&self, kstat: &Kstat) -> bool {
+        self.is_root() || kstat.st_uid == self.euid
+    }
+
+    // 判断是否可以向目标进程发送信号
+    // 发送者的实际或有效用户需要与目标的实际或保存的用户相同
+    pub fn can_signal(&self, target: &Credentials) -> bool {
+        self.is_root() || self.uid == target.uid || self.uid == target.suid
+            || self.euid == target.uid || self.euid == target.suid
+    }
+}
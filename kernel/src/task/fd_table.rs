@@ -1,92 +1,201 @@
-use core::borrow::Borrow;
-
-use alloc::rc::Rc;
+use crate::sync::rwlock::RwLock;
+use alloc::sync::Arc;
 use hashbrown::HashMap;
 use crate::fs::file::FileOP;
 use crate::fs::file::File;
+use crate::fs::file::FileType;
 use crate::fs::stdio::StdIn;
 use crate::fs::stdio::StdOut;
 use crate::fs::stdio::StdErr;
 use crate::runtime_err::RuntimeError;
 use crate::memory::addr::UserAddr;
+use crate::sys_call::OpenFlags;
+use crate::sys_call::consts::{EINVAL, ENXIO, ESPIPE};
+use super::task::Task;
 
 pub const FD_NULL: usize = 0xffffffffffffff9c;
 pub const FD_CWD: usize = -100 as isize as usize;
 pub const FD_RANDOM: usize = usize::MAX;
 
+// lseek 的 whence
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+pub const SEEK_DATA: usize = 3;
+pub const SEEK_HOLE: usize = 4;
+
+// fcntl F_SETFL 可以修改的状态标志
+const SETFL_MASK: OpenFlags = OpenFlags::from_bits_truncate(
+    OpenFlags::APPEND.bits() | OpenFlags::NONBLOCK.bits() | OpenFlags::ASYNC.bits()
+    | OpenFlags::DIRECT.bits() | OpenFlags::NOATIME.bits());
+
+// 路径为空时使用dir_fd本身
+pub const AT_EMPTY_PATH: usize = 0x1000;
+
+// 打开的文件描述 记录读写位置和状态标志
+pub struct FileDescInner {
+    pub offset: usize,
+    pub flags: OpenFlags
+}
+
+// 打开的文件 dup和fork得到的文件描述符共享读写位置和状态标志
 #[derive(Clone)]
 pub struct FileDesc {
-    pub offset: usize,
-    pub file: Rc<dyn FileOP>
+    pub file: Arc<dyn FileOP>,
+    pub inner: Arc<RwLock<FileDescInner>>
 }
 
 impl FileDesc {
-    pub fn new(file: Rc<dyn FileOP>) -> Self {
+    pub fn new(file: Arc<dyn FileOP>) -> Self {
+        Self::with_flags(file, OpenFlags::RDWR)
+    }
+
+    // 使用打开时的标志创建 只保存访问模式和状态标志
+    pub fn with_flags(file: Arc<dyn FileOP>, flags: OpenFlags) -> Self {
         Self {
-            offset: 0,
-            file
+            file,
+            inner: Arc::new(RwLock::new(FileDescInner {
+                offset: 0,
+                flags: flags - (OpenFlags::CREATE | OpenFlags::EXCL | OpenFlags::NOCTTY
+                    | OpenFlags::TRUNC | OpenFlags::CLOEXEC)
+            }))
         }
     }
 
+    // 获取读写位置
+    pub fn get_offset(&self) -> usize {
+        self.inner.read().offset
+    }
+
+    // 设置读写位置
+    pub fn set_offset(&self, offset: usize) {
+        self.inner.write().offset = offset;
+    }
+
+    // 获取状态标志
+    pub fn get_flags(&self) -> OpenFlags {
+        self.inner.read().flags
+    }
+
+    // 修改状态标志 访问模式无法修改
+    pub fn set_flags(&self, flags: OpenFlags) {
+        let mut inner = self.inner.write();
+        inner.flags = (inner.flags - SETFL_MASK) | (flags & SETFL_MASK);
+    }
+
+    // 打开时的访问模式和文件都可以读取
     pub fn readable(&self) -> bool {
-        self.file.readable()
+        self.get_flags().readable() && self.file.readable()
     }
 
+    // 打开时的访问模式和文件都可以写入
     pub fn writeable(&self) -> bool {
-        self.file.writeable()
+        self.get_flags().writeable() && self.file.writeable()
+    }
+
+    // 是否为非阻塞读写
+    pub fn nonblock(&self) -> bool {
+        self.get_flags().contains(OpenFlags::NONBLOCK)
+    }
+
+    pub fn read_ready(&self) -> bool {
+        self.file.read_ready()
+    }
+
+    pub fn write_ready(&self) -> bool {
+        self.file.write_ready()
+    }
+
+    pub fn wait_read(&self, task: Arc<Task>) {
+        self.file.wait_read(task)
+    }
+
+    pub fn wait_write(&self, task: Arc<Task>) {
+        self.file.wait_write(task)
     }
 
     pub fn get_size(&self) -> usize {
         self.file.get_size()
     }
 
+    pub fn seekable(&self) -> bool {
+        self.file.seekable()
+    }
+
     pub fn available(&self) -> usize {
-        self.get_size() - self.offset
+        self.get_size().saturating_sub(self.get_offset())
     }
 
-    pub fn read(&mut self, buf: &mut [u8]) -> usize {
-        let read_len = self.file.read_at(*self.offset.borrow(), buf);
-        self.offset += read_len;
+    // 从当前位置读取 并移动读取位置
+    pub fn read(&self, buf: &mut [u8]) -> usize {
+        let mut inner = self.inner.write();
+        let read_len = self.file.read_at(inner.offset, buf);
+        if self.seekable() {
+            inner.offset += read_len;
+        }
         read_len
     }
 
+    // 写入到当前位置 O_APPEND 时每次写入前移动到文件末尾
     pub fn write(&self, buf: &[u8], count: usize) -> usize {
-        let pos = self.file.get_size();
-        let write_len = self.file.write_at(pos, buf, count);
+        let mut inner = self.inner.write();
+        if inner.flags.contains(OpenFlags::APPEND) {
+            inner.offset = self.file.get_size();
+        }
+        let write_len = self.file.write_at(inner.offset, buf, count);
+        // 写入失败时返回错误码 不移动位置
+        if self.seekable() && (write_len as isize) > 0 {
+            inner.offset += write_len;
+        }
         write_len
     }
 
-    pub fn downcast<T:'static>(&self) -> Result<Rc<T>, Rc<dyn FileOP>> {
+    pub fn downcast<T:'static>(&self) -> Result<Arc<T>, Arc<dyn FileOP>> {
         self.file.clone().downcast()
     }
 
-    pub fn lseek(&mut self, offset: usize, whence: usize) -> usize {
-        let file_size = self.file.get_size();
+    // 是否为目录
+    pub fn is_dir(&self) -> bool {
+        match self.downcast::<File>() {
+            Ok(file) => file.get_file_type() == FileType::Directory,
+            Err(_) => false
+        }
+    }
 
-        self.offset = match whence {
-            // SEEK_SET
-            0 => { 
-                if self.offset < file_size {
-                    self.offset
-                } else {
-                    file_size
+    // 移动读写位置 offset为有符号数 可以移动到文件末尾之后 写入时扩展文件
+    // 目录的位置为getdents返回的d_off 不支持SEEK_END
+    pub fn lseek(&self, offset: usize, whence: usize) -> usize {
+        let is_dir = self.is_dir();
+        if !is_dir && !self.seekable() {
+            return ESPIPE;
+        }
+        let offset = offset as isize;
+        let file_size = self.file.get_size() as isize;
+        let pos = match whence {
+            SEEK_SET => offset,
+            SEEK_CUR => self.get_offset() as isize + offset,
+            SEEK_END if !is_dir => file_size + offset,
+            // 下一个有数据的位置 文件中没有空洞
+            SEEK_DATA if !is_dir => {
+                if offset >= file_size {
+                    return ENXIO;
                 }
+                offset
             }
-            // SEEK_CUR
-            1 => { 
-                if self.offset + offset < file_size {
-                    self.offset + offset
-                } else {
-                    file_size
+            // 下一个空洞的位置 文件末尾视为空洞
+            SEEK_HOLE if !is_dir => {
+                if offset >= file_size {
+                    return ENXIO;
                 }
+                file_size
             }
-            // SEEK_END
-            2 => {
-                file_size + self.offset
-            }
-            _ => { 0 }
+            _ => return EINVAL
         };
-        self.offset
+        if pos < 0 {
+            return EINVAL;
+        }
+        self.set_offset(pos as usize);
+        pos as usize
     }
 }
 
@@ -97,61 +206,114 @@ pub struct IoVec {
     pub iov_len: usize
 }
 
+// 文件描述符 close_on_exec只属于当前文件描述符
 #[derive(Clone)]
-pub struct FDTable(HashMap<usize, FileDesc>);
+pub struct FdSlot {
+    pub desc: FileDesc,
+    pub cloexec: bool
+}
+
+#[derive(Clone)]
+pub struct FDTable {
+    table: HashMap<usize, FdSlot>
+}
 
 impl FDTable {
     pub fn new() -> Self {
-        let mut map:HashMap<usize, FileDesc> = HashMap::new();
-        map.insert(0, FileDesc::new(Rc::new(StdIn)));
-        map.insert(1, FileDesc::new(Rc::new(StdOut)));
-        map.insert(2, FileDesc::new(Rc::new(StdErr)));
-        Self(map)
+        let mut table = Self {
+            table: HashMap::new()
+        };
+        table.set(0, FileDesc::new(Arc::new(StdIn)));
+        table.set(1, FileDesc::new(Arc::new(StdOut)));
+        table.set(2, FileDesc::new(Arc::new(StdErr)));
+        table
     }
 
-    // 申请fd
-    pub fn alloc(&mut self) -> usize {
-        (0..).find(|fd| !self.0.contains_key(fd)).unwrap()
+    // 不包含任何fd的表 用于进程退出后替换原来的表
+    pub fn empty() -> Self {
+        Self {
+            table: HashMap::new()
+        }
+    }
+
+    // 申请fd limit为当前进程的RLIMIT_NOFILE
+    pub fn alloc(&mut self, limit: usize) -> Result<usize, RuntimeError> {
+        self.alloc_from(0, limit)
+    }
+
+    // 申请不小于min的fd 不小于limit时返回EMFILE
+    // 文件描述符表可能由多个进程共享 limit由调用的进程提供
+    pub fn alloc_from(&mut self, min: usize, limit: usize) -> Result<usize, RuntimeError> {
+        (min..limit).find(|fd| !self.table.contains_key(fd)).ok_or(RuntimeError::EMFILE)
     }
 
     // 申请fd
-    pub fn alloc_sock(&mut self) -> usize {
-        (50..).find(|fd| !self.0.contains_key(fd)).unwrap()
+    pub fn alloc_sock(&mut self, limit: usize) -> Result<usize, RuntimeError> {
+        self.alloc_from(50, limit)
     }
 
     // 释放fd
     pub fn dealloc(&mut self, index: usize) {
-        self.0.remove(&index);
+        self.table.remove(&index);
+    }
+
+    // 关闭所有的fd
+    pub fn release(&mut self) {
+        self.table.clear();
+    }
+
+    // 执行新程序时关闭设置了close_on_exec的fd
+    pub fn close_on_exec(&mut self) {
+        self.table.retain(|_, slot| !slot.cloexec);
     }
 
     // 获取fd内容
-    pub fn get(&mut self, index: usize) -> Result<&mut FileDesc, RuntimeError> {
-        self.0.get_mut(&index).ok_or(RuntimeError::NoMatchedFileDesc)
+    pub fn get(&self, index: usize) -> Result<FileDesc, RuntimeError> {
+        self.table.get(&index).map(|x| x.desc.clone()).ok_or(RuntimeError::NoMatchedFileDesc)
     }
 
     // 获取fd内容
-    pub fn get_file(&self, index: usize) -> Result<Rc<File>, RuntimeError> {
-        let value = self.0.get(&index).cloned().ok_or(RuntimeError::NoMatchedFileDesc)?;
+    pub fn get_file(&self, index: usize) -> Result<Arc<File>, RuntimeError> {
+        let value = self.get(index)?;
         value.file.downcast::<File>().map_err(|_| RuntimeError::NoMatchedFile)
     }
 
+    // 获取close_on_exec
+    pub fn get_cloexec(&self, index: usize) -> Result<bool, RuntimeError> {
+        self.table.get(&index).map(|x| x.cloexec).ok_or(RuntimeError::NoMatchedFileDesc)
+    }
+
+    // 设置close_on_exec
+    pub fn set_cloexec(&mut self, index: usize, cloexec: bool) -> Result<(), RuntimeError> {
+        let slot = self.table.get_mut(&index).ok_or(RuntimeError::NoMatchedFileDesc)?;
+        slot.cloexec = cloexec;
+        Ok(())
+    }
+
     // 设置fd内容
     pub fn set(&mut self, index: usize, value: FileDesc) {
-        self.0.insert(index, value);
+        self.set_with_cloexec(index, value, false);
+    }
+
+    // 设置fd内容和close_on_exec
+    pub fn set_with_cloexec(&mut self, index: usize, value: FileDesc, cloexec: bool) {
+        self.table.insert(index, FdSlot {
+            desc: value,
+            cloexec
+        });
     }
 
     // 加入描述符
-    pub fn push(&mut self, value: FileDesc) -> usize {
-        let index = self.alloc();
-        // if index > 41 { return EMFILE; }
+    pub fn push(&mut self, value: FileDesc, limit: usize) -> Result<usize, RuntimeError> {
+        let index = self.alloc(limit)?;
         self.set(index, value);
-        index
+        Ok(index)
     }
 
     // 加入描述符
-    pub fn push_sock(&mut self, value: FileDesc) -> usize {
-        let index = self.alloc_sock();
+    pub fn push_sock(&mut self, value: FileDesc, limit: usize) -> Result<usize, RuntimeError> {
+        let index = self.alloc_sock(limit)?;
         self.set(index, value);
-        index
+        Ok(index)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,169 @@
+use alloc::collections::BTreeMap;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::sync::Weak;
+use alloc::vec::Vec;
+
+use crate::sync::mutex::Mutex;
+
+use super::task::Task;
+use super::task::TaskStatus;
+
+// 匹配所有等待者的bitset
+pub const FUTEX_BITSET_MATCH_ANY: u32 = 0xffffffff;
+
+// futex的值中的标志位 低30位为持有者的tid
+pub const FUTEX_WAITERS: u32 = 0x80000000;
+pub const FUTEX_OWNER_DIED: u32 = 0x40000000;
+pub const FUTEX_TID_MASK: u32 = 0x3fffffff;
+
+// 用户态的robust futex链表头 由set_robust_list设置
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct RobustListHead {
+    pub list: usize,            // 链表中的第一项 指向自身时为空
+    pub futex_offset: isize,    // futex值相对于链表项的偏移
+    pub list_op_pending: usize  // 正在加锁或解锁的链表项
+}
+
+// 任务的futex等待状态
+#[derive(Clone, Copy, PartialEq)]
+pub enum FutexState {
+    None,               // 没有在futex上等待
+    Waiting(usize),     // 在key对应的futex上等待
+    Woken               // 已经被唤醒 重新执行系统调用时返回0
+}
+
+// 线程的futex状态 持有FUTEX_TABLE时访问 不放在TaskInner中
+pub struct TaskFutex {
+    pub state: FutexState,
+    pub pi_locks: Vec<usize>    // 持有的有等待者的PI锁 解锁时重新计算继承的优先级
+}
+
+impl TaskFutex {
+    pub fn new() -> Self {
+        Self {
+            state: FutexState::None,
+            pi_locks: Vec::new()
+        }
+    }
+}
+
+// futex上等待的任务
+struct FutexWaiter {
+    task: Weak<Task>,
+    bitset: u32
+}
+
+// futex等待表 以用户地址对应的物理地址作为key 共享映射的不同进程可以互相唤醒
+pub struct FutexTable(BTreeMap<usize, VecDeque<FutexWaiter>>);
+
+impl FutexTable {
+    pub fn new() -> Self {
+        Self(BTreeMap::new())
+    }
+
+    // 任务在key上等待
+    pub fn wait(&mut self, key: usize, task: Arc<Task>, bitset: u32) {
+        task.futex.lock().state = FutexState::Waiting(key);
+        task.set_status(TaskStatus::WAITING);
+        self.0.entry(key).or_insert_with(VecDeque::new).push_back(FutexWaiter {
+            task: Arc::downgrade(&task),
+            bitset
+        });
+    }
+
+    // 唤醒key上与bitset匹配的最多count个任务 返回唤醒的数量
+    pub fn wake(&mut self, key: usize, count: usize, bitset: u32) -> usize {
+        let mut woken = 0;
+        if let Some(queue) = self.0.get_mut(&key) {
+            queue.retain(|waiter| {
+                let task = match waiter.task.upgrade() {
+                    Some(task) => task,
+                    None => return false
+                };
+                if woken >= count || waiter.bitset & bitset == 0 {
+                    return true;
+                }
+                task.futex.lock().state = FutexState::Woken;
+                task.wake();
+                woken += 1;
+                false
+            });
+        }
+        self.remove_empty(key);
+        woken
+    }
+
+    // 唤醒key上最多wake_count个任务 再把最多requeue_count个任务移动到new_key上等待
+    // 返回唤醒和移动的任务数量
+    pub fn requeue(&mut self, key: usize, new_key: usize, wake_count: usize, requeue_count: usize) -> usize {
+        let woken = self.wake(key, wake_count, FUTEX_BITSET_MATCH_ANY);
+        if key == new_key {
+            return woken;
+        }
+        let mut moved = VecDeque::new();
+        if let Some(queue) = self.0.get_mut(&key) {
+            while moved.len() < requeue_count {
+                let waiter = match queue.pop_front() {
+                    Some(waiter) => waiter,
+                    None => break
+                };
+                if let Some(task) = waiter.task.upgrade() {
+                    task.futex.lock().state = FutexState::Waiting(new_key);
+                    moved.push_back(waiter);
+                }
+            }
+        }
+        self.remove_empty(key);
+        let requeued = moved.len();
+        if requeued > 0 {
+            self.0.entry(new_key).or_insert_with(VecDeque::new).append(&mut moved);
+        }
+        woken + requeued
+    }
+
+    // 取出key上的第一个等待者 不唤醒任务
+    pub fn pop(&mut self, key: usize) -> Option<Arc<Task>> {
+        let mut task = None;
+        if let Some(queue) = self.0.get_mut(&key) {
+            while let Some(waiter) = queue.pop_front() {
+                task = waiter.task.upgrade();
+                if task.is_some() {
+                    break;
+                }
+            }
+        }
+        self.remove_empty(key);
+        task
+    }
+
+    // 获取key上所有的等待者
+    pub fn waiters(&self, key: usize) -> Vec<Arc<Task>> {
+        self.0.get(&key).map_or(vec![], |queue| queue.iter().filter_map(|x| x.task.upgrade()).collect())
+    }
+
+    // 移除key上等待的任务 超时后使用
+    pub fn remove(&mut self, key: usize, tid: usize) {
+        if let Some(queue) = self.0.get_mut(&key) {
+            queue.retain(|waiter| waiter.task.upgrade().map_or(false, |x| x.tid != tid));
+        }
+        self.remove_empty(key);
+    }
+
+    // 删除空的等待队列
+    fn remove_empty(&mut self, key: usize) {
+        if self.0.get(&key).map_or(false, |x| x.is_empty()) {
+            self.0.remove(&key);
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref FUTEX_TABLE: Mutex<FutexTable> = Mutex::new(FutexTable::new());
+}
+
+// 唤醒key上最多count个任务
+pub fn futex_wake(key: usize, count: usize) -> usize {
+    FUTEX_TABLE.lock().wake(key, count, FUTEX_BITSET_MATCH_ANY)
+}
@@ -0,0 +1,61 @@
+use alloc::sync::Arc;
+use crate::memory::page_table::PageMappingManager;
+use crate::memory::mem_set::MemSet;
+use crate::memory::addr::PAGE_SIZE;
+use crate::runtime_err::RuntimeError;
+use super::stack::UserStack;
+use super::user_heap::UserHeap;
+
+// 进程的地址空间 CLONE_VM创建的进程之间共享
+pub struct MemSpace {
+    pub pmm: Arc<PageMappingManager>,            // 内存页映射管理
+    pub mem_set: MemSet,                        // 内存使用集
+    pub stack: UserStack,                       // 用户栈
+    pub heap: UserHeap                          // 用户堆
+}
+
+impl MemSpace {
+    pub fn new() -> Result<Self, RuntimeError> {
+        let pmm = Arc::new(PageMappingManager::new()?);
+        Ok(Self {
+            pmm: pmm.clone(),
+            mem_set: MemSet::new(),
+            stack: UserStack::new(pmm.clone())?,
+            heap: UserHeap::new(pmm)?
+        })
+    }
+
+    // 复制地址空间中的数据 用于不共享地址空间的子进程
+    pub fn clone_with_data(&self) -> Result<Self, RuntimeError> {
+        let pmm = Arc::new(PageMappingManager::new()?);
+        let mem_set = self.mem_set.clone_with_data()?;
+        pmm.add_mapping_by_set(&mem_set)?;
+        let stack = self.stack.clone_with_data(pmm.clone())?;
+        let heap = self.heap.clone_with_data(pmm.clone())?;
+        debug!("heap_pointer: {:#x}", heap.get_heap_top());
+        Ok(Self {
+            pmm,
+            mem_set,
+            stack,
+            heap
+        })
+    }
+
+    // 获取数据段和匿名映射的大小 用于RLIMIT_DATA
+    pub fn get_data_size(&self) -> usize {
+        (self.mem_set.get_page_num() + self.heap.mem_set.get_page_num()) * PAGE_SIZE
+    }
+
+    // 获取已经映射的虚拟内存大小 用于RLIMIT_AS
+    pub fn get_mapped_size(&self) -> usize {
+        self.get_data_size() + self.stack.mem_set.get_page_num() * PAGE_SIZE
+    }
+
+    // 释放内存 最后一个引用被删除时也会释放
+    pub fn release(&mut self) {
+        self.stack.release();
+        self.heap.mem_set.release();
+        self.mem_set.release();
+        self.pmm.release();
+    }
+}
@@ -1,19 +1,26 @@
 use core::arch::global_asm;
-use core::cell::RefCell;
+use crate::sync::rwlock::RwLock;
 
 
 use alloc::collections::BTreeMap;
-use alloc::rc::Rc;
+use alloc::sync::Arc;
+use alloc::string::{String, ToString};
 use alloc::vec::Vec;
-use xmas_elf::program::{Type, SegmentData};
+use xmas_elf::ElfFile;
+use xmas_elf::header;
+use xmas_elf::program::{Type, ProgramHeader64};
 use crate::elf::{self, ElfExtra};
 use crate::fs::filetree::INode;
+use crate::fs::file::{File, FileInner};
 use crate::memory::addr::get_pages_num;
 use crate::memory::addr::get_buf_from_phys_page;
 use crate::memory::mem_map::MemMap;
 use crate::memory::page::alloc_more;
+use crate::interrupt::FpContext;
 use crate::runtime_err::RuntimeError;
-use crate::task::process::Process;
+use crate::random::fill_random;
+use crate::task::process::{Process, init_process};
+use crate::task::mem_space::MemSpace;
 use crate::task::task_scheduler::start_tasks;
 use crate::memory::page_table::PTEFlags;
 use crate::memory::addr::PAGE_SIZE;
@@ -33,6 +40,12 @@ pub mod signal;
 pub mod fd_table;
 pub mod task_scheduler;
 pub mod user_heap;
+pub mod wait_queue;
+pub mod futex;
+pub mod scheduler;
+pub mod rlimit;
+pub mod cred;
+pub mod mem_space;
 
 pub const STDIN: usize = 0;
 pub const STDOUT: usize = 1;
@@ -43,123 +56,240 @@ pub fn get_new_pid() -> usize {
     NEXT_PID.lock().next()
 }
 
-pub fn exec_with_process<'a>(process: Rc<RefCell<Process>>, task: Rc<Task>, path: &'a str, args: Vec<&'a str>) 
-        -> Result<Rc<Task>, RuntimeError> {
-    // 如果存在write
-    let file = INode::open(None, path)?;
+// vDSO映射的地址 位于mmap区域之前
+pub const VDSO_ADDR: usize = 0xc000_0000;
 
-    let file_inner = file.0.borrow_mut();
-    // 读取elf信息
-    let elf = xmas_elf::ElfFile::new(&file_inner.buf).unwrap();
-    let elf_header = elf.header;    
-    let magic = elf_header.pt1.magic;
-
-    let entry_point = elf.header.pt2.entry_point() as usize;
-    assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
-
-    // 测试代码
-    let header = elf
-        .program_iter()
-        .find(|ph| ph.get_type() == Ok(Type::Interp));
-    if let Some(header) = header {
-        if let Ok(SegmentData::Undefined(_data)) = header.get_data(&elf) {
-            // 对 动态链接文件进行转发
-            let path = "libc.so";
-            let mut new_args = vec![path];
-            new_args.extend_from_slice(&args[..]);
-            return exec_with_process(process, task, path, new_args);
+// 初始程序的环境变量
+pub const DEFAULT_ENVP: [&str; 1] = ["LD_LIBRARY_PATH=/"];
+
+// 映射只读的vDSO页 返回映射的地址
+fn map_vdso(mm: &mut MemSpace) -> Result<usize, RuntimeError> {
+    let mem_map = MemMap::new(VirtAddr::from(VDSO_ADDR).into(), 1,
+        PTEFlags::V | PTEFlags::R | PTEFlags::X | PTEFlags::U)?;
+    let buf = get_buf_from_phys_page(mem_map.ppn, 1);
+    buf.fill(0);
+    buf[..elf::VDSO_SIZE].copy_from_slice(&elf::vdso_image());
+    mm.pmm.add_mapping_by_map(&mem_map)?;
+    mm.mem_set.inner().push(mem_map);
+    Ok(VDSO_ADDR)
+}
+
+// 位置无关的程序和解释器的加载地址
+pub const ELF_DYN_BASE: usize = 0x1000_0000;
+pub const INTERP_BASE: usize = 0x2000_0000;
+
+// 获取PT_INTERP中的解释器路径
+fn get_interp(elf: &ElfFile, buf: &[u8]) -> Option<String> {
+    let ph = elf.program_iter().find(|ph| ph.get_type() == Ok(Type::Interp))?;
+    let start = ph.offset() as usize;
+    let data = buf.get(start..start + ph.file_size() as usize)?;
+    let path = core::str::from_utf8(data).ok()?.trim_end_matches('\0');
+    Some(path.to_string())
+}
+
+// 文件的内容 缓冲区的长度按页对齐 只取文件大小的部分
+fn file_data(inner: &FileInner) -> &[u8] {
+    &inner.buf[..inner.file_size]
+}
+
+// 解析elf 不是合法的elf时返回ENOEXEC
+fn parse_elf(buf: &[u8]) -> Result<ElfFile<'_>, RuntimeError> {
+    ElfFile::new(buf).map_err(|_| RuntimeError::ENOEXEC)
+}
+
+// 检查程序头表和每个程序头的数据都在文件范围内 PT_LOAD的文件大小不能超过内存大小
+fn check_elf(elf: &ElfFile, buf: &[u8]) -> Result<(), RuntimeError> {
+    let pt2 = &elf.header.pt2;
+    let ph_size = pt2.ph_entry_size() as usize;
+    let ph_end = (pt2.ph_count() as usize).checked_mul(ph_size)
+        .and_then(|x| x.checked_add(pt2.ph_offset() as usize));
+    if ph_size != core::mem::size_of::<ProgramHeader64>() || ph_end.map_or(true, |end| end > buf.len()) {
+        return Err(RuntimeError::ENOEXEC);
+    }
+    for ph in elf.program_iter() {
+        let file_end = ph.offset().checked_add(ph.file_size());
+        if file_end.map_or(true, |end| end > buf.len() as u64) {
+            return Err(RuntimeError::ENOEXEC);
+        }
+        if ph.get_type() == Ok(Type::Load) && (ph.file_size() > ph.mem_size()
+                || ph.virtual_addr().checked_add(ph.mem_size()).is_none()) {
+            return Err(RuntimeError::ENOEXEC);
         }
     }
+    Ok(())
+}
 
-    // 创建新的任务控制器 并映射栈
-    let mut process = process.borrow_mut();
-
-    let mut base = 0x20000000;
-    let mut relocated_arr = vec![];
-
-    base = match elf.relocate(base) {
-        Ok(arr) => {
-            relocated_arr = arr;
-            base
-        },
-        Err(_) => {
-            0
+// 打开PT_INTERP指定的解释器 解释器不存在时返回ENOENT
+fn open_interp(elf: &ElfFile, buf: &[u8]) -> Result<Option<Arc<File>>, RuntimeError> {
+    match get_interp(elf, buf) {
+        Some(interp_path) => {
+            debug!("interp: {}", interp_path);
+            let interp_file = INode::open(None, &interp_path).map_err(|_| RuntimeError::FileNotFound)?;
+            Ok(Some(interp_file))
         }
-    };
+        None => Ok(None)
+    }
+}
 
-    // 重新映射内存 并设置头
-    let mut heap_bottom = 0;
-    let ph_count = elf_header.pt2.ph_count();
-    for i in 0..ph_count {
-        let ph = elf.program_header(i).unwrap();
-        if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
-            let start_va: VirtAddr = (ph.virtual_addr() as usize + base).into();
-            let alloc_pages = get_pages_num(ph.mem_size() as usize + start_va.0 % 0x1000);
-            let phy_start = alloc_more(alloc_pages)?;
-
-            let ph_offset = ph.offset() as usize;
-            let offset = ph.offset() as usize % PAGE_SIZE;
-            let read_size = ph.file_size() as usize;
-            let temp_buf = get_buf_from_phys_page(phy_start, alloc_pages);
-
-            let vr_offset = ph.virtual_addr() as usize % 0x1000;
-            let vr_offset_end = vr_offset + read_size;
-
-            // 判断是否大于结束 修改HEAP地址
-            let end_va = (((ph.virtual_addr() + ph.mem_size()) + 4095) / 4096 * 4096) as usize;
-            if end_va > heap_bottom { heap_bottom = end_va; }
-
-            // 添加memset
-            process.mem_set.inner().push(MemMap::exists_page(phy_start, VirtAddr::from(ph.virtual_addr() as usize + base).into(), 
-                alloc_pages, PTEFlags::VRWX | PTEFlags::U));
-
-            // 初始化
-            temp_buf[vr_offset..vr_offset_end].copy_from_slice(&file_inner.buf[ph_offset..ph_offset+read_size]);
-            process.pmm.add_mapping_range(PhysAddr::from(phy_start) + PhysAddr::from(offset), 
-                start_va, ph.mem_size() as usize, PTEFlags::VRWX | PTEFlags::U)?;
+// 检查过的程序和解释器 加载时不再重新打开
+pub struct ExecFile {
+    pub file: Arc<File>,
+    pub interp: Option<Arc<File>>
+}
+
+// 执行新程序之前检查程序和解释器 出错时原来的进程保持不变
+pub fn check_exec(current: Option<Arc<INode>>, path: &str) -> Result<ExecFile, RuntimeError> {
+    let file = INode::open(current, path)?;
+    let file_inner = file.0.read();
+    let elf = parse_elf(file_data(&file_inner))?;
+    check_elf(&elf, file_data(&file_inner))?;
+    let interp = open_interp(&elf, file_data(&file_inner))?;
+    if let Some(interp_file) = &interp {
+        let interp_inner = interp_file.0.read();
+        let interp_elf = parse_elf(file_data(&interp_inner))?;
+        check_elf(&interp_elf, file_data(&interp_inner))?;
+    }
+    drop(file_inner);
+    Ok(ExecFile { file, interp })
+}
+
+// 获取elf的加载地址 位置无关的elf加载到base 否则使用程序头中的地址
+fn get_load_base(elf: &ElfFile, base: usize) -> usize {
+    match elf.header.pt2.type_().as_type() {
+        header::Type::SharedObject => base,
+        _ => 0
+    }
+}
+
+// 将elf的PT_LOAD段映射到base 返回结束地址
+fn load_elf(mm: &mut MemSpace, elf: &ElfFile, buf: &[u8], base: usize) -> Result<usize, RuntimeError> {
+    let mut end = 0;
+    for ph in elf.program_iter() {
+        if ph.get_type() != Ok(Type::Load) {
+            continue;
         }
+        let start_va: VirtAddr = (ph.virtual_addr() as usize + base).into();
+        let alloc_pages = get_pages_num(ph.mem_size() as usize + start_va.0 % 0x1000);
+        let phy_start = alloc_more(alloc_pages)?;
+
+        let ph_offset = ph.offset() as usize;
+        let offset = ph.offset() as usize % PAGE_SIZE;
+        let read_size = ph.file_size() as usize;
+        let temp_buf = get_buf_from_phys_page(phy_start, alloc_pages);
+
+        let vr_offset = ph.virtual_addr() as usize % 0x1000;
+        let vr_offset_end = vr_offset + read_size;
+
+        // 判断是否大于结束 修改HEAP地址
+        let end_va = ((ph.virtual_addr() + ph.mem_size()) as usize + base + 4095) / 4096 * 4096;
+        if end_va > end { end = end_va; }
+
+        // 添加memset
+        mm.mem_set.inner().push(MemMap::exists_page(phy_start, start_va.into(), 
+            alloc_pages, PTEFlags::VRWX | PTEFlags::U));
+
+        // 初始化 程序头已经在check_elf中检查过
+        temp_buf[vr_offset..vr_offset_end].copy_from_slice(&buf[ph_offset..ph_offset+read_size]);
+        mm.pmm.add_mapping_range(PhysAddr::from(phy_start) + PhysAddr::from(offset), 
+            start_va, ph.mem_size() as usize, PTEFlags::VRWX | PTEFlags::U)?;
+    }
+    Ok(end)
+}
+
+// 修改重定位的地址 没有解释器的位置无关elf由内核重定位
+fn relocate_elf(mm: &MemSpace, elf: &ElfFile, base: usize) -> Result<(), RuntimeError> {
+    if base == 0 {
+        return Ok(());
     }
-    if base > 0 {
-        let pmm = process.pmm.clone();
-        for (addr, value) in relocated_arr.clone() {
-            let phys_addr = pmm.get_phys_addr(addr.into())?;
-            let ptr = phys_addr.tranfer::<usize>();
-            *ptr = value;
+    if let Ok(relocated_arr) = elf.relocate(base) {
+        for (addr, value) in relocated_arr {
+            let phys_addr = mm.pmm.get_phys_addr(addr.into())?;
+            *phys_addr.tranfer::<usize>() = value;
         }
     }
+    Ok(())
+}
+
+// 执行check_exec检查过的程序 存在PT_INTERP时同时加载解释器 从解释器的入口开始执行
+pub fn exec_with_process<'a>(process: Arc<RwLock<Process>>, task: Arc<Task>, exec_file: ExecFile,
+        path: &'a str, args: Vec<&'a str>, envp: Vec<&'a str>) -> Result<Arc<Task>, RuntimeError> {
+    let file_inner = exec_file.file.0.read();
+    // 读取elf信息
+    let elf = parse_elf(file_data(&file_inner))?;
+    let elf_header = elf.header;
+
+    // 创建新的任务控制器 并映射栈
+    let process = process.read();
+    let mut mm = process.mm.write();
+
+    // 加载程序
+    let base = get_load_base(&elf, ELF_DYN_BASE);
+    let heap_bottom = load_elf(&mut mm, &elf, file_data(&file_inner), base)?;
+    let entry_point = base + elf_header.pt2.entry_point() as usize;
+    let phdr = base + elf.get_ph_addr()? as usize;
+
+    // 加载解释器
+    let (interp_base, start_point) = match &exec_file.interp {
+        Some(interp_file) => {
+            let interp_inner = interp_file.0.read();
+            let interp_elf = parse_elf(file_data(&interp_inner))?;
+            let interp_base = get_load_base(&interp_elf, INTERP_BASE);
+            // 解释器在启动时自己完成重定位 内核只负责映射
+            load_elf(&mut mm, &interp_elf, file_data(&interp_inner), interp_base)?;
+            (interp_base, interp_base + interp_elf.header.pt2.entry_point() as usize)
+        }
+        None => {
+            relocate_elf(&mm, &elf, base)?;
+            (0, entry_point)
+        }
+    };
+
+    // 有效用户或组与实际用户或组不同时为安全模式
+    let cred = process.cred.clone();
+    let secure = cred.uid != cred.euid || cred.gid != cred.egid;
+
+    let vdso = map_vdso(&mut mm)?;
 
     // 添加参数
-    let stack = &mut process.stack;
-    let random_ptr = stack.push_arr(&[0u8; 16]);
+    let stack = &mut mm.stack;
+    let mut random_bytes = [0u8; 16];
+    fill_random(&mut random_bytes);
+    let random_ptr = stack.push_arr(&random_bytes);
     
     let mut auxv = BTreeMap::new();
     auxv.insert(elf::AT_PLATFORM, stack.push_str("riscv"));
     auxv.insert(elf::AT_EXECFN, stack.push_str(path));
     auxv.insert(elf::AT_PHNUM, elf_header.pt2.ph_count() as usize);
     auxv.insert(elf::AT_PAGESZ, PAGE_SIZE);
-    auxv.insert(elf::AT_ENTRY, base + entry_point);
+    auxv.insert(elf::AT_ENTRY, entry_point);
     auxv.insert(elf::AT_PHENT, elf_header.pt2.ph_entry_size() as usize);
-    auxv.insert(elf::AT_PHDR, base + elf.get_ph_addr()? as usize);
+    auxv.insert(elf::AT_PHDR, phdr);
 
-    auxv.insert(elf::AT_GID, 1);
-    auxv.insert(elf::AT_EGID, 1);
-    auxv.insert(elf::AT_UID, 1);
-    auxv.insert(elf::AT_EUID, 1);
-    auxv.insert(elf::AT_SECURE, 0);
+    auxv.insert(elf::AT_GID, cred.gid as usize);
+    auxv.insert(elf::AT_EGID, cred.egid as usize);
+    auxv.insert(elf::AT_UID, cred.uid as usize);
+    auxv.insert(elf::AT_EUID, cred.euid as usize);
+    auxv.insert(elf::AT_SECURE, secure as usize);
     auxv.insert(elf::AT_RANDOM, random_ptr);
+    auxv.insert(elf::AT_HWCAP, elf::HWCAP_ISA);
+    auxv.insert(elf::AT_CLKTCK, elf::CLOCK_TICKS);
+    // 解释器的加载地址 没有解释器时为0
+    auxv.insert(elf::AT_BASE, interp_base);
+    auxv.insert(elf::AT_SYSINFO_EHDR, vdso);
 
-    stack.init_args(args, vec![], auxv);
+    stack.init_args(args, envp, auxv);
     
     // 更新context
-    let mut task_inner = task.inner.borrow_mut();
+    let mut task_inner = task.inner.write();
     task_inner.context.x.fill(0);
-    task_inner.context.sepc = base + entry_point;
-    task_inner.context.x[2] = process.stack.get_stack_top();
+    task_inner.fp = FpContext::new();
+    task_inner.context.sepc = start_point;
+    task_inner.context.x[2] = mm.stack.get_stack_top();
 
     // 设置heap_bottom
-    process.new_heap()?;
-    process.heap.set_heap_top(heap_bottom)?;
+    mm.heap.set_heap_top(heap_bottom)?;
     drop(task_inner);
+    drop(mm);
     drop(process);
 
     // 任务管理器添加任务
@@ -167,10 +297,13 @@ pub fn exec_with_process<'a>(process: Rc<RefCell<Process>>, task: Rc<Task>, path
 }
 
 // 执行一个程序 path: 文件名 思路：加入程序准备池  等待执行  每过一个时钟周期就执行一次
-pub fn exec<'a>(path: &'a str, args: Vec<&'a str>) -> Result<Rc<Task>, RuntimeError> { 
-    // 创建新的任务控制器 并映射栈
-    let (process, task) = Process::new(get_new_pid(), None)?;
-    exec_with_process(process, task, path, args)
+pub fn exec<'a>(path: &'a str, args: Vec<&'a str>) -> Result<Arc<Task>, RuntimeError> { 
+    // 创建新的任务控制器 并映射栈 初始程序的父进程为init进程
+    let init = init_process();
+    let (process, task) = Process::new(get_new_pid(), Some(Arc::downgrade(&init)))?;
+    init.write().children.push(process.clone());
+    let exec_file = check_exec(None, path)?;
+    exec_with_process(process, task, exec_file, path, args, DEFAULT_ENVP.to_vec())
 }
 
 // 包含更换任务代码
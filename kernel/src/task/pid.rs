@@ -17,5 +17,5 @@ impl PidGenerater {
 }
 
 pub fn get_next_pid() -> usize {
-    NEXT_PID.force_get().next()
+    NEXT_PID.lock().next()
 }
\ No newline at end of file
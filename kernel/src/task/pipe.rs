@@ -1,81 +1,136 @@
-use core::cell::RefCell;
+use crate::sync::rwlock::RwLock;
 use alloc::sync::Arc;
-use alloc::rc::Rc;
 use alloc::vec::Vec;
-use crate::fs::file::FileOP;
+use crate::fs::file::{FileOP, Kstat, DEV_PIPE, S_IFIFO};
+use crate::fs::filetree::alloc_ino;
+use crate::sys_call::OpenFlags;
+use crate::sys_call::consts::{EAGAIN, EPIPE};
 use super::fd_table::FileDesc;
+use super::task::Task;
+use super::wait_queue::WaitQueue;
+use super::wait_queue::notify_poll;
 
+// 管道缓冲区大小
+pub const PIPE_BUF_SIZE: usize = 0x4000;
+
+// 不超过PIPE_BUF的写入是原子的 不会与其他写入交错
+pub const PIPE_BUF: usize = 4096;
+
+// 管道环形缓冲区
 pub struct PipeBufInner {
-    pub buf: Vec<u8>,
-    pub read_offset: usize,
-    pub write_offset: usize
+    pub buf: Vec<u8>,           // 缓冲区
+    pub read_offset: usize,     // 读取位置
+    pub write_offset: usize,    // 写入位置
+    pub len: usize,             // 缓冲区中的数据长度
+    pub readers: usize,         // 读端数量
+    pub writers: usize,         // 写端数量
+    pub read_queue: WaitQueue,  // 等待读取的任务
+    pub write_queue: WaitQueue, // 等待写入的任务
+    pub ino: u64                // 读端和写端共享的inode
 }
 
 #[derive(Clone)]
-pub struct PipeBuf(pub Arc<RefCell<PipeBufInner>>);
+pub struct PipeBuf(pub Arc<RwLock<PipeBufInner>>);
 
 impl PipeBuf {
     // 创建pipeBuf
     pub fn new() -> Self {
-        Self(Arc::new(RefCell::new(PipeBufInner {
-            buf: Vec::new(),
+        Self(Arc::new(RwLock::new(PipeBufInner {
+            buf: vec![0u8; PIPE_BUF_SIZE],
             read_offset: 0,
-            write_offset: 0
+            write_offset: 0,
+            len: 0,
+            readers: 0,
+            writers: 0,
+            read_queue: WaitQueue::new(),
+            write_queue: WaitQueue::new(),
+            ino: alloc_ino()
         })))
     }
-    // 读取字节
-    pub fn read_at(&self, mut pos: usize, buf: &mut [u8]) -> usize {
-        let mut read_index = 0;
-        let pipe = self.0.borrow_mut();
-        loop {
-            if read_index >= buf.len() {
-                break;
-            }
-
-            if pos < pipe.buf.len() {
-                buf[read_index] = pipe.buf[pos];
-            } else {
-                break;
-            }
-
-            pos += 1;
-            read_index += 1;
+
+    // 读取字节 返回读取的长度
+    pub fn read(&self, buf: &mut [u8]) -> usize {
+        let mut pipe = self.0.write();
+        let read_len = if buf.len() < pipe.len { buf.len() } else { pipe.len };
+        for i in 0..read_len {
+            let offset = pipe.read_offset;
+            buf[i] = pipe.buf[offset];
+            pipe.read_offset = (offset + 1) % PIPE_BUF_SIZE;
         }
-        read_index
+        pipe.len -= read_len;
+        // 唤醒等待写入的任务
+        if read_len > 0 {
+            pipe.write_queue.notify_all();
+            notify_poll();
+        }
+        read_len
     }
 
-    pub fn write_at(&self, mut pos: usize, buf: &[u8], count: usize) -> usize{
-        let mut write_index = 0;
-        let mut pipe = self.0.borrow_mut();
-
-        if pipe.buf.len() > 4096 {
-            let start = pipe.buf.len() - 4096;
-            pipe.buf = pipe.buf[start..].to_vec();
+    // 写入字节 返回写入的长度 空间不足时只写入部分
+    // 缓冲区已满或者空间不足以原子地写入时返回EAGAIN
+    pub fn write(&self, buf: &[u8]) -> usize {
+        let mut pipe = self.0.write();
+        let remain = PIPE_BUF_SIZE - pipe.len;
+        if !buf.is_empty() && (remain == 0 || (buf.len() <= PIPE_BUF && remain < buf.len())) {
+            return EAGAIN;
         }
-
-        loop {
-            if write_index >= buf.len() || write_index >= count {
-                break;
-            }
-            
-            // queue.push_back(buf[write_index]);
-            pipe.buf.push(buf[write_index]);
-            pos += 1;
-            write_index += 1;
+        let write_len = if buf.len() < remain { buf.len() } else { remain };
+        for i in 0..write_len {
+            let offset = pipe.write_offset;
+            pipe.buf[offset] = buf[i];
+            pipe.write_offset = (offset + 1) % PIPE_BUF_SIZE;
         }
-        // 如果栈过大则重新分配
-        write_index
+        pipe.len += write_len;
+        // 唤醒等待读取的任务
+        if write_len > 0 {
+            pipe.read_queue.notify_all();
+            notify_poll();
+        }
+        write_len
     }
 
     // 获取可获取的大小
     pub fn available(&self) -> usize {
-        let pipe = self.0.borrow_mut();
-        pipe.write_offset - pipe.read_offset
+        self.0.read().len
+    }
+
+    // 读取时是否不需要等待 有数据或者写端全部关闭
+    pub fn read_ready(&self) -> bool {
+        let pipe = self.0.read();
+        pipe.len > 0 || pipe.writers == 0
+    }
+
+    // 写入时是否不需要等待 有空闲空间或者读端全部关闭
+    pub fn write_ready(&self) -> bool {
+        let pipe = self.0.read();
+        pipe.len < PIPE_BUF_SIZE || pipe.readers == 0
+    }
+
+    // 是否还有读端
+    pub fn has_reader(&self) -> bool {
+        self.0.read().readers > 0
+    }
+
+    // 等待数据写入 持有管道锁时再次检查 检查后写入的数据会唤醒等待者
+    // 已经可以读取时不等待 直接重新执行系统调用
+    pub fn wait_read(&self, task: Arc<Task>) {
+        let mut pipe = self.0.write();
+        if pipe.len == 0 && pipe.writers > 0 {
+            pipe.read_queue.wait(task);
+        }
+    }
+
+    // 等待数据被读取
+    pub fn wait_write(&self, task: Arc<Task>) {
+        let mut pipe = self.0.write();
+        if pipe.len >= PIPE_BUF_SIZE && pipe.readers > 0 {
+            pipe.write_queue.wait(task);
+        }
     }
 
-    pub fn get_size(&self) -> usize {
-        let pipe = self.0.borrow_mut();
-        pipe.buf.len()
+    // 获取管道信息 与Linux相同大小为0
+    pub fn stat(&self) -> Kstat {
+        Kstat::new(DEV_PIPE, self.0.read().ino, S_IFIFO | 0o600, 0)
     }
 }
 
@@ -83,6 +138,46 @@ pub struct PipeReader(PipeBuf);
 
 pub struct PipeWriter(PipeBuf);
 
+impl PipeReader {
+    pub fn new(pipe_buf: PipeBuf) -> Self {
+        pipe_buf.0.write().readers += 1;
+        Self(pipe_buf)
+    }
+}
+
+impl PipeWriter {
+    pub fn new(pipe_buf: PipeBuf) -> Self {
+        pipe_buf.0.write().writers += 1;
+        Self(pipe_buf)
+    }
+}
+
+// 所有的文件描述符关闭后 减少读端数量
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        let mut pipe = self.0.0.write();
+        pipe.readers -= 1;
+        // 读端全部关闭 唤醒写入的任务返回EPIPE
+        if pipe.readers == 0 {
+            pipe.write_queue.notify_all();
+            notify_poll();
+        }
+    }
+}
+
+// 所有的文件描述符关闭后 减少写端数量
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        let mut pipe = self.0.0.write();
+        pipe.writers -= 1;
+        // 写端全部关闭 唤醒读取的任务返回EOF
+        if pipe.writers == 0 {
+            pipe.read_queue.notify_all();
+            notify_poll();
+        }
+    }
+}
+
 impl FileOP for PipeReader {
     fn readable(&self) -> bool {
         true
@@ -92,19 +187,28 @@ impl FileOP for PipeReader {
         false
     }
 
-    fn read_at(&self, pos: usize, data: &mut [u8]) -> usize {
-        debug!("write?");
-        self.0.read_at(pos, data)
+    fn read_at(&self, _pos: usize, data: &mut [u8]) -> usize {
+        self.0.read(data)
     }
 
     fn write_at(&self, _pos: usize, _data: &[u8], _count: usize) -> usize {
-        debug!("write?");
-        todo!()
+        EPIPE
     }
 
     fn get_size(&self) -> usize {
-        // self.0.available()
-        self.0.get_size()
+        self.0.available()
+    }
+
+    fn read_ready(&self) -> bool {
+        self.0.read_ready()
+    }
+
+    fn wait_read(&self, task: Arc<Task>) {
+        self.0.wait_read(task)
+    }
+
+    fn stat(&self) -> Kstat {
+        self.0.stat()
     }
 }
 
@@ -118,23 +222,39 @@ impl FileOP for PipeWriter {
     }
 
     fn read_at(&self, _pos: usize, _data: &mut [u8]) -> usize {
-        debug!("write?");
-        todo!()
+        0
     }
 
-    fn write_at(&self, pos: usize, data: &[u8], count: usize) -> usize {
-        debug!("write?");
-        self.0.write_at(pos, data, count)
+    fn write_at(&self, _pos: usize, data: &[u8], count: usize) -> usize {
+        // 读端已经全部关闭
+        if !self.0.has_reader() {
+            return EPIPE;
+        }
+        let count = if count < data.len() { count } else { data.len() };
+        self.0.write(&data[..count])
     }
 
     fn get_size(&self) -> usize {
-        self.0.get_size()
+        self.0.available()
+    }
+
+    fn write_ready(&self) -> bool {
+        self.0.write_ready()
+    }
+
+    fn wait_write(&self, task: Arc<Task>) {
+        self.0.wait_write(task)
+    }
+
+    fn stat(&self) -> Kstat {
+        self.0.stat()
     }
 }
 
-pub fn new_pipe() -> (FileDesc, FileDesc) {
+// 创建管道 flags为读端和写端共同的状态标志
+pub fn new_pipe(flags: OpenFlags) -> (FileDesc, FileDesc) {
     let pipe_buf = PipeBuf::new();
-    let pipe_reader  = FileDesc::new(Rc::new(PipeReader(pipe_buf.clone())));
-    let pipe_writer = FileDesc::new(Rc::new(PipeWriter(pipe_buf.clone())));
+    let pipe_reader  = FileDesc::with_flags(Arc::new(PipeReader::new(pipe_buf.clone())), OpenFlags::RDONLY | flags);
+    let pipe_writer = FileDesc::with_flags(Arc::new(PipeWriter::new(pipe_buf.clone())), OpenFlags::WRONLY | flags);
     (pipe_reader, pipe_writer)
-}
\ No newline at end of file
+}
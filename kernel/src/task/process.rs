@@ -1,89 +1,229 @@
-use core::cell::RefCell;
+use crate::sync::rwlock::RwLock;
 use alloc::vec::Vec;
-use alloc::rc::Rc;
-use alloc::rc::Weak;
-use crate::memory::page_table::PageMappingManager;
-use crate::memory::mem_set::MemSet;
+use alloc::sync::Arc;
+use alloc::sync::Weak;
 use crate::memory::addr::VirtAddr;
 use crate::runtime_err::RuntimeError;
 use crate::interrupt::timer::TMS;
+use crate::interrupt::timer::IntervalTimer;
+use crate::interrupt::timer::cancel_timer;
 use crate::fs::filetree::INode;
 use super::task::Task;
 use super::task::TaskStatus;
-use super::stack::UserStack;
 use super::fd_table::FDTable;
 use super::task_scheduler::kill_process;
+use super::task_scheduler::kick_task;
 use super::signal::SigAction;
-use super::user_heap::UserHeap;
+use super::signal::SigSet;
+use super::signal::NSIG;
+use super::signal::Signal;
+use super::signal::is_stop_signal;
+use super::signal::{SignalDefault, default_action, SIG_DFL, SIG_IGN};
+use super::mem_space::MemSpace;
+use super::wait_queue::WaitQueue;
+use super::cred::Credentials;
+use super::rlimit::{RLimit, RLIM_NLIMITS, RLIM_INFINITY, RLIMIT_CPU, RLIMIT_NOFILE, default_rlimits};
+use crate::sys_call::remove_vfork_wait;
+use crate::sys_call::SignalFlag;
+use crate::sys_call::CloneFlags;
+use crate::sync::mutex::Mutex;
+
+// wait4获取的状态 正常退出时高8位为退出代码
+pub fn exit_status(exit_code: usize) -> usize {
+    (exit_code & 0xff) << 8
+}
+
+// 被信号结束时低7位为信号编号 core dump时设置0x80
+pub fn signaled_status(signum: usize, core: bool) -> usize {
+    signum | if core { 0x80 } else { 0 }
+}
+
+// 被信号停止时低8位为0x7f 高8位为信号编号
+pub fn stopped_status(signum: usize) -> usize {
+    (signum << 8) | 0x7f
+}
+
+// 停止的进程继续运行
+pub const CONTINUED_STATUS: usize = 0xffff;
+
+// init进程的pid 孤儿进程交给init进程 由init进程回收
+pub const INIT_PID: usize = 1;
+
+lazy_static! {
+    // init进程 没有任务 只负责收养孤儿进程
+    pub static ref INIT_PROCESS: Mutex<Arc<RwLock<Process>>> = Mutex::new(Process::new_init());
+    // 等待处理的父进程通知
+    static ref PARENT_EVENTS: Mutex<Vec<ParentEvent>> = Mutex::new(Vec::new());
+}
+
+// 子进程停止、继续或退出后通知父进程
+// 进程的锁按照父进程到子进程的顺序获取 持有子进程时不能获取父进程 通知由调度循环在不持有锁时处理
+struct ParentEvent {
+    parent: Option<Weak<RwLock<Process>>>,
+    child: usize,                               // 子进程的地址 退出后从父进程中移除时使用
+    exited: bool,
+    orphans: Vec<Arc<RwLock<Process>>>          // 退出的进程留下的子进程 交给init进程
+}
+
+impl ParentEvent {
+    // 唤醒在wait4中等待的父进程并发送SIGCHLD
+    // 父进程设置SA_NOCLDSTOP时 子进程停止和继续不发送SIGCHLD
+    fn handle(self) {
+        if !self.orphans.is_empty() {
+            let init = init_process();
+            for child in self.orphans {
+                init.write().adopt(&init, child);
+            }
+        }
+        let parent = match self.parent.and_then(|x| x.upgrade()) {
+            Some(parent) => parent,
+            None => return
+        };
+        let mut parent = parent.write();
+        // 结束vfork等待
+        if self.exited {
+            remove_vfork_wait(parent.pid);
+        }
+        parent.wait_queue.notify_all();
+        let sigchld = Signal::SIGCHLD as usize;
+        let flags = SignalFlag::from_bits_truncate(parent.sig_actions.read()[sigchld].flags);
+        if self.exited || !flags.contains(SignalFlag::SA_NOCLDSTOP) {
+            parent.send_signal(sigchld);
+        }
+        // 父进程忽略SIGCHLD或设置SA_NOCLDWAIT时不保留僵尸进程 直接回收
+        if self.exited && parent.no_zombies() {
+            parent.children.retain(|x| x.data_ptr() as usize != self.child);
+        }
+    }
+}
+
+// 处理等待中的父进程通知
+pub fn handle_parent_events() {
+    let events = core::mem::take(&mut *PARENT_EVENTS.lock());
+    for event in events {
+        event.handle();
+    }
+}
+
+// 获取init进程
+pub fn init_process() -> Arc<RwLock<Process>> {
+    INIT_PROCESS.lock().clone()
+}
 
 pub struct Process {
     pub pid: usize,                             // 进程id
-    pub parent: Option<Weak<RefCell<Process>>>, // 父进程
-    pub pmm: Rc<PageMappingManager>,            // 内存页映射管理 
-    pub mem_set: MemSet,                        // 内存使用集
+    pub pgid: usize,                            // 进程组id
+    pub sid: usize,                             // 会话id
+    pub parent: Option<Weak<RwLock<Process>>>, // 父进程
+    pub mm: Arc<RwLock<MemSpace>>,              // 地址空间 CLONE_VM时共享
     pub tasks: Vec<Weak<Task>>,                 // 任务管理器
     pub entry: VirtAddr,                        // 入口地址
-    pub stack: UserStack,                       // 用户栈
-    pub heap: UserHeap,                         // 用户堆
-    pub workspace: Rc<INode>,                   // 工作目录
-    pub fd_table: FDTable,                      // 文件描述表
+    pub workspace: Arc<RwLock<Arc<INode>>>,      // 工作目录 CLONE_FS时共享
+    pub fd_table: Arc<RwLock<FDTable>>,         // 文件描述表 CLONE_FILES时共享
     pub tms: TMS,                               // 时间记录结构
-    pub sig_actions: [SigAction; 64],           // 信号结构
-    pub children: Vec<Rc<RefCell<Process>>>,    // 子结构
-    pub exit_code: Option<usize>                // 退出代码
+    pub sig_actions: Arc<RwLock<[SigAction; NSIG + 1]>>,  // 信号结构 使用信号编号作为下标 CLONE_SIGHAND时共享
+    pub sig_pending: SigSet,                    // 发送给进程的信号
+    pub children: Vec<Arc<RwLock<Process>>>,    // 子结构
+    pub exit_code: Option<usize>,               // 退出状态 设置后为僵尸进程 等待父进程回收
+    pub stopped: bool,                          // 进程被信号停止
+    pub stop_status: Option<usize>,             // 停止或继续的状态 等待父进程获取
+    pub wait_queue: WaitQueue,                  // 等待子进程退出的任务
+    pub real_timer: IntervalTimer,              // ITIMER_REAL 间隔定时器
+    pub rlimits: [RLimit; RLIM_NLIMITS],        // 资源限制 使用资源类型作为下标
+    pub cred: Credentials                       // 用户和组
 }
 
 impl Process {
-    pub fn new(pid: usize, parent: Option<Weak<RefCell<Process>>>)
-        -> Result<(Rc<RefCell<Process>>, Rc<Task>), RuntimeError> {
-        let pmm = Rc::new(PageMappingManager::new()?);
-        let heap = UserHeap::new(pmm.clone())?;
+    pub fn new(pid: usize, parent: Option<Weak<RwLock<Process>>>)
+        -> Result<(Arc<RwLock<Process>>, Arc<Task>), RuntimeError> {
         let process = Self { 
             pid, 
+            pgid: pid,
+            sid: pid,
             parent, 
-            pmm: pmm.clone(), 
-            mem_set: MemSet::new(), 
+            mm: Arc::new(RwLock::new(MemSpace::new()?)),
             tasks: vec![], 
             entry: 0usize.into(), 
-            stack: UserStack::new(pmm.clone())?, 
-            heap, 
-            workspace: INode::root(),
-            fd_table: FDTable::new(),
+            workspace: Arc::new(RwLock::new(INode::root())),
+            fd_table: Arc::new(RwLock::new(FDTable::new())),
             children: vec![],
-            sig_actions: [SigAction::empty(); 64],
+            sig_actions: Arc::new(RwLock::new([SigAction::empty(); NSIG + 1])),
+            sig_pending: SigSet::new(0),
             tms: TMS::new(),
-            exit_code: None
+            exit_code: None,
+            stopped: false,
+            stop_status: None,
+            wait_queue: WaitQueue::new(),
+            real_timer: IntervalTimer::new(),
+            rlimits: default_rlimits(),
+            cred: Credentials::root()
         };
         // 创建默认任务
-        let process = Rc::new(RefCell::new(process));
-        // 添加到子任务
-        let task = Task::new(0, process.clone());
-        // process.borrow_mut().tasks.push(Rc::downgrade(&task));
+        let process = Arc::new(RwLock::new(process));
+        // 添加到子任务 主线程的tid与pid相同
+        let task = Task::new(pid, process.clone());
+        // process.write().tasks.push(Arc::downgrade(&task));
         Ok((process, task))
     }
 
-    pub fn fork(pid: usize, parent: Rc<RefCell<Process>>) -> Result<(Rc<RefCell<Process>>, Rc<Task>), RuntimeError> {
-        let parent_inner = parent.borrow_mut();
-        let pmm = Rc::new(PageMappingManager::new()?);
-        pmm.add_mapping_by_set(&parent_inner.mem_set)?;
-        pmm.add_mapping_by_set(&parent_inner.heap.mem_set)?;
-        let process = Rc::new(RefCell::new(Self { 
+    // 创建init进程 忽略SIGCHLD 退出的子进程直接回收
+    fn new_init() -> Arc<RwLock<Process>> {
+        let (process, _) = Self::new(INIT_PID, None).expect("can't create init process");
+        let mut inner = process.write();
+        inner.tasks.clear();
+        inner.sig_actions.write()[Signal::SIGCHLD as usize].handler = SIG_IGN;
+        drop(inner);
+        process
+    }
+
+    // 创建子进程 flags中的CLONE_VM CLONE_FILES CLONE_FS CLONE_SIGHAND分别决定是否共享
+    // 地址空间 文件描述符表 工作目录和信号处理 不共享时复制一份
+    pub fn fork(pid: usize, parent: Arc<RwLock<Process>>, flags: CloneFlags) -> Result<(Arc<RwLock<Process>>, Arc<Task>), RuntimeError> {
+        let parent_inner = parent.read();
+        let mm = if flags.contains(CloneFlags::CLONE_VM) {
+            parent_inner.mm.clone()
+        } else {
+            Arc::new(RwLock::new(parent_inner.mm.read().clone_with_data()?))
+        };
+        let fd_table = if flags.contains(CloneFlags::CLONE_FILES) {
+            parent_inner.fd_table.clone()
+        } else {
+            Arc::new(RwLock::new(parent_inner.fd_table.read().clone()))
+        };
+        let workspace = if flags.contains(CloneFlags::CLONE_FS) {
+            parent_inner.workspace.clone()
+        } else {
+            Arc::new(RwLock::new(parent_inner.workspace.read().clone()))
+        };
+        let sig_actions = if flags.contains(CloneFlags::CLONE_SIGHAND) {
+            parent_inner.sig_actions.clone()
+        } else {
+            Arc::new(RwLock::new(*parent_inner.sig_actions.read()))
+        };
+        let process = Arc::new(RwLock::new(Self { 
             pid, 
-            parent: Some(Rc::downgrade(&parent)), 
-            pmm: pmm, 
-            mem_set: parent_inner.mem_set.clone(), 
+            // 子进程加入父进程的进程组和会话
+            pgid: parent_inner.pgid,
+            sid: parent_inner.sid,
+            parent: Some(Arc::downgrade(&parent)), 
+            mm,
             tasks: vec![], 
             entry: parent_inner.entry, 
-            stack: parent_inner.stack.clone(), 
-            heap: parent_inner.heap.clone(), 
-            workspace: INode::root(),
-            fd_table: parent_inner.fd_table.clone(),
+            workspace,
+            fd_table,
             children: vec![],
-            sig_actions: [SigAction::empty(); 64],
+            sig_actions,
+            sig_pending: SigSet::new(0),
             tms: TMS::new(),
-            exit_code: None
+            exit_code: None,
+            stopped: false,
+            stop_status: None,
+            wait_queue: WaitQueue::new(),
+            real_timer: IntervalTimer::new(),
+            rlimits: parent_inner.rlimits,
+            cred: parent_inner.cred.clone()
         }));
-        let task = Task::new(0, process.clone());
+        let task = Task::new(pid, process.clone());
         Ok((process, task))
     }
 
@@ -91,12 +231,7 @@ impl Process {
     pub fn wait(&self) {
         // TODO: 进程进入等待状态  等待目标进程结束
         // let task = self.get_task(0);
-        // task.inner.borrow_mut().status = TaskStatus::WAITING;
-    }
-
-    pub fn new_heap(&mut self) -> Result<(), RuntimeError>{
-        self.heap = UserHeap::new(self.pmm.clone())?;
-        Ok(())
+        // task.inner.write().status = TaskStatus::WAITING;
     }
 
     // 判断是否在等待状态
@@ -104,7 +239,7 @@ impl Process {
         // tasks的len 一定大于 0
         let task = self.get_task(0);
         // 如果父进程在等待 则直接释放资源 并改变父进程的状态
-        if task.inner.borrow().status == TaskStatus::WAITING {
+        if task.status() == TaskStatus::WAITING {
             true
         } else {
             false
@@ -112,38 +247,178 @@ impl Process {
     }
 
     // 获取task 任务
-    pub fn get_task(&self, index: usize) -> Rc<Task> {
+    pub fn get_task(&self, index: usize) -> Arc<Task> {
         if index >= self.tasks.len() {
             panic!("in process.rs index >= task.len()");
         }
-        self.tasks[0].upgrade().unwrap()
+        self.tasks[index].upgrade().unwrap()
     }
 
-    // 结束进程
+    // 根据tid获取进程中的线程
+    pub fn get_task_by_tid(&self, tid: usize) -> Option<Arc<Task>> {
+        self.tasks.iter().filter_map(|x| x.upgrade()).find(|x| x.tid == tid)
+    }
+
+    // 向进程发送信号 唤醒一个没有屏蔽该信号的线程处理
+    pub fn send_signal(&mut self, signum: usize) {
+        self.prepare_signal(signum);
+        self.sig_pending.add(signum);
+        if self.is_ignored(signum) {
+            return;
+        }
+        let task = self.tasks.iter().filter_map(|x| x.upgrade())
+            .find(|x| !x.signal.lock().sig_mask.contains(signum));
+        if let Some(task) = task {
+            task.interrupt_wait();
+            kick_task(&task);
+        }
+    }
+
+    // 判断信号是否被忽略 被忽略的信号不会打断等待
+    pub fn is_ignored(&self, signum: usize) -> bool {
+        let handler = self.sig_actions.read()[signum].handler;
+        handler == SIG_IGN || (handler == SIG_DFL
+            && matches!(default_action(signum), SignalDefault::Ignore | SignalDefault::Continue))
+    }
+
+    // 信号发送时立即生效的部分
+    // SIGCONT和SIGKILL使停止的进程继续运行并丢弃待处理的停止信号 停止信号丢弃待处理的SIGCONT
+    pub fn prepare_signal(&mut self, signum: usize) {
+        let tasks: Vec<_> = self.tasks.iter().filter_map(|x| x.upgrade()).collect();
+        if signum == Signal::SIGCONT as usize || signum == Signal::SIGKILL as usize {
+            for stop_signal in (1..=NSIG).filter(|x| is_stop_signal(*x)) {
+                self.sig_pending.remove(stop_signal);
+                for task in &tasks {
+                    task.signal.lock().sig_pending.remove(stop_signal);
+                }
+            }
+            if self.stopped {
+                self.resume();
+            }
+        } else if is_stop_signal(signum) {
+            self.sig_pending.remove(Signal::SIGCONT as usize);
+            for task in &tasks {
+                task.signal.lock().sig_pending.remove(Signal::SIGCONT as usize);
+            }
+        }
+    }
+
+    // 停止进程中的所有线程 通知父进程 其他核心上运行的线程进入内核后停止
+    pub fn stop(&mut self, signum: usize) {
+        self.stopped = true;
+        for task in self.tasks.iter().filter_map(|x| x.upgrade()) {
+            task.set_status(TaskStatus::STOP);
+            kick_task(&task);
+        }
+        self.stop_status = Some(stopped_status(signum));
+        self.notify_parent(false, Vec::new());
+    }
+
+    // 停止的进程继续运行 通知父进程
+    pub fn resume(&mut self) {
+        self.stopped = false;
+        for task in self.tasks.iter().filter_map(|x| x.upgrade()) {
+            task.change_status(TaskStatus::STOP, TaskStatus::READY);
+        }
+        self.stop_status = Some(CONTINUED_STATUS);
+        self.notify_parent(false, Vec::new());
+    }
+
+    // 通知父进程 由调度循环处理
+    fn notify_parent(&self, exited: bool, orphans: Vec<Arc<RwLock<Process>>>) {
+        PARENT_EVENTS.lock().push(ParentEvent {
+            parent: self.parent.clone(),
+            child: self as *const Process as usize,
+            exited,
+            orphans
+        });
+    }
+
+    // 结束进程 exit_code为wait4获取的状态
     pub fn exit(&mut self, exit_code: usize) {
+        if self.exit_code.is_some() {
+            return;
+        }
+        // 结束所有线程 等待其他核心离开进程的页表后再释放内存
+        kill_process(self.pid);
         self.release();
-        // 如果没有子进程
+        // 关闭文件描述符 使管道等资源可以感知到关闭 共享的文件描述符表由最后一个进程关闭
+        let fd_table = core::mem::replace(&mut self.fd_table, Arc::new(RwLock::new(FDTable::empty())));
+        if Arc::strong_count(&fd_table) == 1 {
+            fd_table.write().release();
+        }
+        // 取消间隔定时器
+        if let Some(timer) = self.real_timer.timer.take() {
+            cancel_timer(timer);
+        }
+        // 成为僵尸进程 只保留退出状态和运行时间
         self.exit_code = Some(exit_code);
-        // 进程回收
-        kill_process(self.pid);
+        self.stopped = false;
+        self.stop_status = None;
+        self.sig_pending = SigSet::new(0);
+        self.tasks.clear();
+        // 子进程交给init进程 已经退出的子进程由init进程回收
+        let orphans = self.children.drain(..).collect();
+        self.notify_parent(true, orphans);
     }
 
-    // 重置内存信息
+    // 忽略SIGCHLD或设置SA_NOCLDWAIT时不保留僵尸进程
+    fn no_zombies(&self) -> bool {
+        let action = self.sig_actions.read()[Signal::SIGCHLD as usize];
+        action.handler == SIG_IGN
+            || SignalFlag::from_bits_truncate(action.flags).contains(SignalFlag::SA_NOCLDWAIT)
+    }
+
+    // 收养孤儿进程 this为当前进程 已经退出的子进程唤醒等待的任务
+    pub fn adopt(&mut self, this: &Arc<RwLock<Process>>, child: Arc<RwLock<Process>>) {
+        let mut child_inner = child.write();
+        child_inner.parent = Some(Arc::downgrade(this));
+        let zombie = child_inner.exit_code.is_some();
+        drop(child_inner);
+        if !zombie || !self.no_zombies() {
+            self.children.push(child);
+        }
+        if zombie {
+            self.wait_queue.notify_all();
+        }
+    }
+
+    // 执行新程序时使用新的地址空间 不再与其他进程共享地址空间 文件描述符表和信号处理
     pub fn reset(&mut self) -> Result<(), RuntimeError>{
-        let pmm = Rc::new(PageMappingManager::new()?);
-        let mem_set = MemSet::new();
-        self.pmm = pmm;
-        self.mem_set = mem_set;
-        self.stack = UserStack::new(self.pmm.clone())?;
+        self.release();
+        self.mm = Arc::new(RwLock::new(MemSpace::new()?));
+        let fd_table = self.fd_table.read().clone();
+        self.fd_table = Arc::new(RwLock::new(fd_table));
+        let sig_actions = *self.sig_actions.read();
+        self.sig_actions = Arc::new(RwLock::new(sig_actions));
         Ok(())
     }
 
-    // 释放内存
+    // 文件描述符数量限制 新的fd需要小于此值
+    pub fn nofile_limit(&self) -> usize {
+        self.rlimits[RLIMIT_NOFILE].rlim_cur
+    }
+
+    // 记录运行时间后检查RLIMIT_CPU 超出软限制后每秒发送SIGXCPU 超出硬限制时发送SIGKILL
+    pub fn check_cpu_limit(&mut self, last_time: u64) {
+        let limit = self.rlimits[RLIMIT_CPU];
+        let last_sec = last_time as usize / 1_000_000;
+        let sec = (self.tms.tms_utime + self.tms.tms_stime) as usize / 1_000_000;
+        if sec == last_sec {
+            return;
+        }
+        if limit.rlim_max != RLIM_INFINITY && sec >= limit.rlim_max {
+            self.send_signal(Signal::SIGKILL as usize);
+        } else if limit.rlim_cur != RLIM_INFINITY && sec >= limit.rlim_cur {
+            self.send_signal(Signal::SIGXCPU as usize);
+        }
+    }
+
+    // 释放内存 地址空间没有其他进程使用时立即释放 否则由最后一个使用的进程释放
     pub fn release(&mut self) {
-        self.stack.release();
-        self.heap.mem_set.release();
-        self.mem_set.release();
-        self.pmm.release();
+        if Arc::strong_count(&self.mm) == 1 {
+            self.mm.write().release();
+        }
     }
 }
     
\ No newline at end of file
@@ -0,0 +1,68 @@
+// 资源类型
+pub const RLIMIT_CPU: usize = 0;
+pub const RLIMIT_FSIZE: usize = 1;
+pub const RLIMIT_DATA: usize = 2;
+pub const RLIMIT_STACK: usize = 3;
+pub const RLIMIT_CORE: usize = 4;
+pub const RLIMIT_RSS: usize = 5;
+pub const RLIMIT_NPROC: usize = 6;
+pub const RLIMIT_NOFILE: usize = 7;
+pub const RLIMIT_MEMLOCK: usize = 8;
+pub const RLIMIT_AS: usize = 9;
+pub const RLIMIT_LOCKS: usize = 10;
+pub const RLIMIT_SIGPENDING: usize = 11;
+pub const RLIMIT_MSGQUEUE: usize = 12;
+pub const RLIMIT_NICE: usize = 13;
+pub const RLIMIT_RTPRIO: usize = 14;
+pub const RLIMIT_RTTIME: usize = 15;
+pub const RLIM_NLIMITS: usize = 16;
+
+// 没有限制
+pub const RLIM_INFINITY: usize = usize::MAX;
+
+// 默认的栈大小限制
+pub const DEFAULT_STACK_LIMIT: usize = 8 * 1024 * 1024;
+
+// 默认的文件描述符数量限制
+pub const DEFAULT_NOFILE: usize = 1024;
+pub const DEFAULT_NOFILE_MAX: usize = 4096;
+
+// 文件描述符数量限制的上限
+pub const NR_OPEN: usize = 1024 * 1024;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct RLimit {
+    pub rlim_cur: usize,    // 软限制
+    pub rlim_max: usize     // 硬限制 软限制不能超过硬限制
+}
+
+impl RLimit {
+    pub const fn new(rlim_cur: usize, rlim_max: usize) -> Self {
+        Self { rlim_cur, rlim_max }
+    }
+
+    // 没有限制
+    pub const fn infinity() -> Self {
+        Self::new(RLIM_INFINITY, RLIM_INFINITY)
+    }
+
+    // 判断value是否超出软限制
+    pub fn exceeded(&self, value: usize) -> bool {
+        self.rlim_cur != RLIM_INFINITY && value > self.rlim_cur
+    }
+}
+
+// 进程的默认资源限制
+pub fn default_rlimits() -> [RLimit; RLIM_NLIMITS] {
+    let mut rlimits = [RLimit::infinity(); RLIM_NLIMITS];
+    rlimits[RLIMIT_STACK] = RLimit::new(DEFAULT_STACK_LIMIT, RLIM_INFINITY);
+    rlimits[RLIMIT_CORE] = RLimit::new(0, RLIM_INFINITY);
+    rlimits[RLIMIT_NOFILE] = RLimit::new(DEFAULT_NOFILE, DEFAULT_NOFILE_MAX);
+    rlimits[RLIMIT_MEMLOCK] = RLimit::new(64 * 1024, 64 * 1024);
+    rlimits[RLIMIT_SIGPENDING] = RLimit::new(8192, 8192);
+    rlimits[RLIMIT_MSGQUEUE] = RLimit::new(819200, 819200);
+    rlimits[RLIMIT_NICE] = RLimit::new(0, 0);
+    rlimits[RLIMIT_RTPRIO] = RLimit::new(0, 0);
+    rlimits
+}
@@ -0,0 +1,246 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use crate::sys_call::is_vfork_wait;
+use super::task::Task;
+use super::task::TaskStatus;
+
+// 调度策略
+pub const SCHED_OTHER: usize = 0;
+pub const SCHED_FIFO: usize = 1;
+pub const SCHED_RR: usize = 2;
+pub const SCHED_BATCH: usize = 3;
+pub const SCHED_IDLE: usize = 5;
+
+// 实时优先级范围
+pub const MIN_RT_PRIO: usize = 1;
+pub const MAX_RT_PRIO: usize = 99;
+
+// nice 范围
+pub const MIN_NICE: isize = -20;
+pub const MAX_NICE: isize = 19;
+
+// nice为0时的权重
+const NICE_0_LOAD: usize = 1024;
+
+// 睡眠任务唤醒后最多领先的虚拟运行时间(微秒) 防止长时间睡眠的任务独占处理器
+const SLEEPER_CREDIT: usize = 10_000;
+
+// nice 到权重的映射 nice每增加1 获得的处理器时间减少约10%
+const NICE_TO_WEIGHT: [usize; 40] = [
+    /* -20 */ 88761, 71755, 56483, 46273, 36291,
+    /* -15 */ 29154, 23254, 18705, 14949, 11916,
+    /* -10 */ 9548, 7620, 6100, 4904, 3906,
+    /*  -5 */ 3121, 2501, 1991, 1586, 1277,
+    /*   0 */ 1024, 820, 655, 526, 423,
+    /*   5 */ 335, 272, 215, 172, 137,
+    /*  10 */ 110, 87, 70, 56, 45,
+    /*  15 */ 36, 29, 23, 18, 15,
+];
+
+// 任务的调度信息
+#[derive(Clone, Copy)]
+pub struct SchedEntity {
+    pub policy: usize,          // 调度策略
+    pub nice: isize,            // 普通任务的nice值
+    pub rt_priority: usize,     // 实时任务的优先级 1~99 越大越优先
+    pub vruntime: usize,        // 虚拟运行时间(微秒)
+    pub seq: usize,             // 进入队列的顺序
+    pub pi_base: Option<(usize, usize, isize)> // 优先级继承前的 (策略, 实时优先级, nice)
+}
+
+impl SchedEntity {
+    pub fn new() -> Self {
+        Self {
+            policy: SCHED_OTHER,
+            nice: 0,
+            rt_priority: 0,
+            vruntime: 0,
+            seq: 0,
+            pi_base: None
+        }
+    }
+
+    // fork时子任务继承调度策略 重新计算运行时间 不继承临时提升的优先级
+    pub fn fork(&self) -> Self {
+        let mut sched = Self {
+            vruntime: 0,
+            seq: 0,
+            ..*self
+        };
+        sched.restore();
+        sched
+    }
+
+    // 是否为实时任务
+    pub fn is_realtime(&self) -> bool {
+        self.policy == SCHED_FIFO || self.policy == SCHED_RR
+    }
+
+    // 根据nice获取权重
+    pub fn weight(&self) -> usize {
+        NICE_TO_WEIGHT[(self.nice - MIN_NICE) as usize]
+    }
+
+    // 获取用于比较的优先级 越大越优先 实时任务高于所有普通任务
+    pub fn prio(&self) -> usize {
+        if self.is_realtime() {
+            (MAX_NICE - MIN_NICE) as usize + 1 + self.rt_priority
+        } else {
+            (MAX_NICE - self.nice) as usize
+        }
+    }
+
+    // 优先级继承 等待者的优先级更高时提升到等待者的优先级 返回是否发生改变
+    pub fn inherit(&mut self, waiter: &SchedEntity) -> bool {
+        if waiter.prio() <= self.prio() {
+            return false;
+        }
+        if self.pi_base.is_none() {
+            self.pi_base = Some((self.policy, self.rt_priority, self.nice));
+        }
+        self.policy = waiter.policy;
+        self.rt_priority = waiter.rt_priority;
+        self.nice = waiter.nice;
+        true
+    }
+
+    // 恢复优先级继承前的调度参数 返回是否发生改变
+    pub fn restore(&mut self) -> bool {
+        match self.pi_base.take() {
+            Some((policy, rt_priority, nice)) => {
+                self.policy = policy;
+                self.rt_priority = rt_priority;
+                self.nice = nice;
+                true
+            }
+            None => false
+        }
+    }
+}
+
+// 判断任务是否可以运行 等待中和被信号停止的任务无法运行
+pub fn is_runnable(task: &Arc<Task>) -> bool {
+    let status = task.check_status();
+    status != TaskStatus::WAITING && status != TaskStatus::STOP && !is_vfork_wait(task.pid)
+}
+
+// 调度器 管理除当前运行任务以外的所有任务
+pub trait Scheduler: Send {
+    // 添加新的任务
+    fn add_task(&mut self, task: Arc<Task>);
+    // 取出下一个可以运行的任务
+    fn pick_next(&mut self) -> Option<Arc<Task>>;
+    // 放回运行结束的任务 runtime为本次运行的时间(微秒)
+    fn put_prev(&mut self, task: Arc<Task>, runtime: usize);
+    // 移除满足条件的任务 返回被移除的任务 由调用者在释放调度器锁之后释放
+    fn remove(&mut self, f: &dyn Fn(&Arc<Task>) -> bool) -> Vec<Arc<Task>>;
+    // 获取所有的任务
+    fn tasks(&self) -> Vec<Arc<Task>>;
+    // 获取任务数量
+    fn len(&self) -> usize;
+}
+
+// 公平调度器
+// 实时任务按照优先级执行 普通任务按照虚拟运行时间执行 虚拟运行时间按照nice对应的权重增长
+pub struct FairScheduler {
+    rt_queue: BTreeMap<(usize, usize), Arc<Task>>,   // (MAX_RT_PRIO - 优先级, 顺序) -> 实时任务
+    fair_queue: BTreeMap<(usize, usize), Arc<Task>>, // (虚拟运行时间, 顺序) -> 普通任务
+    min_vruntime: usize,                            // 最小虚拟运行时间 只增不减
+    next_seq: usize                                 // 下一个顺序
+}
+
+impl FairScheduler {
+    pub fn new() -> Self {
+        Self {
+            rt_queue: BTreeMap::new(),
+            fair_queue: BTreeMap::new(),
+            min_vruntime: 0,
+            next_seq: 1
+        }
+    }
+
+    // 加入队列 keep_seq为true时保持原来的顺序
+    fn insert(&mut self, task: Arc<Task>, keep_seq: bool) {
+        let sched = {
+            let mut sched = task.sched.lock();
+            if !keep_seq || sched.seq == 0 {
+                sched.seq = self.next_seq;
+                self.next_seq += 1;
+            }
+            *sched
+        };
+        if sched.is_realtime() {
+            self.rt_queue.insert((MAX_RT_PRIO - sched.rt_priority, sched.seq), task);
+        } else {
+            self.fair_queue.insert((sched.vruntime, sched.seq), task);
+        }
+    }
+
+    // 从队列中取出第一个可以运行的任务
+    fn pick_from(queue: &mut BTreeMap<(usize, usize), Arc<Task>>) -> Option<Arc<Task>> {
+        let key = queue.iter().find(|(_, task)| is_runnable(task)).map(|(key, _)| *key)?;
+        queue.remove(&key)
+    }
+}
+
+impl Scheduler for FairScheduler {
+    fn add_task(&mut self, task: Arc<Task>) {
+        // 新任务从当前的最小虚拟运行时间开始
+        let mut sched = task.sched.lock();
+        if sched.vruntime < self.min_vruntime {
+            sched.vruntime = self.min_vruntime;
+        }
+        drop(sched);
+        self.insert(task, false);
+    }
+
+    fn pick_next(&mut self) -> Option<Arc<Task>> {
+        if let Some(task) = Self::pick_from(&mut self.rt_queue) {
+            return Some(task);
+        }
+        let task = Self::pick_from(&mut self.fair_queue)?;
+        let mut sched = task.sched.lock();
+        // 限制睡眠后唤醒的任务领先的时间
+        if sched.vruntime + SLEEPER_CREDIT < self.min_vruntime {
+            sched.vruntime = self.min_vruntime - SLEEPER_CREDIT;
+        }
+        if sched.vruntime > self.min_vruntime {
+            self.min_vruntime = sched.vruntime;
+        }
+        drop(sched);
+        Some(task)
+    }
+
+    fn put_prev(&mut self, task: Arc<Task>, runtime: usize) {
+        let mut sched = task.sched.lock();
+        if !sched.is_realtime() {
+            sched.vruntime += runtime * NICE_0_LOAD / sched.weight();
+        }
+        // SCHED_FIFO 任务被抢占时保持在同优先级队列的队首 主动让出或等待时排到队尾
+        let keep_seq = sched.policy == SCHED_FIFO && task.status() == TaskStatus::RUNNING;
+        drop(sched);
+        self.insert(task, keep_seq);
+    }
+
+    fn remove(&mut self, f: &dyn Fn(&Arc<Task>) -> bool) -> Vec<Arc<Task>> {
+        let mut removed = Vec::new();
+        let mut filter = |x: &Arc<Task>| if f(x) {
+            removed.push(x.clone());
+            false
+        } else {
+            true
+        };
+        self.rt_queue.retain(|_, x| filter(x));
+        self.fair_queue.retain(|_, x| filter(x));
+        removed
+    }
+
+    fn tasks(&self) -> Vec<Arc<Task>> {
+        self.rt_queue.values().chain(self.fair_queue.values()).cloned().collect()
+    }
+
+    fn len(&self) -> usize {
+        self.rt_queue.len() + self.fair_queue.len()
+    }
+}
@@ -1,4 +1,22 @@
-use crate::interrupt::Context;
+use crate::interrupt::{Context, FpContext};
+
+// 信号数量 信号编号为1~64
+pub const NSIG: usize = 64;
+
+// 特殊的信号处理函数
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+// 备用信号栈的最小大小
+pub const MINSIGSTKSZ: usize = 2048;
+
+// si_code 由用户发送的信号
+pub const SI_USER: i32 = 0;
+
+// 没有设置restorer时使用的信号返回代码
+// li a7, 139 (SYS_RT_SIGRETURN)
+// ecall
+pub const SIG_TRAMPOLINE: [u32; 2] = [0x08b00893, 0x00000073];
 
 pub enum Signal {
     SIGHUP = 1,
@@ -68,6 +86,48 @@ pub enum Signal {
     SIGRT64 = 64,
 }
 
+// 没有处理函数时信号的默认行为
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SignalDefault {
+    Terminate,  // 结束进程
+    Core,       // 结束进程并标记core dump
+    Ignore,     // 忽略
+    Stop,       // 停止进程
+    Continue    // 停止的进程继续运行
+}
+
+// 获取信号的默认行为 实时信号默认结束进程
+pub fn default_action(signum: usize) -> SignalDefault {
+    const SIGQUIT: usize = Signal::SIGQUIT as usize;
+    const SIGILL: usize = Signal::SIGILL as usize;
+    const SIGTRAP: usize = Signal::SIGTRAP as usize;
+    const SIGABRT: usize = Signal::SIGABRT as usize;
+    const SIGBUS: usize = Signal::SIGBUS as usize;
+    const SIGFPE: usize = Signal::SIGFPE as usize;
+    const SIGSEGV: usize = Signal::SIGSEGV as usize;
+    const SIGXCPU: usize = Signal::SIGXCPU as usize;
+    const SIGXFSZ: usize = Signal::SIGXFSZ as usize;
+    const SIGSYS: usize = Signal::SIGSYS as usize;
+    const SIGCHLD: usize = Signal::SIGCHLD as usize;
+    const SIGURG: usize = Signal::SIGURG as usize;
+    const SIGWINCH: usize = Signal::SIGWINCH as usize;
+    const SIGCONT: usize = Signal::SIGCONT as usize;
+    match signum {
+        SIGQUIT | SIGILL | SIGTRAP | SIGABRT | SIGBUS | SIGFPE | SIGSEGV
+            | SIGXCPU | SIGXFSZ | SIGSYS => SignalDefault::Core,
+        SIGCHLD | SIGURG | SIGWINCH => SignalDefault::Ignore,
+        SIGCONT => SignalDefault::Continue,
+        _ if is_stop_signal(signum) => SignalDefault::Stop,
+        _ => SignalDefault::Terminate
+    }
+}
+
+// 判断是否为停止进程的信号
+pub fn is_stop_signal(signum: usize) -> bool {
+    signum == Signal::SIGSTOP as usize || signum == Signal::SIGTSTP as usize
+        || signum == Signal::SIGTTIN as usize || signum == Signal::SIGTTOU as usize
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct SigSet(u64);
 
@@ -87,6 +147,41 @@ impl SigSet {
     pub fn new(val: u64) -> Self {
         Self(val)
     }
+
+    // 添加信号
+    pub fn add(&mut self, signum: usize) {
+        self.0 |= 1 << (signum - 1);
+    }
+
+    // 移除信号
+    pub fn remove(&mut self, signum: usize) {
+        self.0 &= !(1 << (signum - 1));
+    }
+
+    // 判断是否包含信号
+    pub fn contains(&self, signum: usize) -> bool {
+        self.0 & (1 << (signum - 1)) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    // 获取没有被mask屏蔽的编号最小的信号
+    pub fn first_unmasked(&self, mask: &SigSet) -> Option<usize> {
+        let bits = self.0 & !mask.0;
+        if bits == 0 {
+            None
+        } else {
+            Some(bits.trailing_zeros() as usize + 1)
+        }
+    }
+
+    // SIGKILL和SIGSTOP无法被屏蔽
+    pub fn sanitize(&mut self) {
+        self.remove(Signal::SIGKILL as usize);
+        self.remove(Signal::SIGSTOP as usize);
+    }
 }
 
 impl Default for SigSet {
@@ -159,6 +254,53 @@ pub struct SignalStack {
     pub size: usize,
 }
 
+impl SignalStack {
+    // 未设置的备用信号栈
+    pub fn new() -> Self {
+        Self {
+            sp: 0,
+            flags: SignalStackFlags::DISABLE,
+            size: 0
+        }
+    }
+
+    // 判断栈指针是否在备用信号栈上
+    pub fn contains(&self, sp: usize) -> bool {
+        !self.flags.contains(SignalStackFlags::DISABLE) && sp > self.sp && sp <= self.sp + self.size
+    }
+}
+
+// 传递给SA_SIGINFO处理函数的信号信息 大小为128字节
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SigInfo {
+    pub signo: i32,
+    pub errno: i32,
+    pub code: i32,
+    _align: i32,
+    pub pid: i32,
+    pub uid: u32,
+    pub status: i32,
+    _pad: i32,
+    _rest: [u64; 12]
+}
+
+impl SigInfo {
+    pub fn new(signo: usize, code: i32, pid: usize) -> Self {
+        Self {
+            signo: signo as i32,
+            errno: 0,
+            code,
+            _align: 0,
+            pid: pid as i32,
+            uid: 0,
+            status: 0,
+            _pad: 0,
+            _rest: [0; 12]
+        }
+    }
+}
+
 
 #[repr(C)]
 #[derive(Clone)]
@@ -168,5 +310,33 @@ pub struct SignalUserContext {
     pub stack: SignalStack,     // 2
     pub sig_mask: SigSet,       // 5
     pub _pad: [u64; 16], // very strange, maybe a bug of musl libc
-    pub context: Context,       // pc offset = 22 - 6=16
-}
\ No newline at end of file
+    pub regs: [usize; 32],      // 通用寄存器 x[0]的位置保存返回地址 pc offset = 22 - 6=16
+    pub fp_state: [usize; 66],  // 浮点寄存器 f0~f31之后为fcsr 其余保留
+}
+
+impl SignalUserContext {
+    // 保存上下文和浮点寄存器
+    pub fn save(&mut self, context: &Context, fp: &FpContext) {
+        self.regs.copy_from_slice(&context.x);
+        self.regs[0] = context.sepc;
+        self.fp_state[..32].copy_from_slice(&fp.f);
+        self.fp_state[32] = fp.fcsr;
+    }
+
+    // 恢复上下文和浮点寄存器 保留内核设置的状态寄存器
+    pub fn restore(&self, context: &mut Context, fp: &mut FpContext) {
+        context.x.copy_from_slice(&self.regs);
+        context.sepc = self.regs[0];
+        context.x[0] = 0;
+        fp.f.copy_from_slice(&self.fp_state[..32]);
+        fp.fcsr = self.fp_state[32] & 0xff;
+    }
+}
+
+// 处理信号时压入用户栈的数据 sp指向frame 由sigreturn恢复
+#[repr(C)]
+pub struct SignalFrame {
+    pub info: SigInfo,
+    pub ucontext: SignalUserContext,
+    pub trampoline: [u32; 2]
+}
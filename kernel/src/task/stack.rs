@@ -1,6 +1,6 @@
 use alloc::vec::Vec;
 use alloc::collections::BTreeMap;
-use alloc::rc::Rc;
+use alloc::sync::Arc;
 
 use crate::memory::page_table::PTEFlags;
 use crate::memory::page_table::PageMappingManager;
@@ -20,13 +20,13 @@ pub struct UserStack {
     pub bottom: usize,
     pub top: usize,
     pub pointer: usize,
-    pub pmm: Rc<PageMappingManager>,
+    pub pmm: Arc<PageMappingManager>,
     pub mem_set: MemSet
 }
 
 impl UserStack {
     // 创建新的栈
-    pub fn new(pmm: Rc<PageMappingManager>) -> Result<Self, RuntimeError> {
+    pub fn new(pmm: Arc<PageMappingManager>) -> Result<Self, RuntimeError> {
         let mut mem_set = MemSet::new();
         let mem_map = MemMap::new((DEFAULT_STACK_ADDR / PAGE_SIZE - DEFAULT_STACK_PAGE_NUM).into(), DEFAULT_STACK_PAGE_NUM, PTEFlags::UVRWX)?;
         pmm.add_mapping_by_map(&mem_map)?;
@@ -68,7 +68,7 @@ impl UserStack {
             virt_ptr += 1;
             // 如果虚拟地址越界 则重新映射
             if virt_ptr % 4096 == 0 {
-                phys_ptr = self.pmm.get_phys_addr(VirtAddr::from(self.pointer)).unwrap().0;
+                phys_ptr = self.pmm.get_phys_addr(VirtAddr::from(virt_ptr)).unwrap().0;
             } else {
                 phys_ptr += 1;
             }
@@ -87,32 +87,44 @@ impl UserStack {
         self.push(ptr)
     }
 
-    pub fn init_args(&mut self, args: Vec<&str>, _envp: Vec<&str>, auxv: BTreeMap<usize, usize>) {
-        let envp = self.push_str("LD_LIBRARY_PATH=/");
+    // 初始化程序的栈 从栈顶到栈底依次为
+    // 字符串 对齐 auxv AT_NULL envp NULL argv NULL argc
+    // 执行程序时sp指向argc 按照RISC-V psABI对齐到16字节
+    pub fn init_args(&mut self, args: Vec<&str>, envp: Vec<&str>, auxv: BTreeMap<usize, usize>) {
+        let envp: Vec<usize> = envp.iter().map(|x| self.push_str(x)).collect();
         let args: Vec<usize> = args.iter().map(|x| self.push_str(x)).collect();
-        // auxv top
-        self.push(0);
 
+        // 需要写入的数量为奇数时额外写入一个0 使sp对齐到16字节
+        self.pointer &= !0xf;
+        let words = 1 + (args.len() + 1) + (envp.len() + 1) + (auxv.len() + 1) * 2;
+        if words % 2 == 1 {
+            self.push(0);
+        }
+
+        // auxv AT_NULL
+        self.push(0);
+        self.push(0);
         for (key, value) in auxv {
             self.push(value);
             self.push(key);
         }
-        // envp top
+
+        // envp
         self.push(0);
-        self.push(envp);
-        // argv top
-        self.push(0);    // argv 没有top? 
+        for i in envp.iter().rev() {
+            self.push(*i);
+        }
 
-        // args
-        let args_len = args.len();
+        // argv
+        self.push(0);
         for i in args.iter().rev() {
-            self.push(i.clone());
+            self.push(*i);
         }
-        self.push(args_len);
+        self.push(args.len());
     }
 
     // 复制数据
-    pub fn clone_with_data(&self, pmm: Rc<PageMappingManager>) -> Result<Self, RuntimeError> {
+    pub fn clone_with_data(&self, pmm: Arc<PageMappingManager>) -> Result<Self, RuntimeError> {
         let mem_set = self.mem_set.clone_with_data()?;
 
         pmm.add_mapping_by_set(&mem_set)?;
@@ -131,7 +143,11 @@ impl UserStack {
         self.mem_set.release();
     }
 
-    pub fn alloc_until(&mut self, until_addr: usize) -> Result<(), RuntimeError> {
+    // 扩展栈直到包含until_addr 栈大小不能超过limit(RLIMIT_STACK)
+    pub fn alloc_until(&mut self, until_addr: usize, limit: usize) -> Result<(), RuntimeError> {
+        if until_addr < self.top && self.bottom - until_addr > limit {
+            return Err(RuntimeError::NoEnoughPage);
+        }
         loop {
             if until_addr >= self.top { break; }
             let start_page = self.top / PAGE_SIZE - 1;
@@ -1,14 +1,25 @@
-use core::cell::{RefCell, RefMut};
-use alloc::rc::Rc;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crate::sync::rwlock::{RwLock, RwLockWriteGuard};
+use crate::sync::mutex::Mutex;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
 use crate::interrupt::timer::TimeSpec;
+use crate::interrupt::timer::TimerId;
+use crate::interrupt::timer::add_timer;
 use crate::memory::addr::UserAddr;
-use crate::interrupt::Context;
+use crate::interrupt::{Context, FpContext};
+use riscv::register::sstatus::{self, FS};
 use crate::task::task_scheduler::kill_task;
 
 use super::process::Process;
 use super::signal::SigSet;
+use super::signal::SignalStack;
+use super::scheduler::SchedEntity;
+use super::futex::TaskFutex;
+use super::futex::RobustListHead;
 
 #[allow(unused)]
+#[repr(C)]
 #[derive(Clone, Copy)]
 pub struct Rusage {
     pub ru_utime: TimeSpec, /* user CPU time used */
@@ -29,6 +40,30 @@ pub struct Rusage {
     ru_nivcsw: isize,      // 暂不使用
 }
 
+impl Rusage {
+    // 根据运行时间(微秒)创建 rusage中的时间为timeval 第二项为微秒
+    pub fn new(utime: u64, stime: u64) -> Self {
+        Self {
+            ru_utime: TimeSpec { tv_sec: (utime / 1_000_000) as usize, tv_nsec: (utime % 1_000_000) as usize },
+            ru_stime: TimeSpec { tv_sec: (stime / 1_000_000) as usize, tv_nsec: (stime % 1_000_000) as usize },
+            ru_maxrss: 0,
+            ru_ixrss: 0,
+            ru_idrss: 0,
+            ru_isrss: 0,
+            ru_minflt: 0,
+            ru_majflt: 0,
+            ru_nswap: 0,
+            ru_inblock: 0,
+            ru_oublock: 0,
+            ru_msgsnd: 0,
+            ru_msgrcv: 0,
+            ru_nsignals: 0,
+            ru_nvcsw: 0,
+            ru_nivcsw: 0
+        }
+    }
+}
+
 
 #[derive(Clone, Copy, PartialEq)]
 // 任务状态
@@ -41,40 +76,78 @@ pub enum TaskStatus {
     WAITING = 5,
 }
 
+impl From<usize> for TaskStatus {
+    fn from(status: usize) -> Self {
+        match status {
+            0 => TaskStatus::READY,
+            1 => TaskStatus::RUNNING,
+            2 => TaskStatus::PAUSE,
+            3 => TaskStatus::STOP,
+            4 => TaskStatus::EXIT,
+            _ => TaskStatus::WAITING
+        }
+    }
+}
+
+// 线程的信号屏蔽字和待处理信号 进程发送信号时持有进程锁访问 不放在TaskInner中
+pub struct TaskSignal {
+    pub sig_mask: SigSet,
+    pub sig_pending: SigSet,        // 发送给当前线程的信号
+}
+
 pub struct TaskInner {
     pub context: Context,
-    pub process: Rc<RefCell<Process>>,
-    pub status: TaskStatus,
+    pub fp: FpContext,              // 用户程序修改浮点寄存器后保存 运行前恢复
     pub wake_time: usize,
-    pub sig_mask: SigSet
+    pub sig_stack: SignalStack,     // 备用信号栈
+    pub io_done: usize,             // 阻塞的写入已经完成的长度 重新执行系统调用时继续写入
+    pub robust_list: UserAddr<RobustListHead>
 }
 
-#[derive(Clone)]
+// 调度器和其他核心访问的状态不放在inner中 避免持有调度器锁或进程锁时获取inner
 pub struct Task {
     pub tid: usize,
     pub pid: usize,
-    pub clear_child_tid: RefCell<UserAddr<u32>>,
-    pub inner: Rc<RefCell<TaskInner>>
+    pub clear_child_tid: RwLock<UserAddr<u32>>,
+    pub process: Arc<RwLock<Process>>,  // 所属进程 创建后不再改变
+    status: AtomicUsize,            // 任务状态 TaskStatus
+    wake_pending: AtomicBool,       // 进入等待之前收到的唤醒 由调度器在检查状态时补上
+    interrupted: AtomicBool,        // 等待被信号打断 处理信号时决定是否重新执行系统调用
+    pub sched: Mutex<SchedEntity>,  // 调度信息 调度器持有调度器锁时访问
+    pub signal: Mutex<TaskSignal>,
+    pub futex: Mutex<TaskFutex>,
+    pub inner: RwLock<TaskInner>
 }
 
 impl Task {
     // 创建进程
-    pub fn new(tid: usize, process: Rc<RefCell<Process>>) -> Rc<Self> {
-        let mut process_mut = process.borrow_mut();
+    pub fn new(tid: usize, process: Arc<RwLock<Process>>) -> Arc<Self> {
+        let mut process_mut = process.write();
         let pid = process_mut.pid;
-        let task = Rc::new(Self {
+        let task = Arc::new(Self {
             tid,
             pid,
-            clear_child_tid: RefCell::new(0.into()),
-            inner: Rc::new(RefCell::new(TaskInner {
+            clear_child_tid: RwLock::new(0.into()),
+            process: process.clone(),
+            status: AtomicUsize::new(TaskStatus::READY as usize),
+            wake_pending: AtomicBool::new(false),
+            interrupted: AtomicBool::new(false),
+            sched: Mutex::new(SchedEntity::new()),
+            signal: Mutex::new(TaskSignal {
+                sig_mask: SigSet::new(0),
+                sig_pending: SigSet::new(0)
+            }),
+            futex: Mutex::new(TaskFutex::new()),
+            inner: RwLock::new(TaskInner {
                 context: Context::new(), 
-                process: process.clone(), 
-                status: TaskStatus::READY,
+                fp: FpContext::new(),
                 wake_time: 0,
-                sig_mask: SigSet::new(0)
-            }))
+                sig_stack: SignalStack::new(),
+                io_done: 0,
+                robust_list: 0.into()
+            })
         });
-        process_mut.tasks.push(Rc::downgrade(&task));
+        process_mut.tasks.push(Arc::downgrade(&task));
         task
     }
 
@@ -83,16 +156,70 @@ impl Task {
         kill_task(self.pid, self.tid);
     }
 
+    // 获取任务状态
+    pub fn status(&self) -> TaskStatus {
+        self.status.load(Ordering::Acquire).into()
+    }
+
+    // 设置任务状态 已经被结束的任务保持结束状态
+    pub fn set_status(&self, status: TaskStatus) {
+        let _ = self.status.fetch_update(Ordering::AcqRel, Ordering::Acquire, |x| {
+            if x == TaskStatus::EXIT as usize { None } else { Some(status as usize) }
+        });
+    }
+
+    // 状态为from时改为to 返回是否成功
+    pub fn change_status(&self, from: TaskStatus, to: TaskStatus) -> bool {
+        self.status.compare_exchange(from as usize, to as usize, Ordering::AcqRel, Ordering::Acquire).is_ok()
+    }
+
+    // 唤醒等待中的任务
+    pub fn wake(&self) {
+        // 任务还没有进入等待 记录唤醒 避免在进入等待之前丢失
+        if !self.change_status(TaskStatus::WAITING, TaskStatus::READY) {
+            self.wake_pending.store(true, Ordering::Release);
+        }
+    }
+
+    // 处理被记录的唤醒 返回任务当前的状态
+    pub fn check_status(&self) -> TaskStatus {
+        if self.wake_pending.swap(false, Ordering::AcqRel) {
+            self.change_status(TaskStatus::WAITING, TaskStatus::READY);
+        }
+        self.status()
+    }
+
+    // 信号到达时打断等待 返回用户态时处理信号
+    pub fn interrupt_wait(&self) {
+        if self.change_status(TaskStatus::WAITING, TaskStatus::READY) {
+            self.interrupted.store(true, Ordering::Release);
+        }
+    }
+
+    // 获取并清除等待被打断的标志
+    pub fn take_interrupted(&self) -> bool {
+        self.interrupted.swap(false, Ordering::AcqRel)
+    }
+
+    // 添加定时器 在deadline(微秒)时唤醒任务
+    pub fn wake_at(self: &Arc<Self>, deadline: usize) -> TimerId {
+        let task = Arc::downgrade(self);
+        add_timer(deadline, Box::new(move || {
+            if let Some(task) = task.upgrade() {
+                task.wake();
+            }
+        }))
+    }
+
     // 设置 tid ptr
     pub fn set_tid_address(&self, tid_ptr: UserAddr<u32>) {
-        *self.clear_child_tid.borrow_mut() = tid_ptr;
+        *self.clear_child_tid.write() = tid_ptr;
     }
 
     pub fn before_run(&self) {
         debug!("run before task");
-        let inner = self.inner.borrow();
-        let process = inner.process.borrow_mut();
-        process.pmm.change_satp();
+        let process = self.process.read();
+        process.mm.read().pmm.change_satp();
     }
 
     // 运行当前任务
@@ -101,29 +228,43 @@ impl Task {
             // 改变任务
             fn change_task(stack: usize);
         }
-        let inner = self.inner.borrow_mut();
-        let process = inner.process.borrow_mut();
-        // 可能需要更换内存
-        // usleep(1000);
-        let context_ptr = &inner.context as *const Context as usize;
-        // 释放资源
-        drop(process);
-        drop(inner);
+        // 运行期间当前核心独占任务的上下文 中断时直接保存到TaskInner中
+        let context_ptr = {
+            let mut inner = self.inner.write();
+            // 恢复浮点寄存器 标记为干净 用户程序修改后变为脏
+            inner.fp.restore();
+            &mut inner.context as *mut Context as usize
+        };
         unsafe {
+            sstatus::set_fs(FS::Clean);
             change_task(context_ptr)
         };
+        // 用户程序修改了浮点寄存器 保存到任务中
+        if sstatus::read().fs() == FS::Dirty {
+            self.inner.write().fp.save();
+        }
+    }
+
+    // 记录任务的运行时间 单位为微秒
+    pub fn account_time(&self, utime: usize, stime: usize) {
+        let process = self.get_process();
+        let mut process = process.write();
+        let last_time = process.tms.tms_utime + process.tms.tms_stime;
+        process.tms.tms_utime += utime as u64;
+        process.tms.tms_stime += stime as u64;
+        process.check_cpu_limit(last_time);
     }
 
     // 获取process
-    pub fn get_process(&self) -> Rc<RefCell<Process>> {
-        self.inner.borrow_mut().process.clone()
+    pub fn get_process(&self) -> Arc<RwLock<Process>> {
+        self.process.clone()
     }
 
     pub fn update_context(&self, f: fn(&mut Context)) {
-        f(&mut self.inner.borrow_mut().context)
+        f(&mut self.inner.write().context)
     }
 
-    pub fn update_inner(&self, f: fn(RefMut<TaskInner>)) {
-        f(self.inner.borrow_mut())
+    pub fn update_inner(&self, f: fn(RwLockWriteGuard<TaskInner>)) {
+        f(self.inner.write())
     }
 }
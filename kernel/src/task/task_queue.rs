@@ -48,7 +48,7 @@ lazy_static! {
         // "lmbench_all lat_ctx -P 1 -s 32 2 4 8 16 24 32 64 96",  // 最后执行 可能出现问题
 
         // "lmbench_all lat_sig -P 1 prot lat_sig", // 暂时出问题
-        // "lmbench_all lat_pipe -P 1", // 暂时出问题
+        "lmbench_all lat_pipe -P 1",
         // "lmbench_all lmdd label=\"File /var/tmp/XXX write bandwidth:\" of=/var/tmp/XXX move=1m fsync=1 print=3", // 暂时出问题
         // "lmbench_all lat_pagefault -P 1 /var/tmp/XXX",  // 暂时出问题
 
@@ -56,7 +56,7 @@ lazy_static! {
         "lmbench_all lat_fs /var/tmp",   // 暂时出问题
         
         // Bandwidth measurements
-        // "lmbench_all bw_pipe -P 1",      // 暂时出问题
+        "lmbench_all bw_pipe -P 1",
 
     ]));
 }
@@ -1,152 +1,360 @@
 use core::arch::asm;
+use riscv::register::sip;
 
-use alloc::collections::VecDeque;
-use alloc::rc::Rc;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use crate::sync::rwlock::RwLock;
 use crate::sync::mutex::Mutex;
-use crate::sys_call::is_vfork_wait;
+use crate::sync::mutex::MutexGuard;
+use crate::cpu::{MAX_HARTS, hart_id, is_boot_hart, set_online};
+use crate::sbi::send_ipi;
 use crate::task::pid::PidGenerater;
 use crate::interrupt::timer::task_time_refresh;
+use crate::interrupt::timer::timer_handler;
+use crate::interrupt::timer::get_time_us;
+use crate::interrupt::timer::check_timers;
 use crate::memory::page_table::switch_to_kernel_page;
 use super::task::Task;
+use super::process::Process;
+use super::process::handle_parent_events;
 use super::task::TaskStatus;
 use super::task_queue::load_next_task;
+use super::scheduler::Scheduler;
+use super::scheduler::FairScheduler;
+use super::scheduler::is_runnable;
 
 // 任务控制器管理器
 pub struct TaskScheduler {
-    pub queue: VecDeque<Rc<Task>>,          // 准备队列
-    pub is_run: bool                    // 任务运行标志
+    pub current: Option<Arc<Task>>,          // 当前运行的任务
+    pub scheduler: Box<dyn Scheduler>,      // 调度器 管理其他的任务
+    pub run_start: usize,                   // 当前任务开始运行的时间
+    pub is_run: bool                        // 任务运行标志
 }
 
 impl TaskScheduler {
     // 创建Task调度器
     pub fn new() -> Self {
+        Self::with_scheduler(Box::new(FairScheduler::new()))
+    }
+
+    // 使用指定的调度器创建Task调度器
+    pub fn with_scheduler(scheduler: Box<dyn Scheduler>) -> Self {
         Self {
-            queue: VecDeque::new(),
+            current: None,
+            scheduler,
+            run_start: 0,
             is_run: false
         }
     }
 
     // 添加任务调度器
-    pub fn add_task(&mut self, task: Rc<Task>) {
-        self.queue.push_back(task.clone());
+    pub fn add_task(&mut self, task: Arc<Task>) {
+        self.scheduler.add_task(task);
     }
 
     // 执行下一个任务
     pub fn switch_next(&mut self) {
-        if let Some(task) = self.queue.pop_front() {
-            // task.before_run();
-            task.inner.borrow_mut().status = TaskStatus::READY;
-            self.queue.push_back(task);
-            self.queue[0].before_run();
+        if let Some(task) = self.current.take() {
+            let runtime = get_time_us() - self.run_start;
+            // 已经结束的任务不再放回调度器
+            if task.status() != TaskStatus::EXIT {
+                self.scheduler.put_prev(task.clone(), runtime);
+            }
+            // 等待中的任务保持等待状态
+            task.change_status(TaskStatus::RUNNING, TaskStatus::READY);
         }
         task_time_refresh();     
     }
 
-    // 执行第一个任务
-    /// 进行调度更新
-    pub fn start(&mut self) {
-        info!("开始执行任务");
-        loop {
-            // 没有任务时从任务队列取出任务
-            if self.queue.len() == 0 {
-                if !load_next_task() {
-                    break;
-                }
-                unsafe {
-                    // 为什么需要缓冲？
-                    for i in 0..10 {
-                        asm!("nop");
-                    }
-                }
+    // 选择下一个运行的任务 当前任务无法继续运行时切换任务
+    // 本地没有可以运行的任务时返回None 由调用者从其他核心窃取
+    pub fn schedule(&mut self) -> Option<Arc<Task>> {
+        if let Some(task) = self.current.clone() {
+            if is_runnable(&task) {
+                return Some(task);
             }
-            // TODO: 判断是否存在等待中的任务 如果存在就切换任务
-            let task = self.queue[0].clone();
-            if is_vfork_wait(task.pid) {
-                switch_next();
-                continue;
-            }
-            self.is_run = true;
-            warn!("执行pid: {}   tid: {}   tasks len: {}", task.pid, task.tid, self.queue.len());
-            task.run();
-            task.catch();
+            self.switch_next();
         }
+        let task = self.scheduler.pick_next()?;
+        self.set_current(task.clone());
+        Some(task)
     }
 
-    // 关闭进程
-    pub fn kill_process(&mut self, pid: usize) {
-        self.queue = self.queue.clone().into_iter().filter(|x| x.pid != pid).collect();
+    // 设置当前运行的任务 由调用者在释放调度器锁后切换页表
+    pub fn set_current(&mut self, task: Arc<Task>) {
+        task.set_status(TaskStatus::RUNNING);
+        self.current = Some(task);
+        self.run_start = get_time_us();
     }
 
-    // 关闭进程
-    pub fn kill_task(&mut self, pid: usize, tid: usize) {
-        self.queue = self.queue.clone().into_iter().filter(|x| x.pid != pid || x.tid != tid).collect();
+    // 获取任务数量
+    pub fn len(&self) -> usize {
+        self.scheduler.len() + if self.current.is_some() { 1 } else { 0 }
+    }
+
+    // 获取所有的任务
+    pub fn tasks(&self) -> Vec<Arc<Task>> {
+        let mut tasks = self.scheduler.tasks();
+        if let Some(task) = &self.current {
+            tasks.push(task.clone());
+        }
+        tasks
+    }
+
+    // 结束满足条件的任务 当前任务标记为退出 由运行它的核心回到调度循环后移除
+    // 返回被移除的任务和当前任务是否被结束
+    pub fn kill(&mut self, f: &dyn Fn(&Arc<Task>) -> bool) -> (Vec<Arc<Task>>, bool) {
+        let removed = self.scheduler.remove(f);
+        let running = match &self.current {
+            Some(task) if f(task) => {
+                task.set_status(TaskStatus::EXIT);
+                true
+            }
+            _ => false
+        };
+        (removed, running)
+    }
+
+    // 调度参数改变后重新加入调度器 当前任务在放回时生效
+    pub fn requeue(&mut self, task: Arc<Task>) {
+        if self.current.as_ref().map_or(false, |x| Arc::ptr_eq(x, &task)) {
+            return;
+        }
+        // 任务不在当前调度器中
+        if !self.scheduler.tasks().iter().any(|x| Arc::ptr_eq(x, &task)) {
+            return;
+        }
+        self.scheduler.remove(&|x| Arc::ptr_eq(x, &task));
+        self.scheduler.add_task(task);
     }
 
 }
 
 lazy_static! {
-    // 任务管理器和pid生成器
-    pub static ref TASK_SCHEDULER: Mutex<TaskScheduler> = Mutex::new(TaskScheduler::new());
+    // 每个核心的任务管理器和pid生成器
+    pub static ref TASK_SCHEDULERS: Vec<Mutex<TaskScheduler>> = (0..MAX_HARTS).map(|_| Mutex::new(TaskScheduler::new())).collect();
     pub static ref NEXT_PID: Mutex<PidGenerater> = Mutex::new(PidGenerater::new());
 }
 
+// 获取当前核心的任务管理器
+// 调度器锁只保护调度队列 持有期间只获取任务的调度信息 不能获取任务和进程的锁
+fn local_scheduler() -> MutexGuard<'static, TaskScheduler> {
+    TASK_SCHEDULERS[hart_id()].lock()
+}
+
+// 获取所有核心的任务
+fn all_tasks() -> Vec<Arc<Task>> {
+    TASK_SCHEDULERS.iter().flat_map(|x| x.lock().tasks()).collect()
+}
+
+// 从其他核心的调度器中窃取一个可以运行的任务 设置为当前核心的当前任务
+// 任务在两个调度器之间转移时始终可以被找到 互相窃取时使用try_lock避免死锁
+fn steal_task(scheduler: &mut TaskScheduler) -> Option<Arc<Task>> {
+    let hart_id = hart_id();
+    let task = (0..MAX_HARTS).filter(|&i| i != hart_id)
+        .find_map(|i| TASK_SCHEDULERS[i].try_lock().and_then(|mut x| x.scheduler.pick_next()))?;
+    scheduler.set_current(task.clone());
+    Some(task)
+}
+
+// 获取其他核心中当前任务满足条件的核心掩码
+fn running_harts(f: &dyn Fn(&Arc<Task>) -> bool) -> usize {
+    let hart_id = hart_id();
+    (0..MAX_HARTS).filter(|&i| i != hart_id)
+        .filter(|&i| TASK_SCHEDULERS[i].lock().current.as_ref().map_or(false, |x| f(x)))
+        .fold(0, |mask, i| mask | (1 << i))
+}
+
+// 没有可以运行的任务 等待中断
+fn idle() {
+    unsafe {
+        asm!("wfi");
+    }
+    // 内核态不开启中断 wfi被唤醒时不会进入中断处理 只有时钟中断等待处理时才更新时钟
+    if sip::read().stimer() {
+        timer_handler();
+    }
+}
+
+// 执行任务 直到所有任务结束
 pub fn start_tasks() {
     // 刷新下一个调度时间
-    // info!("开始任务");
     task_time_refresh();
-    let mut task_scheduler = TASK_SCHEDULER.force_get();
-    task_scheduler.start();
-    switch_to_kernel_page();
+    info!("开始执行任务");
+    // 开始参与调度后需要响应TLB刷新请求
+    set_online();
+    // 上一次运行的任务 切换任务时切换页表
+    let mut last: Option<Arc<Task>> = None;
+    loop {
+        // 所有核心都没有任务时 由主核心从任务队列取出任务
+        if get_task_num() == 0 {
+            if last.take().is_some() {
+                switch_to_kernel_page();
+            }
+            if !is_boot_hart() {
+                idle();
+                continue;
+            }
+            if !load_next_task() {
+                break;
+            }
+            unsafe {
+                // 为什么需要缓冲？
+                for i in 0..10 {
+                    asm!("nop");
+                }
+            }
+        }
+        // 通知父进程子进程的状态改变 执行到期的定时器 唤醒睡眠的任务
+        handle_parent_events();
+        check_timers();
+        // 选择下一个任务 本地没有可以运行的任务时从其他核心窃取 全部在等待时进入空闲
+        let next = {
+            let mut scheduler = local_scheduler();
+            let next = scheduler.schedule().or_else(|| steal_task(&mut scheduler));
+            if let Some(task) = &next {
+                scheduler.is_run = true;
+                warn!("执行pid: {}   tid: {}   tasks len: {}", task.pid, task.tid, scheduler.len());
+            }
+            next
+        };
+        let task = match next {
+            Some(task) => task,
+            None => {
+                // 空闲时离开上一个任务的页表 地址空间可能被其他核心释放
+                if last.take().is_some() {
+                    switch_to_kernel_page();
+                }
+                idle();
+                continue;
+            }
+        };
+        // 运行期间持有地址空间 进程在其他核心上结束时 由最后使用的核心释放
+        let mm = task.get_process().read().mm.clone();
+        if !last.as_ref().map_or(false, |x| Arc::ptr_eq(x, &task)) {
+            mm.read().pmm.change_satp();
+            last = Some(task.clone());
+        }
+        // 返回用户态之前处理信号 信号结束或停止当前任务时重新调度
+        task.handle_signals();
+        if get_current_task().is_some() && task.status() != TaskStatus::EXIT {
+            // 记录用户态和内核态的运行时间
+            let run_time = get_time_us();
+            task.run();
+            // 在用户态运行期间被其他核心结束时不再处理中断
+            if task.status() != TaskStatus::EXIT {
+                let trap_time = get_time_us();
+                task.catch();
+                task.account_time(trap_time - run_time, get_time_us() - trap_time);
+            }
+        }
+        // 任务已经结束 离开它的页表后再释放地址空间
+        if task.status() == TaskStatus::EXIT {
+            let mut scheduler = local_scheduler();
+            if scheduler.current.as_ref().map_or(false, |x| Arc::ptr_eq(x, &task)) {
+                scheduler.current = None;
+            }
+            drop(scheduler);
+            switch_to_kernel_page();
+            last = None;
+        }
+        drop(mm);
+    }
     // 切换到内核页表
+    switch_to_kernel_page();
+}
+
+pub fn add_task_to_scheduler(task: Arc<Task>) {
+    local_scheduler().add_task(task);
 }
 
-pub fn add_task_to_scheduler(task: Rc<Task>) {
-    TASK_SCHEDULER.force_get().add_task(task);
+// 结束满足条件的任务 通知其他核心离开用户态
+// 运行中的任务持有地址空间 释放内存时不需要等待其他核心
+// 按顺序持有所有调度器的锁 正在被窃取的任务不会被遗漏
+fn kill_tasks(f: &dyn Fn(&Arc<Task>) -> bool) {
+    let hart_id = hart_id();
+    let mut hart_mask = 0;
+    let mut removed = Vec::new();
+    let mut schedulers: Vec<_> = TASK_SCHEDULERS.iter().map(|x| x.lock()).collect();
+    for (i, scheduler) in schedulers.iter_mut().enumerate() {
+        let (tasks, running) = scheduler.kill(f);
+        removed.extend(tasks);
+        if running && i != hart_id {
+            hart_mask |= 1 << i;
+        }
+    }
+    // 释放任务可能释放进程和文件 不能持有调度器锁
+    drop(schedulers);
+    drop(removed);
+    if hart_mask != 0 {
+        send_ipi(hart_mask, 0);
+    }
 }
 
 pub fn kill_process(pid: usize) {
-    TASK_SCHEDULER.force_get().kill_process(pid);
+    kill_tasks(&|x| x.pid == pid);
 }
 
 pub fn kill_task(pid: usize, tid: usize) {
-    TASK_SCHEDULER.force_get().kill_task(pid, tid);
+    kill_tasks(&|x| x.pid == pid && x.tid == tid);
+}
+
+// 通知在其他核心上运行的任务进入内核 处理新的信号或者状态
+pub fn kick_task(task: &Task) {
+    let hart_mask = running_harts(&|x| x.tid == task.tid);
+    if hart_mask != 0 {
+        send_ipi(hart_mask, 0);
+    }
 }
 
 pub fn switch_next() {
-    TASK_SCHEDULER.force_get().switch_next();
+    local_scheduler().switch_next();
 }
 
-pub fn get_current_task() -> Option<Rc<Task>> {
-    match TASK_SCHEDULER.force_get().queue.front() {
-        Some(task) => Some(task.clone()),
-        None => None
-    }
+pub fn get_current_task() -> Option<Arc<Task>> {
+    local_scheduler().current.clone()
 }
 
-pub fn get_task(pid: usize, tid: usize) -> Option<Rc<Task>> {
-    let task_scheduler = TASK_SCHEDULER.force_get();
-    for i in 0..task_scheduler.queue.len() {
-        let task = task_scheduler.queue[i].clone();
-        if task.pid == pid && task.tid == tid {
-            return Some(task.clone());
+pub fn get_task(pid: usize, tid: usize) -> Option<Arc<Task>> {
+    all_tasks().into_iter().find(|x| x.pid == pid && x.tid == tid)
+}
+
+// 根据tid获取任务 tid在所有进程中唯一
+pub fn get_task_by_tid(tid: usize) -> Option<Arc<Task>> {
+    all_tasks().into_iter().find(|x| x.tid == tid)
+}
+
+// 获取进程的所有任务
+pub fn get_process_tasks(pid: usize) -> Vec<Arc<Task>> {
+    all_tasks().into_iter().filter(|x| x.pid == pid).collect()
+}
+
+// 获取所有的进程
+pub fn get_all_processes() -> Vec<Arc<RwLock<Process>>> {
+    let mut processes: Vec<Arc<RwLock<Process>>> = Vec::new();
+    for task in all_tasks() {
+        let process = task.process.clone();
+        if !processes.iter().any(|x| Arc::ptr_eq(x, &process)) {
+            processes.push(process);
         }
     }
-    None
+    processes
 }
 
-pub fn switch_to_task(pid: usize, tid: usize) {
-    let mut task_scheduler = TASK_SCHEDULER.force_get();
+// 根据pid获取进程
+pub fn get_process_by_pid(pid: usize) -> Option<Arc<RwLock<Process>>> {
+    all_tasks().into_iter().find(|x| x.pid == pid).map(|x| x.process.clone())
+}
 
-    while let Some(task) = task_scheduler.queue.pop_front() {
-        let ctask = task.clone();
-        task_scheduler.queue.push_back(task);
-        if ctask.tid == tid && ctask.pid == pid {
-            break;
-        }
+// 调度参数改变后更新任务在调度器中的位置
+pub fn requeue_task(task: Arc<Task>) {
+    for scheduler in TASK_SCHEDULERS.iter() {
+        scheduler.lock().requeue(task.clone());
     }
 }
 
 // 获取当前的任务数量
 pub fn get_task_num() -> usize {
-    TASK_SCHEDULER.force_get().queue.len()
-}
\ No newline at end of file
+    TASK_SCHEDULERS.iter().map(|x| x.lock().len()).sum()
+}
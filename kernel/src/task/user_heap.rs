@@ -1,4 +1,4 @@
-use alloc::rc::Rc;
+use alloc::sync::Arc;
 
 use crate::memory::page_table::PageMappingManager;
 use crate::memory::page_table::PTEFlags;
@@ -23,13 +23,13 @@ pub struct UserHeap {
     pub pointer: usize,
     pub end: usize,
     pub temp: usize,
-    pub pmm: Rc<PageMappingManager>,
+    pub pmm: Arc<PageMappingManager>,
     pub mem_set: MemSet
 }
 
 impl UserHeap {
     // 创建heap
-    pub fn new(pmm: Rc<PageMappingManager>) -> Result<Self, RuntimeError> {
+    pub fn new(pmm: Arc<PageMappingManager>) -> Result<Self, RuntimeError> {
         let mem_set = MemSet::new();
         // 申请页表作为heap
         Ok(UserHeap {
@@ -82,7 +82,7 @@ impl UserHeap {
     }
 
     // 获取临时页表
-    pub fn get_temp(&mut self, pmm: Rc<PageMappingManager>) -> Result<PhysAddr, RuntimeError>{
+    pub fn get_temp(&mut self, pmm: Arc<PageMappingManager>) -> Result<PhysAddr, RuntimeError>{
         if self.temp == 0 {
             let mem_map = MemMap::new(0xe0000usize.into(), 1, PTEFlags::UVRWX).unwrap();
             self.temp = mem_map.ppn.into();
@@ -97,7 +97,7 @@ impl UserHeap {
         get_buf_from_phys_page(self.temp.into(), 1).fill(0)
     }
 
-    pub fn clone_with_data(&self, pmm: Rc<PageMappingManager>) -> Result<Self, RuntimeError> {
+    pub fn clone_with_data(&self, pmm: Arc<PageMappingManager>) -> Result<Self, RuntimeError> {
         let mem_set = self.mem_set.clone_with_data()?;
         pmm.add_mapping_by_set(&mem_set)?;
         Ok(Self {
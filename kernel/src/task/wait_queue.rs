@@ -0,0 +1,80 @@
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::sync::Weak;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+
+use crate::sync::mutex::Mutex;
+
+use super::task::Task;
+use super::task::TaskStatus;
+
+// 等待队列 任务在队列中等待 直到被唤醒
+pub struct WaitQueue(VecDeque<Weak<Task>>);
+
+impl WaitQueue {
+    // 创建等待队列
+    pub fn new() -> Self {
+        Self(VecDeque::new())
+    }
+
+    // 任务进入等待状态 持有队列所在的锁时检查条件并调用 唤醒者修改条件后获取同一个锁
+    pub fn wait(&mut self, task: Arc<Task>) {
+        task.set_status(TaskStatus::WAITING);
+        self.0.push_back(Arc::downgrade(&task));
+    }
+
+    // 唤醒一个任务 返回是否唤醒成功
+    pub fn notify_one(&mut self) -> bool {
+        while let Some(task) = self.0.pop_front() {
+            if let Some(task) = task.upgrade() {
+                task.wake();
+                return true;
+            }
+        }
+        false
+    }
+
+    // 唤醒所有的任务 返回唤醒的数量
+    pub fn notify_all(&mut self) -> usize {
+        let mut count = 0;
+        while let Some(task) = self.0.pop_front() {
+            if let Some(task) = task.upgrade() {
+                task.wake();
+                count += 1;
+            }
+        }
+        count
+    }
+
+    // 判断是否有任务在等待
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+lazy_static! {
+    // ppoll 等待队列 文件状态改变时唤醒
+    pub static ref POLL_QUEUE: Mutex<WaitQueue> = Mutex::new(WaitQueue::new());
+}
+
+// 文件状态改变的次数 ppoll在检查文件状态之前读取
+static POLL_EVENTS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn poll_events() -> usize {
+    POLL_EVENTS.load(Ordering::Acquire)
+}
+
+// 文件状态改变 唤醒ppoll中等待的任务
+pub fn notify_poll() {
+    POLL_EVENTS.fetch_add(1, Ordering::AcqRel);
+    POLL_QUEUE.lock().notify_all();
+}
+
+// ppoll进入等待 检查文件状态之后有文件状态改变时不等待 直接重新检查
+pub fn poll_wait(task: Arc<Task>, events: usize) {
+    let mut queue = POLL_QUEUE.lock();
+    if POLL_EVENTS.load(Ordering::Acquire) == events {
+        queue.wait(task);
+    }
+}